@@ -0,0 +1,47 @@
+"""Builds data/english-words.txt from harper-core's dictionary, expanding each word's affixes.
+See data/README.md."""
+import json, re, sys
+base = sys.argv[1].rstrip('/') + '/'
+affixes = json.load(open(base + 'annotations.json'))['affixes']
+lines = open(base + 'dictionary.dict').read().split('\n')
+start = lines.index('# Start of original dictionary import')
+end = lines.index('# End of original dictionary imports')
+words = set()
+def apply(word, flag):
+    a = affixes[flag]
+    out = []
+    for r in a['replacements']:
+        if a['kind'] == 'suffix':
+            if re.search('(?:' + r['condition'] + ')$', word) and word.endswith(r['remove']):
+                out.append(word[:len(word) - len(r['remove'])] + r['add'])
+        else:
+            if re.match(r['condition'], word) and word.startswith(r['remove']):
+                out.append(r['add'] + word[len(r['remove']):])
+    return out
+for line in lines[start + 1:end]:
+    line = line.split('#')[0].strip()
+    if not line:
+        continue
+    word, _, flags = line.partition('/')
+    flags = [f for f in flags if f in affixes]
+    words.add(word)
+    suffixes = [f for f in flags if affixes[f]['kind'] == 'suffix']
+    prefixes = [f for f in flags if affixes[f]['kind'] == 'prefix']
+    for s in suffixes:
+        words.update(apply(word, s))
+    for p in prefixes:
+        for w in apply(word, p):
+            words.add(w)
+            if affixes[p]['cross_product']:
+                for s in suffixes:
+                    if affixes[s]['cross_product']:
+                        words.update(apply(w, s))
+out = sorted({
+    w.lower()
+    for w in words
+    if re.fullmatch(r"[A-Za-z]+(?:'[A-Za-z]+)*", w)
+    and not w.endswith("'s")
+    # Letters of the alphabet are listed as words, but only these are words on their own
+    and (len(w) > 1 or w.lower() in "aio")
+})
+print('\n'.join(out))
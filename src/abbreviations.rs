@@ -0,0 +1,431 @@
+use regex::Regex;
+use std::fs;
+
+use crate::language::Language;
+
+/// When an abbreviation is expanded, and what its full stop means
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// Always expanded, and a full stop followed by a new sentence is kept, eg "etc."
+    Always,
+    /// Always expanded, and always followed by the word it belongs to, so the full stop never
+    /// ends a sentence, eg "Mr." and "e.g."
+    Leading,
+    /// Only expanded before a number, eg "No. 5", "p. 12"
+    BeforeNumber,
+    /// Only expanded before a capitalised word, eg "Mt. Everest"
+    BeforeName,
+    /// The expansion before a name, or this one after a name or number, eg "St. Paul" is
+    /// "Saint Paul" but "Baker St." is "Baker Street"
+    Either(&'static str),
+}
+
+/// A single abbreviation and what it expands to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Abbreviation {
+    abbreviated: String,
+    expansion: String,
+    context: Context,
+}
+
+const BUILT_IN: [(&str, &str, Context); 78] = [
+    // Titles
+    ("Mr.", "Mister", Context::Leading),
+    ("Mrs.", "Missus", Context::Leading),
+    ("Ms.", "Miz", Context::Leading),
+    ("Messrs.", "Messieurs", Context::Leading),
+    ("Dr.", "Doctor", Context::Either("Drive")),
+    ("Prof.", "Professor", Context::Leading),
+    ("Rev.", "Reverend", Context::Leading),
+    ("Fr.", "Father", Context::BeforeName),
+    ("Sr.", "Sister", Context::Either("Senior")),
+    ("Jr.", "Junior", Context::Always),
+    ("Hon.", "Honourable", Context::Leading),
+    ("Gov.", "Governor", Context::Leading),
+    ("Sen.", "Senator", Context::Leading),
+    ("Rep.", "Representative", Context::BeforeName),
+    ("Pres.", "President", Context::Leading),
+    ("Supt.", "Superintendent", Context::Leading),
+    ("Insp.", "Inspector", Context::Leading),
+    ("Det.", "Detective", Context::Leading),
+    // Military ranks
+    ("Pvt.", "Private", Context::Leading),
+    ("Cpl.", "Corporal", Context::Leading),
+    ("Sgt.", "Sergeant", Context::Leading),
+    ("Lt.", "Lieutenant", Context::Leading),
+    ("Capt.", "Captain", Context::Leading),
+    ("Maj.", "Major", Context::Leading),
+    ("Col.", "Colonel", Context::Leading),
+    ("Gen.", "General", Context::Leading),
+    ("Brig.", "Brigadier", Context::Leading),
+    ("Cmdr.", "Commander", Context::Leading),
+    ("Adm.", "Admiral", Context::Leading),
+    // Places
+    ("St.", "Saint", Context::Either("Street")),
+    ("Mt.", "Mount", Context::BeforeName),
+    ("Ft.", "Fort", Context::BeforeName),
+    ("Ave.", "Avenue", Context::Always),
+    ("Rd.", "Road", Context::Always),
+    ("Blvd.", "Boulevard", Context::Always),
+    ("Sq.", "Square", Context::Always),
+    ("Pl.", "Place", Context::Always),
+    ("Ln.", "Lane", Context::Always),
+    // Companies
+    ("Co.", "Company", Context::Always),
+    ("Corp.", "Corporation", Context::Always),
+    ("Inc.", "Incorporated", Context::Always),
+    ("Ltd.", "Limited", Context::Always),
+    ("Bros.", "Brothers", Context::Always),
+    ("Dept.", "Department", Context::Always),
+    // Latin and common abbreviations
+    ("e.g.", "for example", Context::Leading),
+    ("i.e.", "that is", Context::Leading),
+    ("etc.", "et cetera", Context::Always),
+    ("et al.", "and others", Context::Always),
+    ("vs.", "versus", Context::Leading),
+    ("viz.", "namely", Context::Leading),
+    ("cf.", "compare", Context::Leading),
+    ("a.k.a.", "also known as", Context::Leading),
+    ("approx.", "approximately", Context::Leading),
+    ("esp.", "especially", Context::Leading),
+    ("incl.", "including", Context::Leading),
+    ("c.", "circa", Context::BeforeNumber),
+    ("ca.", "circa", Context::BeforeNumber),
+    // References
+    ("No.", "Number", Context::BeforeNumber),
+    ("Nos.", "Numbers", Context::BeforeNumber),
+    ("vol.", "volume", Context::BeforeNumber),
+    ("p.", "page", Context::BeforeNumber),
+    ("pp.", "pages", Context::BeforeNumber),
+    ("ch.", "chapter", Context::BeforeNumber),
+    ("chap.", "chapter", Context::BeforeNumber),
+    ("fig.", "figure", Context::BeforeNumber),
+    ("para.", "paragraph", Context::BeforeNumber),
+    // Months
+    ("Jan.", "January", Context::Always),
+    ("Feb.", "February", Context::Always),
+    ("Mar.", "March", Context::BeforeNumber),
+    ("Apr.", "April", Context::Always),
+    ("Jun.", "June", Context::Always),
+    ("Jul.", "July", Context::Always),
+    ("Aug.", "August", Context::Always),
+    ("Sep.", "September", Context::Always),
+    ("Sept.", "September", Context::Always),
+    ("Oct.", "October", Context::Always),
+    ("Nov.", "November", Context::Always),
+    ("Dec.", "December", Context::Always),
+];
+
+/// Capitalised words that usually start a new sentence rather than continue a name, so
+/// "Baker St. The" is a street at the end of a sentence, but "Old St. Paul's" is a saint
+const SENTENCE_STARTERS: [&str; 22] = [
+    "A", "After", "An", "And", "As", "At", "But", "He", "Her", "His", "I", "If", "In", "It", "On",
+    "She", "That", "The", "There", "They", "This", "We",
+];
+
+/// The built in table of abbreviations for the book's language, plus any the user added
+#[derive(Debug, Clone)]
+pub struct Abbreviations {
+    entries: Vec<Abbreviation>,
+    /// The entries the user added, kept so the table can be rebuilt for another language
+    added: Vec<Abbreviation>,
+}
+
+impl Default for Abbreviations {
+    fn default() -> Self {
+        Self::from_table(&BUILT_IN)
+    }
+}
+
+/// Reads a line of an abbreviations file, `abbreviation==expansion` with an optional third field
+/// of `leading`, `before-number` or `before-name`
+fn process_line(text: &str) -> Option<Abbreviation> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+        return None;
+    }
+    let mut fields = text.split("==");
+    let (Some(abbreviation), Some(expansion)) = (fields.next(), fields.next()) else {
+        println!("Abbreviations File Syntax error - Ignoring line, no '==' found: {text}");
+        return None;
+    };
+    let context = match fields.next().map(str::trim) {
+        None | Some("" | "always") => Context::Always,
+        Some("leading") => Context::Leading,
+        Some("before-number") => Context::BeforeNumber,
+        Some("before-name") => Context::BeforeName,
+        Some(other) => {
+            println!(
+                "Abbreviations File Syntax error - Ignoring unknown context '{other}': {text}"
+            );
+            Context::Always
+        }
+    };
+
+    Some(Abbreviation {
+        abbreviated: abbreviation.trim().to_string(),
+        expansion: expansion.trim().to_string(),
+        context,
+    })
+}
+
+impl Abbreviations {
+    /// Adds abbreviations to the table, replacing any built in entry for the same abbreviation
+    ///
+    /// # Arguments
+    /// * `text` - The contents of an abbreviations file
+    pub fn extend_from_text(&mut self, text: &str) {
+        for abbreviation in text.lines().filter_map(process_line) {
+            self.add(abbreviation);
+        }
+    }
+
+    fn add(&mut self, abbreviation: Abbreviation) {
+        self.entries
+            .retain(|entry| entry.abbreviated != abbreviation.abbreviated);
+        self.added.push(abbreviation.clone());
+        self.entries.push(abbreviation);
+    }
+
+    fn from_table(table: &[(&str, &str, Context)]) -> Self {
+        Self {
+            entries: table
+                .iter()
+                .map(|(abbreviation, expansion, context)| Abbreviation {
+                    abbreviated: (*abbreviation).to_string(),
+                    expansion: (*expansion).to_string(),
+                    context: *context,
+                })
+                .collect(),
+            added: Vec::new(),
+        }
+    }
+
+    /// Returns the built in table for a language
+    ///
+    /// # Arguments
+    /// * `language` - The language of the book
+    pub fn built_in(language: Language) -> Self {
+        language
+            .pack()
+            .map_or_else(Self::default, |pack| Self::from_table(pack.abbreviations))
+    }
+
+    /// Returns the built in table for another language, with the same user entries, eg for a
+    /// passage quoted in another language
+    ///
+    /// # Arguments
+    /// * `language` - The language of the passage
+    pub fn in_language(&self, language: Language) -> Self {
+        let mut abbreviations = Self::built_in(language);
+        for abbreviation in &self.added {
+            abbreviations.add(abbreviation.clone());
+        }
+        abbreviations
+    }
+
+    /// Loads the built in table for the language, extended by a user file if it exists
+    ///
+    /// # Arguments
+    /// * `filename` - The user's abbreviations file
+    /// * `language` - The language of the book
+    pub fn load(filename: &str, language: Language) -> Self {
+        let mut abbreviations = Self::built_in(language);
+        if let Ok(file_text) = fs::read_to_string(filename) {
+            println!("Opening user abbreviations: {filename}");
+            abbreviations.extend_from_text(&file_text);
+        }
+        abbreviations
+    }
+
+    /// Finds an abbreviation, also matching the capitalised form of lower case entries at the
+    /// start of a sentence, eg "E.g."
+    fn find(&self, text: &str) -> Option<Abbreviation> {
+        if let Some(entry) = self.entries.iter().find(|entry| entry.abbreviated == text) {
+            return Some(entry.clone());
+        }
+        self.entries
+            .iter()
+            .find(|entry| {
+                entry.abbreviated.starts_with(char::is_lowercase)
+                    && capitalise(&entry.abbreviated) == text
+            })
+            .map(|entry| Abbreviation {
+                abbreviated: text.to_string(),
+                expansion: capitalise(&entry.expansion),
+                context: entry.context,
+            })
+    }
+
+    fn pattern(&self) -> String {
+        let mut abbreviations: Vec<String> = self
+            .entries
+            .iter()
+            .flat_map(|entry| [entry.abbreviated.clone(), capitalise(&entry.abbreviated)])
+            .collect();
+        abbreviations.sort_by_key(|abbreviation| std::cmp::Reverse(abbreviation.len()));
+        abbreviations.dedup();
+        abbreviations
+            .iter()
+            .map(|abbreviation| regex::escape(abbreviation))
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    /// Expands the abbreviations in a text
+    ///
+    /// # Arguments
+    /// * `text` - The text to convert
+    /// # Returns
+    /// The text with abbreviations expanded
+    pub fn expand(&self, text: &str) -> String {
+        if self.entries.is_empty() {
+            return text.to_string();
+        }
+        let re = Regex::new(&self.pattern()).unwrap();
+        let next_sentence = Regex::new(r#"^(\s*$|\n|\s+["“‘']?[A-Z])"#).unwrap();
+
+        let mut result = String::new();
+        let mut last = 0;
+        for found in re.find_iter(text) {
+            let before = &text[..found.start()];
+            let after = &text[found.end()..];
+            // Only whole tokens, so "Dr." in "AMDr." and "p." in "help." are left alone
+            if before.ends_with(|c: char| c.is_alphanumeric() || c == '.')
+                || after.starts_with(char::is_alphanumeric)
+            {
+                continue;
+            }
+            let Some(abbreviation) = self.find(found.as_str()) else {
+                continue;
+            };
+
+            let previous_word = before.split_whitespace().next_back().unwrap_or_default();
+            let next_word = after.split_whitespace().next().unwrap_or_default();
+            let after_number = previous_word.starts_with(|c: char| c.is_ascii_digit());
+            let before_number =
+                after.starts_with(' ') && next_word.starts_with(|c: char| c.is_ascii_digit());
+            let before_name = after.starts_with(' ') && next_word.starts_with(char::is_uppercase);
+            let after_name = previous_word.starts_with(char::is_uppercase)
+                && !previous_word.ends_with(['.', '!', '?']);
+            let ends_sentence =
+                abbreviation.abbreviated.ends_with('.') && next_sentence.is_match(after);
+
+            let expansion = match abbreviation.context {
+                Context::Always => Some((abbreviation.expansion, ends_sentence)),
+                Context::Leading => Some((abbreviation.expansion, false)),
+                Context::BeforeNumber => before_number.then_some((abbreviation.expansion, false)),
+                Context::BeforeName => before_name.then_some((abbreviation.expansion, false)),
+                Context::Either(after_expansion) => {
+                    let starts_sentence = SENTENCE_STARTERS
+                        .contains(&next_word.trim_end_matches(|c: char| !c.is_alphanumeric()));
+                    if before_name && !after_number && !(after_name && starts_sentence) {
+                        Some((abbreviation.expansion, false))
+                    } else {
+                        Some((after_expansion.to_string(), ends_sentence))
+                    }
+                }
+            };
+            let Some((expansion, ends_sentence)) = expansion else {
+                continue;
+            };
+
+            result.push_str(&text[last..found.start()]);
+            result.push_str(&expansion);
+            if ends_sentence {
+                result.push('.');
+            }
+            last = found.end();
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().collect::<String>() + chars.as_str()
+    })
+}
+
+#[test]
+fn titles_are_expanded_without_ending_the_sentence() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("Mr. Smith met Lt. Col. Jones and Dr. Watson."),
+        "Mister Smith met Lieutenant Colonel Jones and Doctor Watson."
+    );
+}
+
+#[test]
+fn saint_and_street_are_resolved_from_context() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("He walked from St. Paul's to Baker St. and on to 42nd St. The end."),
+        "He walked from Saint Paul's to Baker Street and on to 42nd Street. The end."
+    );
+    assert_eq!(
+        abbreviations.expand("The church of Old St. Mary stood on Baker St. The rain fell."),
+        "The church of Old Saint Mary stood on Baker Street. The rain fell."
+    );
+}
+
+#[test]
+fn sentence_final_full_stop_is_kept() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("apples, pears etc. The rest, e.g. Plums, vs. grapes etc.\nNext"),
+        "apples, pears et cetera. The rest, for example Plums, versus grapes et cetera.\nNext"
+    );
+}
+
+#[test]
+fn context_limited_abbreviations_need_their_context() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("See p. 12 and No. 5, c. 1500. No. He climbed Mt. Everest."),
+        "See page 12 and Number 5, circa 1500. No. He climbed Mount Everest."
+    );
+}
+
+#[test]
+fn only_whole_tokens_are_expanded() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("Help. The U.S.Co. cap."),
+        "Help. The U.S.Co. cap."
+    );
+}
+
+#[test]
+fn capitalised_forms_are_expanded() {
+    let abbreviations = Abbreviations::default();
+    assert_eq!(
+        abbreviations.expand("E.g. this one."),
+        "For example this one."
+    );
+}
+
+#[test]
+fn users_can_extend_and_override_the_table() {
+    let mut abbreviations = Abbreviations::default();
+    abbreviations.extend_from_text(
+        "# My abbreviations\nDept.==Division\nAdm. Sec.==Administrative Secretary==leading\nbogus line\n",
+    );
+    assert_eq!(
+        abbreviations.expand("The Dept. head, Adm. Sec. Jones."),
+        "The Division head, Administrative Secretary Jones."
+    );
+}
+
+#[test]
+fn user_entries_are_kept_in_another_language() {
+    let mut abbreviations = Abbreviations::default();
+    abbreviations.extend_from_text("Bros.==Brothers");
+    let german = abbreviations.in_language(Language::German);
+    assert_eq!(
+        german.expand("Dr. Weber, z. B. Warner Bros."),
+        "Doktor Weber, zum Beispiel Warner Brothers."
+    );
+}
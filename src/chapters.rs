@@ -0,0 +1,438 @@
+use epub::doc::EpubDoc;
+use regex::Regex;
+use std::fs::File;
+use std::io::BufReader;
+use std::str;
+
+use crate::matter::MatterCategory;
+use crate::title_selection::{ChapterTitle, TitleSource};
+use crate::toc::{self, TocEntry};
+
+/// The html of one spine document (or the part of it) that makes up a chapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    /// Path of the spine document inside the epub
+    pub path: String,
+    pub html: String,
+}
+
+/// A single chapter of output, either a whole spine document, one part of it, or several spine
+/// documents merged together
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub idref: String,
+    /// The TOC anchor this chapter was split at, if it was split out of a larger document
+    pub anchor: Option<String>,
+    /// Which part of a split document this is, counting from 0, or 0 if it wasn't split
+    pub part: usize,
+    /// False if the spine marks the document `linear="no"`
+    pub linear: bool,
+    /// One document per spine document in this chapter, in reading order
+    pub documents: Vec<Document>,
+    pub title: String,
+    pub title_source: TitleSource,
+    /// Labels of the TOC entries this chapter is nested under, outermost first
+    pub parents: Vec<String>,
+    /// Set if the chapter is front or back matter
+    pub matter: Option<MatterCategory>,
+}
+
+impl Chapter {
+    /// Returns the path of the first spine document in the chapter
+    pub fn path(&self) -> &str {
+        self.documents
+            .first()
+            .map(|document| document.path.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Returns the title with every level of the TOC hierarchy, eg
+    /// "Part One – Chapter 1: The Beginning"
+    pub fn full_title(&self) -> String {
+        let mut hierarchy = self.parents.clone();
+        hierarchy.push(self.title.clone());
+        hierarchy.join(" – ")
+    }
+}
+
+/// Finds the byte offset of the start of the element with the given id (or `<a name>`)
+fn find_anchor(html: &str, anchor: &str) -> Option<usize> {
+    let pattern = format!(
+        r#"<[a-zA-Z][^>]*\s(id|name)\s*=\s*["']{}["']"#,
+        regex::escape(anchor)
+    );
+    let re = Regex::new(&pattern).unwrap();
+    re.find(html).map(|found| found.start())
+}
+
+/// Returns true if an html fragment has no text, eg "\n  <div>"
+fn is_blank(html: &str) -> bool {
+    let re = Regex::new(r"<[^>]*>").unwrap();
+    re.replace_all(html, "").trim().is_empty()
+}
+
+/// Returns the byte offset just after the `<body>` tag, or 0 if there isn't one
+fn body_start(html: &str) -> usize {
+    let body_re = Regex::new(r"(?i)<body[^>]*>").unwrap();
+    body_re.find(html).map_or(0, |body| body.end())
+}
+
+/// Returns true if the html has the anchor with no text before it, so a link to the anchor is a
+/// link to the whole document
+pub fn starts_at_anchor(html: &str, anchor: &str) -> bool {
+    let start = body_start(html);
+    find_anchor(html, anchor)
+        .is_some_and(|position| position >= start && is_blank(&html[start..position]))
+}
+
+/// Splits an html document into one document per anchor.
+///
+/// Everything up to and including the `<body>` tag is repeated at the start of every part so that
+/// each part still has its `<head>`.  Content before the first anchor is kept with the first part.
+/// An empty anchor is the start of the body, and gets that content unless it has no text.
+///
+/// # Arguments
+/// * `html` - The html document to split
+/// * `anchors` - The element ids to split at, in document order
+/// # Returns
+/// Vector of `(anchor, html)` for every anchor that was found in the document.  Anchors that
+/// could not be found are dropped.
+pub fn split_html_at_anchors(html: &str, anchors: &[String]) -> Vec<(String, String)> {
+    let body_end = body_start(html);
+    let prefix = &html[..body_end];
+
+    // Anchors are listed before the start of the body, so one at the very start wins over it
+    let mut found: Vec<(usize, &String)> = anchors
+        .iter()
+        .filter(|anchor| !anchor.is_empty())
+        .chain(anchors.iter().filter(|anchor| anchor.is_empty()).take(1))
+        .filter_map(|anchor| {
+            let position = if anchor.is_empty() {
+                Some(body_end)
+            } else {
+                find_anchor(html, anchor)
+            };
+            position.map(|position| (position, anchor))
+        })
+        .collect();
+    found.sort_by_key(|(position, _)| *position);
+    found.dedup_by_key(|(position, _)| *position);
+
+    let mut parts = Vec::new();
+    let mut start = body_end;
+    for (i, (_, anchor)) in found.iter().enumerate() {
+        let end = found.get(i + 1).map_or(html.len(), |next| next.0);
+        if anchor.is_empty() && is_blank(&html[start..end]) {
+            continue;
+        }
+        parts.push(((*anchor).clone(), format!("{prefix}{}", &html[start..end])));
+        start = end;
+    }
+    parts
+}
+
+/// Splits a spine document at the TOC entries that point into it.  An entry for the whole
+/// document, as Calibre writes for the first chapter in a file, starts the first part.
+///
+/// # Arguments
+/// * `html` - The spine document
+/// * `entries` - The TOC entries for the document
+/// # Returns
+/// Each part with its TOC entry, or nothing if the document isn't split
+fn split_at_toc_entries<'a>(html: &str, entries: &[&'a TocEntry]) -> Vec<(&'a TocEntry, String)> {
+    let anchors: Vec<String> = entries
+        .iter()
+        .map(|entry| entry.fragment.clone().unwrap_or_default())
+        .collect();
+    if anchors.iter().all(String::is_empty) {
+        return Vec::new();
+    }
+    split_html_at_anchors(html, &anchors)
+        .into_iter()
+        .filter_map(|(anchor, part)| {
+            let entry = entries
+                .iter()
+                .find(|entry| entry.fragment.as_deref().unwrap_or_default() == anchor)?;
+            Some((*entry, part))
+        })
+        .collect()
+}
+
+/// Builds the list of output chapters from the spine.
+///
+/// Spine documents that the TOC points into more than once (`file.xhtml#anchor`) are split at each
+/// anchor, and every part takes its title from the TOC entry.  An entry for the whole file counts
+/// as one at the start, so `file.xhtml` and `file.xhtml#ch2` give two chapters.  Nested TOC
+/// entries are included, and every chapter records the TOC entries it sits under.
+///
+/// # Arguments
+/// * `doc` - the epub document
+/// * `titles` - the chosen title for every spine document
+/// # Returns
+/// Vector of chapters in reading order
+pub fn build_chapters(doc: &mut EpubDoc<BufReader<File>>, titles: &[ChapterTitle]) -> Vec<Chapter> {
+    let spine = doc.spine.clone();
+    let toc_entries = toc::flatten_toc(&doc.toc);
+    let mut chapters = Vec::new();
+
+    for (i, current_section) in spine.iter().enumerate() {
+        let path = doc.resources[&current_section.idref].path.clone();
+        let text = doc.get_resource_by_path(&path).unwrap();
+        let html = str::from_utf8(&text).unwrap();
+        let path_string: String = path.to_string_lossy().into();
+
+        let entries = toc::entries_for_path(&toc_entries, &path_string);
+        let parts = split_at_toc_entries(html, &entries);
+
+        if parts.len() > 1 {
+            println!(
+                "Splitting {} into {} chapters at TOC anchors",
+                current_section.idref,
+                parts.len()
+            );
+            for (part_number, (entry, part)) in parts.into_iter().enumerate() {
+                chapters.push(Chapter {
+                    idref: entry.fragment.as_ref().map_or_else(
+                        || current_section.idref.clone(),
+                        |anchor| format!("{}#{anchor}", current_section.idref),
+                    ),
+                    anchor: entry.fragment.clone(),
+                    part: part_number,
+                    linear: current_section.linear,
+                    documents: vec![Document {
+                        path: path_string.clone(),
+                        html: part,
+                    }],
+                    title: entry.label.clone(),
+                    title_source: TitleSource::Toc,
+                    parents: entry.parents.clone(),
+                    matter: None,
+                });
+            }
+        } else {
+            chapters.push(Chapter {
+                idref: current_section.idref.clone(),
+                anchor: None,
+                part: 0,
+                linear: current_section.linear,
+                documents: vec![Document {
+                    path: path_string,
+                    html: html.to_string(),
+                }],
+                title: titles[i].title.clone(),
+                title_source: titles[i].source,
+                // Documents missing from the TOC stay at the level of the chapter before them
+                parents: entries.first().map_or_else(
+                    || {
+                        chapters
+                            .last()
+                            .map(|previous: &Chapter| previous.parents.clone())
+                            .unwrap_or_default()
+                    },
+                    |entry| entry.parents.clone(),
+                ),
+                matter: None,
+            });
+        }
+    }
+
+    chapters
+}
+
+/// Attaches chapters that have no title to the previous titled chapter.
+///
+/// Publishers often split one chapter over several spine documents (`ch05.xhtml`, `ch05a.xhtml`)
+/// and only list the first in the TOC.  Merging them keeps the chapter as one audio track.  Front
+/// and back matter is only merged into a chapter of the same category.
+///
+/// # Arguments
+/// * `chapters` - The chapters in reading order
+/// # Returns
+/// Vector of chapters, untitled chapters before the first titled one are left alone
+pub fn merge_untitled_chapters(chapters: Vec<Chapter>) -> Vec<Chapter> {
+    let mut merged: Vec<Chapter> = Vec::new();
+
+    for chapter in chapters {
+        match merged.last_mut() {
+            Some(previous)
+                if chapter.title_source == TitleSource::Idref
+                    && previous.title_source != TitleSource::Idref
+                    && chapter.matter == previous.matter =>
+            {
+                println!("Merging untitled {} into {}", chapter.idref, previous.idref);
+                previous.documents.extend(chapter.documents);
+            }
+            _ => merged.push(chapter),
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+fn chapter(idref: &str, title: &str, title_source: TitleSource) -> Chapter {
+    Chapter {
+        idref: idref.to_string(),
+        anchor: None,
+        part: 0,
+        linear: true,
+        documents: vec![Document {
+            path: format!("OEBPS/{idref}.xhtml"),
+            html: format!("<p>{idref}</p>"),
+        }],
+        title: title.to_string(),
+        title_source,
+        parents: Vec::new(),
+        matter: None,
+    }
+}
+
+#[test]
+fn full_title_includes_parents() {
+    let mut chapter = chapter("ch1", "Chapter 1: The Beginning", TitleSource::Toc);
+    assert_eq!(chapter.full_title(), "Chapter 1: The Beginning");
+
+    chapter.parents = vec!["Part One".to_string()];
+    assert_eq!(chapter.full_title(), "Part One – Chapter 1: The Beginning");
+}
+
+#[test]
+fn merge_untitled_chapters_attaches_to_previous_titled_chapter() {
+    let chapters = vec![
+        chapter("cover", "", TitleSource::Idref),
+        chapter("ch05", "Chapter 5", TitleSource::Toc),
+        chapter("ch05a", "", TitleSource::Idref),
+        chapter("ch05b", "", TitleSource::Idref),
+        chapter("ch06", "Chapter 6", TitleSource::Toc),
+    ];
+    let merged = merge_untitled_chapters(chapters);
+
+    let idrefs: Vec<&str> = merged.iter().map(|c| c.idref.as_str()).collect();
+    assert_eq!(idrefs, vec!["cover", "ch05", "ch06"]);
+    let html: Vec<&str> = merged[1]
+        .documents
+        .iter()
+        .map(|document| document.html.as_str())
+        .collect();
+    assert_eq!(html, vec!["<p>ch05</p>", "<p>ch05a</p>", "<p>ch05b</p>"]);
+    assert_eq!(merged[1].title, "Chapter 5");
+}
+
+#[test]
+fn merge_untitled_chapters_does_not_merge_back_matter_into_chapters() {
+    let mut index = chapter("index", "", TitleSource::Idref);
+    index.matter = Some(MatterCategory::Index);
+    let chapters = vec![chapter("ch12", "Chapter 12", TitleSource::Toc), index];
+    assert_eq!(merge_untitled_chapters(chapters).len(), 2);
+}
+
+#[test]
+fn merge_untitled_chapters_keeps_titled_chapters() {
+    let chapters = vec![
+        chapter("ch01", "Chapter 1", TitleSource::Heading),
+        chapter("ch02", "Chapter 2", TitleSource::Toc),
+    ];
+    assert_eq!(merge_untitled_chapters(chapters).len(), 2);
+}
+
+#[test]
+fn split_html_at_anchors_splits_at_each_anchor() {
+    let html = r#"<html><head><title>t</title></head><body class="b"><p>intro</p><h2 id="one">One</h2><p>first</p><div id="two"><h2>Two</h2><p>second</p></div></body></html>"#;
+    let parts = split_html_at_anchors(html, &["one".to_string(), "two".to_string()]);
+
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].0, "one");
+    assert_eq!(
+        parts[0].1,
+        r#"<html><head><title>t</title></head><body class="b"><p>intro</p><h2 id="one">One</h2><p>first</p>"#
+    );
+    assert_eq!(parts[1].0, "two");
+    assert!(
+        parts[1]
+            .1
+            .starts_with(r#"<html><head><title>t</title></head><body class="b"><div id="two">"#)
+    );
+    assert!(parts[1].1.ends_with("</body></html>"));
+}
+
+#[test]
+fn split_html_at_anchors_orders_by_position_and_skips_missing() {
+    let html = r#"<body><a name="b"></a>b text<p id='a'>a text</p></body>"#;
+    let parts = split_html_at_anchors(
+        html,
+        &["a".to_string(), "missing".to_string(), "b".to_string()],
+    );
+
+    let anchors: Vec<&str> = parts.iter().map(|(anchor, _)| anchor.as_str()).collect();
+    assert_eq!(anchors, vec!["b", "a"]);
+    assert_eq!(parts[1].1, "<body><p id='a'>a text</p></body>");
+}
+
+#[test]
+fn split_html_at_anchors_does_not_match_partial_ids() {
+    let html = r#"<body><p id="chap10">ten</p><p id="chap1">one</p></body>"#;
+    let parts = split_html_at_anchors(html, &["chap1".to_string()]);
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].1, html);
+}
+
+#[test]
+fn build_chapters_splits_documents_with_several_toc_entries() {
+    let fixture = "fixtures/alice_in_wonderland_by_lewis_carroll.epub";
+    let mut doc = EpubDoc::new(fixture).unwrap();
+    let titles: Vec<ChapterTitle> = doc
+        .spine
+        .iter()
+        .map(|_| ChapterTitle {
+            title: String::new(),
+            source: TitleSource::Idref,
+        })
+        .collect();
+    let chapters = build_chapters(&mut doc, &titles);
+
+    // The Gutenberg header holds the book title, edition and contents
+    assert_eq!(chapters.len(), 17);
+    assert_eq!(chapters[1].idref, "pg-header#pgepubid00000");
+    assert_eq!(chapters[2].title, "THE MILLENNIUM FULCRUM EDITION 3.0");
+    assert_eq!(chapters[3].title, "Contents");
+    assert_eq!(chapters[3].title_source, TitleSource::Toc);
+    assert_eq!(chapters[4].idref, "item4");
+}
+
+#[cfg(test)]
+fn toc_entry(label: &str, fragment: Option<&str>) -> TocEntry {
+    TocEntry {
+        label: label.to_string(),
+        path: "text/part1.html".to_string(),
+        fragment: fragment.map(String::from),
+        parents: Vec::new(),
+    }
+}
+
+#[test]
+fn a_toc_entry_for_the_whole_document_starts_the_first_part() {
+    let html = r#"<body><h1>Chapter 1</h1><p>one</p><h1 id="ch2">Chapter 2</h1><p>two</p></body>"#;
+    let (first, second) = (
+        toc_entry("Chapter 1", None),
+        toc_entry("Chapter 2", Some("ch2")),
+    );
+    let parts = split_at_toc_entries(html, &[&first, &second]);
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].0.label, "Chapter 1");
+    assert_eq!(parts[0].1, "<body><h1>Chapter 1</h1><p>one</p>");
+    assert_eq!(parts[1].0.label, "Chapter 2");
+    assert!(parts[1].1.starts_with(r#"<body><h1 id="ch2">"#));
+
+    // With no text before the first anchor there is no part for the whole document
+    let html = r#"<body>
+  <h1 id="ch1">Chapter 1</h1><h1 id="ch2">Chapter 2</h1></body>"#;
+    let only = toc_entry("Chapter 1", Some("ch1"));
+    let parts = split_at_toc_entries(html, &[&first, &only, &second]);
+    let labels: Vec<&str> = parts
+        .iter()
+        .map(|(entry, _)| entry.label.as_str())
+        .collect();
+    assert_eq!(labels, vec!["Chapter 1", "Chapter 2"]);
+    assert_eq!(parts[0].0.fragment.as_deref(), Some("ch1"));
+    assert!(split_at_toc_entries(html, &[&first]).is_empty());
+}
@@ -1,113 +1,1074 @@
-use regex::Regex;
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
-fn process_line(text: &str) -> (String, String) {
-    if text.chars().next().is_none() {
-        return (String::new(), String::new());
+/// The name of a replacements file in each directory it is looked for in
+pub const FILENAME: &str = "custom-replacements.conf";
+
+/// A custom replacement, compiled from a line of a replacements file
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    /// The file and line the rule is on, and its pattern as written, for the hits report
+    filename: String,
+    line: usize,
+    rule: String,
+    pattern: Regex,
+    /// The text that replaces each match
+    text: String,
+    first_only: bool,
+    /// The `[section]` the rule is in, or "" if it comes before any section
+    section: String,
+    /// The chapters the section is limited to, or empty for every chapter
+    scopes: Vec<Scope>,
+}
+
+impl Replacement {
+    pub fn section(&self) -> &str {
+        &self.section
     }
-    if text.starts_with('#') {
-        return (String::new(), String::new());
+
+    /// Returns true if the rule is used in the chapter
+    fn applies_to(&self, chapter: &ChapterContext) -> bool {
+        self.scopes.is_empty() || self.scopes.iter().any(|scope| scope.contains(chapter))
     }
-    let ret = text.split_once("==");
-    if let Some((x, y)) = ret {
-        (x.to_string(), y.to_string())
-    } else {
-        println!("Custom Replacements File Syntax error - Ignoring line, no '==' found: {text}");
-        (String::new(), String::new())
+}
+
+/// The chapter a text comes from, for rules limited to some chapters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChapterContext<'a> {
+    /// The chapter's number, counting from 1 as in the output filenames
+    pub number: usize,
+    pub title: &'a str,
+    pub idref: &'a str,
+}
+
+/// Part of a section header that limits its rules to some chapters
+#[derive(Debug, Clone)]
+enum Scope {
+    /// `chapters=10-12,15`
+    Chapters(Vec<RangeInclusive<usize>>),
+    /// `title=regex`, matched against the chapter's title
+    Title(Regex),
+    /// `idref=chapter10,appendix`, the id of the chapter's spine document
+    Idrefs(Vec<String>),
+}
+
+impl Scope {
+    /// Reads a scope, eg `chapters=10-12`
+    ///
+    /// # Arguments
+    /// * `key` - The kind of scope
+    /// * `value` - The chapters, title regex or idrefs, without quotes
+    fn parse(key: &str, value: &str) -> Result<Self, String> {
+        match key {
+            "chapters" => value
+                .split(',')
+                .map(|range| {
+                    let (start, end) = range.split_once('-').unwrap_or((range, range));
+                    match (start.trim().parse::<usize>(), end.trim().parse::<usize>()) {
+                        (Ok(start), Ok(end)) if start <= end => Ok(start..=end),
+                        _ => Err(format!(
+                            "invalid chapter range '{range}', write eg chapters=10-12,15"
+                        )),
+                    }
+                })
+                .collect::<Result<_, _>>()
+                .map(Self::Chapters),
+            "title" => Regex::new(value)
+                .map(Self::Title)
+                .map_err(|error| error.to_string()),
+            "idref" => Ok(Self::Idrefs(
+                value
+                    .split(',')
+                    .map(|idref| idref.trim().to_string())
+                    .collect(),
+            )),
+            _ => Err(format!(
+                "unknown scope '{key}', the scopes are chapters, title and idref"
+            )),
+        }
+    }
+
+    fn contains(&self, chapter: &ChapterContext) -> bool {
+        match self {
+            Self::Chapters(ranges) => ranges.iter().any(|range| range.contains(&chapter.number)),
+            Self::Title(pattern) => pattern.is_match(chapter.title),
+            Self::Idrefs(idrefs) => idrefs.iter().any(|idref| idref == chapter.idref),
+        }
+    }
+}
+
+/// Reads a section header, a name followed by any scopes, eg
+/// `Elvish chapters=10-12 title="^The Grey Havens"`.  A rule in a section with scopes is used in
+/// a chapter that matches any of them.
+///
+/// # Arguments
+/// * `header` - The text between the brackets
+/// # Returns
+/// The name and the scopes
+fn parse_section(header: &str) -> Result<(String, Vec<Scope>), String> {
+    let re = Regex::new(r#"(\w+)=(?:"([^"]*)"|(\S+))"#).unwrap();
+    let scopes = re
+        .captures_iter(header)
+        .map(|caps| {
+            let value = caps
+                .get(2)
+                .or_else(|| caps.get(3))
+                .map_or("", |m| m.as_str());
+            Scope::parse(&caps[1], value)
+        })
+        .collect::<Result<_, _>>()?;
+    let name = re.replace_all(header, "");
+    Ok((
+        name.split_whitespace().collect::<Vec<_>>().join(" "),
+        scopes,
+    ))
+}
+
+/// A line of a replacements file whose pattern or replacement can't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplacementError {
+    pub filename: String,
+    /// The line number, counting from 1, or 0 if the file itself can't be read
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ReplacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.filename, self.message)
+        } else {
+            write!(f, "{}, line {}: {}", self.filename, self.line, self.message)
+        }
+    }
+}
+
+/// The first line of a file written in the version 2 syntax, which has flags, trailing comments
+/// and escapes.  It is a comment to the original syntax, so older versions still read the file.
+const SYNTAX_V2: &str = "# syntax: v2";
+
+/// How the lines of a replacements file are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Syntax {
+    /// `pattern==replacement`, split at the first `==`, with `#` only starting a comment at the
+    /// start of a line, so existing files keep their meaning
+    #[default]
+    V1,
+    /// `pattern==replacement==flags`, with trailing comments and the `\==` and `\#` escapes
+    V2,
+}
+
+impl Syntax {
+    /// Returns the syntax of a file, from its first line that isn't blank
+    fn of(text: &str) -> Self {
+        match text.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) if line.trim() == SYNTAX_V2 => Self::V2,
+            _ => Self::V1,
+        }
+    }
+}
+
+/// A line of a replacements file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// A blank line or a comment
+    Empty,
+    /// A line that is neither a rule, a section nor a comment, and why
+    Invalid(String),
+    /// A `[name]` header, naming the rules after it
+    Section(String),
+    /// `pattern==replacement`, with optional flags after another `==`
+    Rule {
+        pattern: String,
+        replacement: String,
+        flags: String,
+    },
+}
+
+/// Removes a trailing comment, a `#` at the start of the line or after a space, eg
+/// "Dr.==Doctor  # title".  An escaped `\#` is not a comment.
+fn strip_comment(text: &str) -> &str {
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            return text[..i].trim_end();
+        }
+        previous = c;
+    }
+    text
+}
+
+/// Splits a line into its fields at each `==`, turning the escapes `\==` and `\#` into a
+/// literal `==` and `#`
+fn split_fields(text: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let field = fields.last_mut().unwrap();
+        if let Some(after) = rest.strip_prefix("\\==") {
+            field.push_str("==");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("\\#") {
+            field.push('#');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("==") {
+            fields.push(String::new());
+            rest = after;
+        } else {
+            field.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    fields
+}
+
+fn process_line(text: &str, syntax: Syntax) -> Line {
+    let text = match syntax {
+        Syntax::V1 if text.trim_start().starts_with('#') => "",
+        Syntax::V1 => text,
+        Syntax::V2 => strip_comment(text),
+    };
+    if text.trim().is_empty() {
+        return Line::Empty;
+    }
+    let trimmed = text.trim();
+    if trimmed.starts_with('[') && trimmed.ends_with(']') && !trimmed.contains("==") {
+        return Line::Section(trimmed[1..trimmed.len() - 1].trim().to_string());
+    }
+    let fields = match syntax {
+        Syntax::V1 => text.split_once("==").map_or_else(
+            || vec![text.to_string()],
+            |(pattern, replacement)| vec![pattern.to_string(), replacement.to_string()],
+        ),
+        Syntax::V2 => split_fields(text),
+    };
+    let mut fields = fields.into_iter();
+    let (Some(pattern), Some(replacement)) = (fields.next(), fields.next()) else {
+        return Line::Invalid(format!("no '==' found in '{text}'"));
+    };
+    Line::Rule {
+        pattern,
+        replacement,
+        // Any further fields are reported as unknown flags
+        flags: fields.collect::<Vec<_>>().join("=="),
     }
-    //assert_eq!("cfg=".split_once('='), Some(("cfg", "")));
 }
 
-fn process_file_text(text: &str) -> Vec<(String, String)> {
-    let mut ret: Vec<(String, String)> = Vec::new();
+fn process_file_text(text: &str) -> Vec<Line> {
+    let syntax = Syntax::of(text);
+    let mut ret: Vec<Line> = Vec::new();
     for line in text.lines() {
-        ret.push(process_line(line));
+        ret.push(process_line(line, syntax));
     }
     ret
 }
 
-pub fn process_user_replacements(text: &str, replacements: &Vec<(String, String)>) -> String {
+/// Prints a warning for each line of a file in the original syntax that the version 2 syntax
+/// would read differently, eg "a==b==c" or "Dr.==Doctor # title"
+fn warn_about_v2_lines(filename: &str, text: &str) {
+    if Syntax::of(text) == Syntax::V2 {
+        return;
+    }
+    for (i, line) in text.lines().enumerate() {
+        if process_line(line, Syntax::V1) != process_line(line, Syntax::V2) {
+            println!(
+                "Custom Replacements File Warning - {filename}, line {}: a second '==' or a ' #' \
+                 is read as part of the rule; start the file with '{SYNTAX_V2}' to read them as flags \
+                 and comments: {line}",
+                i + 1
+            );
+        }
+    }
+}
+
+/// How a rule matches, from the letters after its second `==`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+struct Flags {
+    /// `l`: the pattern is plain text rather than a regular expression, and `$` in the
+    /// replacement is a dollar sign
+    literal: bool,
+    /// `i`: ignore case
+    case_insensitive: bool,
+    /// `w`: only match whole words
+    whole_word: bool,
+    /// `1`: only replace the first match in each chapter
+    first_only: bool,
+}
+
+impl Flags {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut flags = Self::default();
+        for c in text.chars().filter(|c| !c.is_whitespace() && *c != ',') {
+            match c {
+                'l' => flags.literal = true,
+                'i' => flags.case_insensitive = true,
+                'w' => flags.whole_word = true,
+                '1' => flags.first_only = true,
+                _ => {
+                    return Err(format!(
+                        "unknown flag '{c}' in '{text}', the flags are l (literal), i (ignore \
+                         case), w (whole words) and 1 (first match only)"
+                    ));
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// Returns the regular expression for a rule's pattern and flags
+fn pattern_source(pattern: &str, flags: Flags) -> String {
+    let mut source = if flags.literal {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    if flags.whole_word {
+        if flags.literal {
+            // A word boundary only makes sense next to a word character, eg "Dr." -> "\bDr\."
+            let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+            if is_word_char(pattern.chars().next()) {
+                source = format!(r"\b{source}");
+            }
+            if is_word_char(pattern.chars().last()) {
+                source = format!(r"{source}\b");
+            }
+        } else {
+            source = format!(r"\b(?:{source})\b");
+        }
+    }
+    if flags.case_insensitive {
+        source = format!("(?i){source}");
+    }
+    source
+}
+
+/// Returns the groups a replacement refers to, eg "$1" and "${name}", following the rules of
+/// `Regex::replace_all`: `$$` is a dollar sign and a name is as many letters, digits and
+/// underscores as follow the `$`
+fn group_references(replacement: &str) -> Vec<&str> {
+    let re = Regex::new(r"\$(?:\$|\{([^}]*)\}|([0-9A-Za-z_]+))").unwrap();
+    re.captures_iter(replacement)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|name| name.as_str())
+        .collect()
+}
+
+/// Checks a replacement only refers to groups the pattern has
+fn check_group_references(pattern: &Regex, replacement: &str) -> Result<(), String> {
+    for name in group_references(replacement) {
+        let exists = name.parse::<usize>().map_or_else(
+            |_| pattern.capture_names().flatten().any(|group| group == name),
+            |index| index < pattern.captures_len(),
+        );
+        if !exists {
+            // "$1st" is the group "1st", not group 1 followed by "st"
+            let hint = match name.find(|c: char| !c.is_ascii_digit()) {
+                Some(end) if end > 0 => format!(
+                    " (write ${{{}}}{} for group {})",
+                    &name[..end],
+                    &name[end..],
+                    &name[..end]
+                ),
+                _ => String::new(),
+            };
+            return Err(format!(
+                "replacement refers to group '{name}', which the pattern '{pattern}' doesn't have{hint}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Compiles the lines of a replacements file, so a mistake is found before any chapter is
+/// converted
+///
+/// # Arguments
+/// * `filename` - The name of the file, for the errors
+/// * `text` - The contents of the file
+/// # Returns
+/// The replacements, or every line that has an invalid pattern or group reference
+pub fn compile(filename: &str, text: &str) -> Result<Vec<Replacement>, Vec<ReplacementError>> {
+    let mut replacements = Vec::new();
+    let mut errors = Vec::new();
+    let mut section = String::new();
+    let mut scopes = Vec::new();
+    warn_about_v2_lines(filename, text);
+    for (i, line) in process_file_text(text).into_iter().enumerate() {
+        let (pattern, replacement, flags) = match line {
+            Line::Empty => continue,
+            Line::Invalid(message) => {
+                errors.push(ReplacementError {
+                    filename: filename.to_string(),
+                    line: i + 1,
+                    message,
+                });
+                continue;
+            }
+            Line::Section(header) => {
+                match parse_section(&header) {
+                    Ok((name, section_scopes)) => (section, scopes) = (name, section_scopes),
+                    Err(message) => errors.push(ReplacementError {
+                        filename: filename.to_string(),
+                        line: i + 1,
+                        message,
+                    }),
+                }
+                continue;
+            }
+            Line::Rule {
+                pattern,
+                replacement,
+                flags,
+            } => (pattern, replacement, flags),
+        };
+        let rule = pattern.clone();
+        let compiled = Flags::parse(&flags).and_then(|flags| {
+            let regex =
+                Regex::new(&pattern_source(&pattern, flags)).map_err(|error| error.to_string())?;
+            if flags.literal {
+                Ok((regex, replacement.replace('$', "$$"), flags))
+            } else {
+                check_group_references(&regex, &replacement).map(|()| (regex, replacement, flags))
+            }
+        });
+        match compiled {
+            Ok((pattern, text, flags)) => replacements.push(Replacement {
+                filename: filename.to_string(),
+                line: i + 1,
+                rule,
+                pattern,
+                text,
+                first_only: flags.first_only,
+                section: section.clone(),
+                scopes: scopes.clone(),
+            }),
+            Err(message) => errors.push(ReplacementError {
+                filename: filename.to_string(),
+                line: i + 1,
+                message,
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(replacements)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Applies the replacements, leaving out rules limited to other chapters
+///
+/// # Arguments
+/// * `text` - The text to change
+/// * `replacements` - The compiled rules, in order
+/// * `chapter` - The chapter the text is from
+/// * `report` - Counts how often each rule matched in the chapter.  A chapter read in several
+///   pieces, eg passages in other languages, shares its counts, so a first match only rule is only
+///   used once in the chapter.
+/// # Returns
+/// The text with the replacements made
+pub fn process_user_replacements(
+    text: &str,
+    replacements: &[Replacement],
+    chapter: &ChapterContext,
+    report: &mut Report,
+) -> String {
     let mut ret = text.to_string();
 
-    for replace in replacements {
-        let re = Regex::new(&replace.0).unwrap();
-        ret = re.replace_all(&ret, &replace.1).to_string();
+    for (i, replace) in replacements
+        .iter()
+        .enumerate()
+        .filter(|(_, replace)| replace.applies_to(chapter))
+    {
+        if replace.first_only && report.hits(i, chapter.number) > 0 {
+            continue;
+        }
+        let mut hits = 0;
+        ret = replace
+            .pattern
+            .replacen(&ret, usize::from(replace.first_only), |caps: &Captures| {
+                hits += 1;
+                let mut replacement = String::new();
+                caps.expand(&replace.text, &mut replacement);
+                replacement
+            })
+            .to_string();
+        report.record(i, chapter.number, hits);
     }
     ret
 }
 
-pub fn load_custom_replacements(filename: &str) -> Option<Vec<(String, String)>> {
+/// How often a rule matched, for the JSON report
+#[derive(Debug, Serialize)]
+struct RuleHits<'a> {
+    file: &'a str,
+    line: usize,
+    section: &'a str,
+    pattern: &'a str,
+    total: usize,
+    /// The number of matches in each chapter with any, by chapter number
+    chapters: BTreeMap<usize, usize>,
+    unused: bool,
+}
+
+/// How often each custom replacement matched in each chapter, so rules that never match because
+/// of a typo or a wrong scope can be found
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The matches of each rule, by its index, in each chapter, by its number
+    hits: HashMap<usize, BTreeMap<usize, usize>>,
+}
+
+impl Report {
+    /// Counts the matches of a rule in a chapter
+    ///
+    /// # Arguments
+    /// * `rule` - The index of the rule in the replacements
+    /// * `chapter` - The chapter's number
+    /// * `count` - The number of matches
+    pub fn record(&mut self, rule: usize, chapter: usize, count: usize) {
+        if count > 0 {
+            *self
+                .hits
+                .entry(rule)
+                .or_default()
+                .entry(chapter)
+                .or_default() += count;
+        }
+    }
+
+    /// Returns how often a rule has matched in a chapter
+    fn hits(&self, rule: usize, chapter: usize) -> usize {
+        self.hits
+            .get(&rule)
+            .and_then(|chapters| chapters.get(&chapter))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the hits of every rule
+    fn rules<'a>(&self, replacements: &'a [Replacement]) -> Vec<RuleHits<'a>> {
+        replacements
+            .iter()
+            .enumerate()
+            .map(|(i, replacement)| {
+                let chapters = self.hits.get(&i).cloned().unwrap_or_default();
+                let total = chapters.values().sum();
+                RuleHits {
+                    file: &replacement.filename,
+                    line: replacement.line,
+                    section: &replacement.section,
+                    pattern: &replacement.rule,
+                    total,
+                    chapters,
+                    unused: total == 0,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the number of rules that never matched
+    pub fn unused(&self, replacements: &[Replacement]) -> usize {
+        (0..replacements.len())
+            .filter(|i| !self.hits.contains_key(i))
+            .count()
+    }
+
+    /// Returns a table of how often each rule matched and in how many chapters, with the rules
+    /// that never matched flagged
+    ///
+    /// # Arguments
+    /// * `replacements` - The rules the hits were counted for
+    pub fn to_text(&self, replacements: &[Replacement]) -> String {
+        let mut lines = vec![format!("{:>7} {:>8}  Rule", "Hits", "Chapters")];
+        for rule in self.rules(replacements) {
+            let section = if rule.section.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", rule.section)
+            };
+            lines.push(format!(
+                "{:>7} {:>8}  {}:{}{} {}{}",
+                rule.total,
+                rule.chapters.len(),
+                rule.file,
+                rule.line,
+                section,
+                rule.pattern,
+                if rule.unused {
+                    "  <- never matched"
+                } else {
+                    ""
+                }
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Returns the hits of every rule, per chapter, as JSON
+    ///
+    /// # Arguments
+    /// * `replacements` - The rules the hits were counted for
+    pub fn to_json(&self, replacements: &[Replacement]) -> String {
+        serde_json::to_string_pretty(&self.rules(replacements)).unwrap()
+    }
+}
+
+/// Loads and compiles a replacements file
+///
+/// # Arguments
+/// * `filename` - The replacements file
+/// # Returns
+/// The replacements, or the lines with errors, or an error if the file can't be read
+pub fn load_custom_replacements(filename: &str) -> Result<Vec<Replacement>, Vec<ReplacementError>> {
     let file_result = fs::read_to_string(filename);
     match file_result {
         Ok(file_text) => {
             println!("Opening custom user replacements: {filename}");
-            Some(process_file_text(&file_text))
+            compile(filename, &file_text)
+        }
+        Err(error) => Err(vec![ReplacementError {
+            filename: filename.to_string(),
+            line: 0,
+            message: format!("Unable to open file: {error}"),
+        }]),
+    }
+}
+
+/// Returns every place a replacements file is looked for, in the order their rules are applied:
+/// the user's config directory, the current directory, next to the EPUB, the output directory,
+/// then the files given on the command line
+///
+/// # Arguments
+/// * `config_directory` - The user's config directory, eg ~/.config
+/// * `epub_filename` - The book, if there is one
+/// * `output_directory` - The output directory, if there is one
+/// * `extra` - The files given with `--replacements`
+fn candidate_files(
+    config_directory: Option<PathBuf>,
+    epub_filename: Option<&str>,
+    output_directory: Option<&str>,
+    extra: &[String],
+) -> Vec<PathBuf> {
+    let epub_directory = epub_filename
+        .map(|filename| {
+            Path::new(filename)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+        })
+        .map(|directory| {
+            if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                directory
+            }
+        });
+    let directories = [
+        config_directory.map(|directory| directory.join("ebook2audiobook")),
+        Some(PathBuf::from(".")),
+        epub_directory.map(Path::to_path_buf),
+        output_directory.map(PathBuf::from),
+    ];
+    directories
+        .into_iter()
+        .flatten()
+        .map(|directory| directory.join(FILENAME))
+        .chain(extra.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// Finds the replacements files to read, in the order their rules are applied.  The layered
+/// files are only read if they exist and each file is only read once, eg when the EPUB is in the
+/// current directory.  Files given with `--replacements` are always read.
+///
+/// # Arguments
+/// * `epub_filename` - The book, if there is one
+/// * `output_directory` - The output directory, if there is one
+/// * `extra` - The files given with `--replacements`
+pub fn find_files(
+    epub_filename: Option<&str>,
+    output_directory: Option<&str>,
+    extra: &[String],
+) -> Vec<PathBuf> {
+    let layers = candidate_files(dirs::config_dir(), epub_filename, output_directory, extra);
+    let first_explicit = layers.len() - extra.len();
+    let mut seen = Vec::new();
+    let mut files = Vec::new();
+    for (i, file) in layers.into_iter().enumerate() {
+        if i < first_explicit && !file.exists() {
+            continue;
+        }
+        let canonical = fs::canonicalize(&file).unwrap_or_else(|_| file.clone());
+        if !seen.contains(&canonical) {
+            seen.push(canonical);
+            files.push(file);
         }
-        Err(error) => {
-            eprintln!("Unable to open file: {error} {filename}");
-            None
+    }
+    files
+}
+
+/// Loads and compiles every replacements file, keeping their rules in order
+///
+/// # Arguments
+/// * `files` - The replacements files, from `find_files`
+/// # Returns
+/// The replacements, None if there are no files, or the lines with errors in every file.  A file
+/// that can't be read is an error, since `find_files` only returns the layered files that exist.
+pub fn load_files(files: &[PathBuf]) -> Result<Option<Vec<Replacement>>, Vec<ReplacementError>> {
+    let mut replacements: Option<Vec<Replacement>> = None;
+    let mut errors = Vec::new();
+    for file in files {
+        match load_custom_replacements(&file.to_string_lossy()) {
+            Ok(rules) => replacements.get_or_insert_default().extend(rules),
+            Err(file_errors) => errors.extend(file_errors),
         }
     }
+    if errors.is_empty() {
+        Ok(replacements)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+fn rule(pattern: &str, replacement: &str) -> Line {
+    Line::Rule {
+        pattern: pattern.to_string(),
+        replacement: replacement.to_string(),
+        flags: String::new(),
+    }
 }
 
 #[test]
 fn test_split_valid_replacement() {
     let text = "word==WORD";
-    assert_eq!(process_line(text), ("word".to_string(), "WORD".to_string()));
+    assert_eq!(process_line(text, Syntax::V1), rule("word", "WORD"));
 }
 
 #[test]
 fn test_return_empty_for_empty_string() {
     let text = "";
-    assert_eq!(process_line(text), (String::new(), String::new()));
+    assert_eq!(process_line(text, Syntax::V1), Line::Empty);
 }
 
 #[test]
-fn test_return_empty_for_missing_splitter() {
+fn test_return_invalid_for_missing_splitter() {
     let text = "word  WORD";
-    assert_eq!(process_line(text), (String::new(), String::new()));
+    assert_eq!(
+        process_line(text, Syntax::V1),
+        Line::Invalid("no '==' found in 'word  WORD'".to_string())
+    );
+    let errors = compile("test.conf", "ok==fine\nword  WORD").unwrap_err();
+    assert_eq!(errors[0].line, 2);
 }
 
 #[test]
 fn test_process_line_is_comment() {
     let text = "# comment";
-    assert_eq!(process_line(text), (String::new(), String::new()));
+    assert_eq!(process_line(text, Syntax::V1), Line::Empty);
 
     let text = "      # comment";
-    assert_eq!(process_line(text), (String::new(), String::new()));
+    assert_eq!(process_line(text, Syntax::V1), Line::Empty);
 
     let text = "# WORD==word";
-    assert_eq!(process_line(text), (String::new(), String::new()));
+    assert_eq!(process_line(text, Syntax::V1), Line::Empty);
 }
 
-// Currently not going to support this
-/*#[test]
+#[test]
 fn test_process_line_has_comment_at_end() {
     let text = "WORD==word # Comment at end";
-    assert_eq!(process_line(text), ("WORD".to_string(), "word".to_string()));
-}*/
+    assert_eq!(process_line(text, Syntax::V2), rule("WORD", "word"));
+
+    let text = r"C#==C sharp  \# not a comment";
+    assert_eq!(
+        process_line(text, Syntax::V2),
+        rule("C#", "C sharp  # not a comment")
+    );
+}
 
 #[test]
 fn should_return_vector_of_replacements() {
     let text = "word==WORD\n\
             hi==hello";
-    let results = vec![
-        ("word".to_string(), "WORD".to_string()),
-        ("hi".to_string(), "hello".to_string()),
-    ];
+    let results = vec![rule("word", "WORD"), rule("hi", "hello")];
 
     assert_eq!(process_file_text(text), results);
 }
 
 #[test]
 fn should_apply_all_replacements() {
-    let replacements = process_file_text(
+    let replacements = compile(
+        "custom-replacements.conf",
         "word==WORD\n\
             hi==hello",
-    );
+    )
+    .unwrap();
 
     let text = "hi there, word to your brother";
     let expected = "hello there, WORD to your brother";
-    let result = process_user_replacements(text, &replacements);
+    let result = process_user_replacements(
+        text,
+        &replacements,
+        &ChapterContext::default(),
+        &mut Report::default(),
+    );
     assert_eq!(expected, result);
 }
+
+#[test]
+fn group_references_are_checked() {
+    let replacements = compile("test.conf", r"(\d+) ?kph==$1 kilometers per hour").unwrap();
+    assert_eq!(
+        process_user_replacements(
+            "at 50kph",
+            &replacements,
+            &ChapterContext::default(),
+            &mut Report::default()
+        ),
+        "at 50 kilometers per hour"
+    );
+    assert!(compile("test.conf", r"(?<n>\d)x==${n} times $$5").is_ok());
+
+    let errors = compile("test.conf", r"(\d)x==$1times").unwrap_err();
+    assert_eq!(errors[0].line, 1);
+    assert!(errors[0].message.ends_with("(write ${1}times for group 1)"));
+    assert!(compile("test.conf", r"(\d)x==$2").is_err());
+}
+
+#[test]
+fn invalid_patterns_are_reported_with_their_line() {
+    let errors = compile(
+        "custom-replacements.conf",
+        "# comment\nok==fine\n(oops==x\n[a-==y",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+        vec![3, 4]
+    );
+    assert!(
+        errors[0]
+            .to_string()
+            .starts_with("custom-replacements.conf, line 3: regex parse error")
+    );
+}
+
+#[test]
+fn replacements_files_are_layered_from_global_to_explicit() {
+    let layers = candidate_files(
+        Some(PathBuf::from("/home/reader/.config")),
+        Some("books/dune.epub"),
+        Some("out"),
+        &["names.conf".to_string()],
+    );
+    assert_eq!(
+        layers,
+        vec![
+            PathBuf::from("/home/reader/.config/ebook2audiobook/custom-replacements.conf"),
+            PathBuf::from("./custom-replacements.conf"),
+            PathBuf::from("books/custom-replacements.conf"),
+            PathBuf::from("out/custom-replacements.conf"),
+            PathBuf::from("names.conf"),
+        ]
+    );
+    assert_eq!(
+        candidate_files(None, Some("dune.epub"), None, &[]),
+        vec![
+            PathBuf::from("./custom-replacements.conf"),
+            PathBuf::from("./custom-replacements.conf"),
+        ]
+    );
+}
+
+#[test]
+fn files_found_twice_are_read_once() {
+    let extra = ["Cargo.toml", "./Cargo.toml", "missing.conf"].map(String::from);
+    let files = find_files(Some("dune.epub"), Some("."), &extra);
+    assert!(files.ends_with(&[PathBuf::from("Cargo.toml"), PathBuf::from("missing.conf")]));
+}
+
+#[test]
+fn a_file_that_cannot_be_read_is_an_error() {
+    let errors = load_files(&[PathBuf::from("missing.conf")]).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0]
+            .to_string()
+            .starts_with("missing.conf: Unable to open file: ")
+    );
+    assert!(matches!(load_files(&[]), Ok(None)));
+}
+
+#[test]
+fn escaped_equals_flags_and_sections_are_read() {
+    assert_eq!(
+        process_line(r"a\==b==a equals b==i", Syntax::V2),
+        Line::Rule {
+            pattern: "a==b".to_string(),
+            replacement: "a equals b".to_string(),
+            flags: "i".to_string(),
+        }
+    );
+    assert_eq!(
+        process_line(" [Character names]  # from the appendix", Syntax::V2),
+        Line::Section("Character names".to_string())
+    );
+}
+
+#[test]
+fn files_without_the_syntax_line_keep_their_meaning() {
+    assert_eq!(process_line("a==b==c", Syntax::V1), rule("a", "b==c"));
+    assert_eq!(
+        process_line(r"Chapter #(\d+)==Chapter $1", Syntax::V1),
+        rule(r"Chapter #(\d+)", "Chapter $1")
+    );
+    assert_eq!(Syntax::of("\n# syntax: v2\na==b"), Syntax::V2);
+    assert_eq!(Syntax::of("# comment\n# syntax: v2"), Syntax::V1);
+    let replacements = compile("test.conf", "a==b==c\nx #1==y").unwrap();
+    assert_eq!(
+        process_user_replacements(
+            "a x #1",
+            &replacements,
+            &ChapterContext::default(),
+            &mut Report::default()
+        ),
+        "b==c y"
+    );
+}
+
+#[test]
+fn flags_change_how_rules_match() {
+    let replacements = compile(
+        "test.conf",
+        "# syntax: v2\n\
+         [units]\n\
+         Dr.==Doctor==l\n\
+         $5 (approx.)==about five dollars==l\n\
+         [names]\n\
+         cat==dog==wi\n\
+         the==a==1",
+    )
+    .unwrap();
+    assert_eq!(
+        replacements
+            .iter()
+            .map(Replacement::section)
+            .collect::<Vec<_>>(),
+        vec!["units", "units", "names", "names"]
+    );
+    assert_eq!(
+        process_user_replacements(
+            "Dr. Drake's Cat, the catalog and $5 (approx.) for the cat.",
+            &replacements,
+            &ChapterContext::default(),
+            &mut Report::default()
+        ),
+        "Doctor Drake's dog, a catalog and about five dollars for the dog."
+    );
+}
+
+#[test]
+fn unknown_flags_are_reported() {
+    let errors = compile("test.conf", "# syntax: v2\na==b==c\nx==y==q").unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[1].message.starts_with("unknown flag 'q'"));
+}
+
+#[test]
+fn sections_can_be_limited_to_chapters() {
+    let replacements = compile(
+        "test.conf",
+        "# syntax: v2\n\
+         elen==EH-len\n\
+         [Elvish chapters=10-12,15]\n\
+         mellon==MEL-lon\n\
+         [Appendix title=\"^Appendix [A-F]\" idref=notes]\n\
+         tbl.==table==l",
+    )
+    .unwrap();
+    assert_eq!(replacements[1].section(), "Elvish");
+    let text = "elen mellon tbl.";
+    let chapter = |number, title, idref| {
+        process_user_replacements(
+            text,
+            &replacements,
+            &ChapterContext {
+                number,
+                title,
+                idref,
+            },
+            &mut Report::default(),
+        )
+    };
+    assert_eq!(chapter(11, "Moria", "ch11"), "EH-len MEL-lon tbl.");
+    assert_eq!(chapter(13, "Moria", "ch13"), "EH-len mellon tbl.");
+    assert_eq!(chapter(40, "Appendix B", "app"), "EH-len mellon table");
+    assert_eq!(chapter(41, "Notes", "notes"), "EH-len mellon table");
+}
+
+#[test]
+fn invalid_scopes_are_reported() {
+    let errors = compile(
+        "test.conf",
+        "[A chapters=12-10]\n[B chapter=3]\n[C title=(]",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(errors[1].message.starts_with("unknown scope 'chapter'"));
+}
+
+#[test]
+fn hits_are_counted_per_rule_and_chapter() {
+    let replacements = compile(
+        "custom-replacements.conf",
+        "# syntax: v2\n[Names]\nGryphon==Griffin\nDinha==Dye-nah\nthe==a==1",
+    )
+    .unwrap();
+    let mut report = Report::default();
+    for (number, text) in [(3, "the Gryphon, the Gryphon"), (5, "the Gryphon")] {
+        let chapter = ChapterContext {
+            number,
+            ..ChapterContext::default()
+        };
+        process_user_replacements(text, &replacements, &chapter, &mut report);
+    }
+    assert_eq!(report.unused(&replacements), 1);
+    assert_eq!(
+        report.to_text(&replacements).lines().collect::<Vec<_>>(),
+        vec![
+            "   Hits Chapters  Rule",
+            "      3        2  custom-replacements.conf:3 [Names] Gryphon",
+            "      0        0  custom-replacements.conf:4 [Names] Dinha  <- never matched",
+            "      2        2  custom-replacements.conf:5 [Names] the",
+        ]
+    );
+    let json: serde_json::Value = serde_json::from_str(&report.to_json(&replacements)).unwrap();
+    assert_eq!(json[0]["chapters"]["3"], 2);
+    assert_eq!(json[1]["unused"], true);
+}
+
+#[test]
+fn first_only_rules_match_once_per_chapter_across_pieces() {
+    let replacements = compile("test.conf", "# syntax: v2\nthe==a==1").unwrap();
+    let mut report = Report::default();
+    let mut piece = |number, text| {
+        let chapter = ChapterContext {
+            number,
+            ..ChapterContext::default()
+        };
+        process_user_replacements(text, &replacements, &chapter, &mut report)
+    };
+    assert_eq!(piece(1, "the cat"), "a cat");
+    assert_eq!(piece(1, "the dog"), "the dog");
+    assert_eq!(piece(2, "the dog"), "a dog");
+}
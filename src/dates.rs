@@ -0,0 +1,371 @@
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+
+use crate::numbers::{cardinal, ordinal, plural, year};
+
+/// How all-number dates such as 12/03/1998 are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DateOrder {
+    /// Month first, 12/03/1998 is December third
+    #[default]
+    Us,
+    /// Day first, 12/03/1998 is the twelfth of March
+    Dmy,
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Matches a month name or its abbreviation, eg "March", "Mar." or "Sept"
+const MONTH_PATTERN: &str = r"(?<month>January|February|March|April|May|June|July|August|September|October|November|December|Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec)\.?";
+
+/// Words before a twenty four hour time, eg "at 1200 hours".  After anything else, "1200 hours"
+/// is more likely a duration and is left to be read as a number.
+const TIME_PREPOSITIONS: [&str; 7] = ["at", "by", "until", "till", "from", "before", "after"];
+
+fn month_name(month: &str) -> &'static str {
+    MONTHS
+        .iter()
+        .find(|name| name.starts_with(&month[..3]))
+        .unwrap_or(&MONTHS[0])
+}
+
+/// Reads a year, a two digit year is read as it is written, eg "'98" -> "ninety-eight"
+fn year_words(text: &str) -> String {
+    let value = text.parse::<u64>().unwrap_or_default();
+    match text.len() {
+        2 if value < 10 => format!("oh {}", cardinal(value)),
+        2 => cardinal(value),
+        _ => year(value),
+    }
+}
+
+/// Speaks a date in the order it is written: "March third" or "the third of March"
+fn date_words(day: u64, month: &str, year: Option<&str>, day_first: bool) -> String {
+    let date = if day_first {
+        format!("the {} of {month}", ordinal(day))
+    } else {
+        format!("{month} {}", ordinal(day))
+    };
+    match year {
+        Some(year) => format!("{date}, {}", year_words(year)),
+        None => date,
+    }
+}
+
+/// Puts back a full stop that an abbreviation such as "p.m." or "B.C." took from the end of a
+/// sentence
+fn sentence_end(abbreviation: &str, rest: &str) -> &'static str {
+    let next_sentence = Regex::new(r#"^(\s*$|\n|\s+["“‘']?[A-Z])"#).unwrap();
+    if abbreviation.ends_with('.') && next_sentence.is_match(rest) {
+        "."
+    } else {
+        ""
+    }
+}
+
+/// Reads "a.m.", "PM"... as letters
+fn letters(abbreviation: &str) -> String {
+    abbreviation
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase().to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replaces every match of a regex with the result of `replace`, which also gets the text after
+/// the match so abbreviations can keep a sentence's full stop
+pub fn replace_with_rest(
+    re: &Regex,
+    text: &str,
+    replace: impl Fn(&Captures, &str) -> Option<String>,
+) -> String {
+    let mut result = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let rest = &text[whole.end()..];
+        // Only whole tokens, eg not the "am" in "10 amps"
+        if rest.starts_with(char::is_alphanumeric) {
+            continue;
+        }
+        let Some(replacement) = replace(&caps, rest) else {
+            continue;
+        };
+        result.push_str(&text[last..whole.start()]);
+        result.push_str(&replacement);
+        last = whole.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Converts dates written all in numbers, eg "1998-03-12" or "12/03/1998"
+fn convert_numeric_dates(text: &str, order: DateOrder) -> String {
+    // ISO dates, eg 1998-03-12
+    let re = Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").unwrap();
+    let text = replace_with_rest(&re, text, |caps, _| {
+        let month = caps[2]
+            .parse::<usize>()
+            .ok()
+            .filter(|m| (1..=12).contains(m))?;
+        let day = caps[3]
+            .parse::<u64>()
+            .ok()
+            .filter(|d| (1..=31).contains(d))?;
+        Some(date_words(
+            day,
+            MONTHS[month - 1],
+            Some(&caps[1]),
+            order == DateOrder::Dmy,
+        ))
+    });
+
+    // All-number dates, eg 12/03/1998 or 12.03.98, in the configured order unless only the other
+    // order makes sense, eg 25/12/1998
+    let re = Regex::new(r"\b(\d{1,2})([/.-])(\d{1,2})([/.-])(\d{4}|\d{2})\b").unwrap();
+    replace_with_rest(&re, &text, |caps, _| {
+        if caps[2] != caps[4] {
+            return None;
+        }
+        let first = caps[1].parse::<u64>().ok()?;
+        let second = caps[3].parse::<u64>().ok()?;
+        let valid = |month: u64, day: u64| (1..=12).contains(&month) && (1..=31).contains(&day);
+        let day_first = match order {
+            DateOrder::Us => !valid(first, second),
+            DateOrder::Dmy => valid(second, first),
+        };
+        let (month, day) = if day_first {
+            (second, first)
+        } else {
+            (first, second)
+        };
+        if !valid(month, day) {
+            return None;
+        }
+        let month = MONTHS[usize::try_from(month - 1).ok()?];
+        Some(date_words(day, month, Some(&caps[5]), day_first))
+    })
+}
+
+/// Converts dates with the month written as a word, eg "3rd of March" or "March 3"
+fn convert_written_dates(text: &str) -> String {
+    // Day before the month, eg 3 March 1998, 3rd of March
+    let re = Regex::new(&format!(
+        r"\b(?<the>[Tt]he\s+)?(?<day>\d{{1,2}})(?:st|nd|rd|th)?\s+(?:of\s+)?{MONTH_PATTERN}(?:,?\s+(?<year>\d{{4}}))?"
+    ))
+    .unwrap();
+    let text = replace_with_rest(&re, text, |caps, _| {
+        let day = caps["day"]
+            .parse::<u64>()
+            .ok()
+            .filter(|d| (1..=31).contains(d))?;
+        let date = date_words(
+            day,
+            month_name(&caps["month"]),
+            caps.name("year").map(|year| year.as_str()),
+            true,
+        );
+        // Don't double up "the 4th of July", but keep the capital of "The 4th of July"
+        match caps.name("the") {
+            Some(the) if the.as_str().starts_with('T') => Some(format!("T{}", &date[1..])),
+            _ => Some(date),
+        }
+    });
+
+    // Month before the day, eg March 3rd, 1998
+    let re = Regex::new(&format!(
+        r"\b{MONTH_PATTERN}\s+(?<day>\d{{1,2}})(?:st|nd|rd|th)?(?:,?\s+(?<year>\d{{4}}))?"
+    ))
+    .unwrap();
+    replace_with_rest(&re, &text, |caps, _| {
+        let day = caps["day"]
+            .parse::<u64>()
+            .ok()
+            .filter(|d| (1..=31).contains(d))?;
+        Some(date_words(
+            day,
+            month_name(&caps["month"]),
+            caps.name("year").map(|year| year.as_str()),
+            false,
+        ))
+    })
+}
+
+/// Converts years with an era, eg "AD 79" -> "A D seventy-nine", and decades such as "'90s"
+fn convert_eras(text: &str) -> String {
+    // Eras, eg AD 79, 300 BC, 1066 A.D.
+    let re = Regex::new(r"\b(?<era>AD|A\.D\.)\s?(?<year>\d{1,4})\b").unwrap();
+    let text = replace_with_rest(&re, text, |caps, _| {
+        Some(format!(
+            "{} {}",
+            letters(&caps["era"]),
+            year_words(&caps["year"])
+        ))
+    });
+    let re =
+        Regex::new(r"\b(?<year>\d{1,4})\s?(?<era>BCE|B\.C\.E\.|BC|B\.C\.|AD|A\.D\.|CE|C\.E\.)")
+            .unwrap();
+    let text = replace_with_rest(&re, &text, |caps, rest| {
+        Some(format!(
+            "{} {}{}",
+            year_words(&caps["year"]),
+            letters(&caps["era"]),
+            sentence_end(&caps["era"], rest)
+        ))
+    });
+
+    // Decades written with an apostrophe, eg the '90s
+    let re = Regex::new(r"['’](?<decade>\d0)s\b").unwrap();
+    replace_with_rest(&re, &text, |caps, _| {
+        Some(plural(&cardinal(caps["decade"].parse().ok()?)))
+    })
+}
+
+/// Converts twelve and twenty four hour times, eg "10:45 p.m." -> "ten forty-five P M"
+fn convert_times(text: &str) -> String {
+    // Twelve hour times, eg 10:45 p.m., 9am
+    let re = Regex::new(
+        r"\b(?<hour>1[0-2]|0?[1-9])(?::(?<minutes>[0-5]\d))?\s?(?<period>a\.m\.|p\.m\.|A\.M\.|P\.M\.|am|pm|AM|PM)",
+    )
+    .unwrap();
+    let text = replace_with_rest(&re, text, |caps, rest| {
+        let hour = cardinal(caps["hour"].parse().ok()?);
+        let time = match caps.name("minutes").map(|m| m.as_str().parse::<u64>()) {
+            Some(Ok(0)) | None => hour,
+            Some(Ok(minutes)) if minutes < 10 => format!("{hour} oh {}", cardinal(minutes)),
+            Some(Ok(minutes)) => format!("{hour} {}", cardinal(minutes)),
+            Some(Err(_)) => return None,
+        };
+        Some(format!(
+            "{time} {}{}",
+            letters(&caps["period"]),
+            sentence_end(&caps["period"], rest)
+        ))
+    });
+
+    // Twenty four hour times, eg 0900 hours -> "oh nine hundred hours", but not "for 1000 hours"
+    let re = Regex::new(r"\b(?<hour>[01]\d|2[0-3])(?<minutes>[0-5]\d)\s?hours\b").unwrap();
+    replace_with_rest(&re, &text, |caps, _| {
+        let previous_word = text[..caps.get(0)?.start()]
+            .split_whitespace()
+            .next_back()
+            .unwrap_or_default()
+            .to_lowercase();
+        if !caps["hour"].starts_with('0') && !TIME_PREPOSITIONS.contains(&previous_word.as_str()) {
+            return None;
+        }
+        let hour = caps["hour"].parse::<u64>().ok()?;
+        let minutes = caps["minutes"].parse::<u64>().ok()?;
+        let hour = match hour {
+            0 => "zero".to_string(),
+            1..=9 => format!("oh {}", cardinal(hour)),
+            _ => cardinal(hour),
+        };
+        let minutes = match minutes {
+            0 => "hundred".to_string(),
+            1..=9 => format!("oh {}", cardinal(minutes)),
+            _ => cardinal(minutes),
+        };
+        Some(format!("{hour} {minutes} hours"))
+    })
+}
+
+/// Converts dates and times to the way they are spoken, eg "March 3rd, 1998" -> "March third,
+/// nineteen ninety-eight", "3 March 1998" -> "the third of March, nineteen ninety-eight",
+/// "300 BC" -> "three hundred B C" and "10:45 p.m." -> "ten forty-five P M".
+///
+/// # Arguments
+/// * `text` - The text to convert
+/// * `order` - Whether all-number dates are month or day first
+/// # Returns
+/// The text with dates and times in words
+pub fn convert_dates_to_words(text: &str, order: DateOrder) -> String {
+    let text = convert_numeric_dates(text, order);
+    let text = convert_written_dates(&text);
+    let text = convert_eras(&text);
+    convert_times(&text)
+}
+
+#[test]
+fn numeric_dates_follow_the_date_order() {
+    assert_eq!(
+        convert_dates_to_words("on 12/03/1998.", DateOrder::Us),
+        "on December third, nineteen ninety-eight."
+    );
+    assert_eq!(
+        convert_dates_to_words("on 12/03/1998.", DateOrder::Dmy),
+        "on the twelfth of March, nineteen ninety-eight."
+    );
+    // Only one order makes sense
+    assert_eq!(
+        convert_dates_to_words("on 25/12/98", DateOrder::Us),
+        "on the twenty-fifth of December, ninety-eight"
+    );
+    assert_eq!(
+        convert_dates_to_words("on 1998-03-12", DateOrder::Us),
+        "on March twelfth, nineteen ninety-eight"
+    );
+}
+
+#[test]
+fn written_dates_are_read_in_the_order_written() {
+    assert_eq!(
+        convert_dates_to_words("3 March 1998 and March 3rd, 1998", DateOrder::Us),
+        "the third of March, nineteen ninety-eight and March third, nineteen ninety-eight"
+    );
+    assert_eq!(
+        convert_dates_to_words("the 4th of July and Dec. 25", DateOrder::Dmy),
+        "the fourth of July and December twenty-fifth"
+    );
+}
+
+#[test]
+fn eras_and_decades_are_read_out() {
+    assert_eq!(
+        convert_dates_to_words(
+            "Pompeii fell in AD 79, Rome was founded in 753 B.C. The '90s",
+            DateOrder::Us
+        ),
+        "Pompeii fell in A D seventy-nine, Rome was founded in seven hundred and fifty-three B C. The nineties"
+    );
+}
+
+#[test]
+fn times_are_read_out() {
+    assert_eq!(
+        convert_dates_to_words(
+            "at 10:45 p.m. and 9am, then 7:05 PM or 0900 hours",
+            DateOrder::Us
+        ),
+        "at ten forty-five P M and nine A M, then seven oh five P M or oh nine hundred hours"
+    );
+    assert_eq!(
+        convert_dates_to_words("until 1430 hours", DateOrder::Us),
+        "until fourteen thirty hours"
+    );
+    assert_eq!(
+        convert_dates_to_words(
+            "He practised for 1000 hours, 1200 hours of work",
+            DateOrder::Us
+        ),
+        "He practised for 1000 hours, 1200 hours of work"
+    );
+    assert_eq!(
+        convert_dates_to_words("I am 10 amps", DateOrder::Us),
+        "I am 10 amps"
+    );
+}
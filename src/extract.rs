@@ -0,0 +1,351 @@
+use ego_tree::NodeRef;
+use scraper::node::Element;
+use scraper::{Html, Node, Selector};
+
+use crate::hyphenation;
+use crate::language::Language;
+
+/// Elements that start a new paragraph
+const BLOCK_ELEMENTS: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tbody",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Wrapped around the text of an element with a `lang` attribute: the start, then the language
+/// tag, then the end of the tag, then the text and then the end.  Private use characters, so they
+/// can't clash with the book's own text.
+pub const LANGUAGE_START: char = '\u{E003}';
+pub const LANGUAGE_TAG_END: char = '\u{E004}';
+pub const LANGUAGE_END: char = '\u{E005}';
+
+/// Marks the end of a drop cap or small caps lead in, so `hyphenation::rejoin_lead_ins` can join
+/// it to the rest of its word
+pub const LEAD_IN_END: char = '\u{E006}';
+
+/// Class names (or parts of them) publishers use for drop caps and small caps lead ins
+const LEAD_IN_CLASSES: [&str; 10] = [
+    "drop",
+    "initial",
+    "lettrine",
+    "first-letter",
+    "firstletter",
+    "smallcap",
+    "small-cap",
+    "smcap",
+    "leadin",
+    "lead-in",
+];
+
+/// Returns true if the element is styled as a drop cap or small caps
+fn is_lead_in(element: &Element) -> bool {
+    let class = element.classes().any(|class| {
+        let class = class.to_lowercase();
+        class == "sc" || LEAD_IN_CLASSES.iter().any(|name| class.contains(name))
+    });
+    let style = element
+        .attr("style")
+        .is_some_and(|style| style.contains("small-caps") || style.contains("float"));
+    class || style
+}
+
+/// Elements whose content is never read out
+const SKIPPED_ELEMENTS: [&str; 6] = ["head", "noscript", "script", "style", "template", "title"];
+
+/// Text accumulated while walking the DOM
+#[derive(Default)]
+struct TextBuilder {
+    text: String,
+    /// The language tags of the elements being walked, and whether their start marker has been
+    /// written.  It is only written before the first word, so empty elements leave no marker.
+    languages: Vec<(String, bool)>,
+    /// The language of the book, for joining words hyphenated across lines
+    language: Language,
+}
+
+impl TextBuilder {
+    /// Makes sure the text ends with at least `count` newlines (unless nothing has been written yet)
+    fn line_break(&mut self, count: usize) {
+        if self.text.is_empty() {
+            return;
+        }
+        let trimmed = self.text.trim_end_matches([' ', '\t']).len();
+        self.text.truncate(trimmed);
+
+        let existing = self.text.len() - self.text.trim_end_matches('\n').len();
+        for _ in existing..count {
+            self.text.push('\n');
+        }
+    }
+
+    /// Adds a single space, unless the text is empty or already ends with whitespace
+    fn space(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with([' ', '\n']) {
+            self.text.push(' ');
+        }
+    }
+
+    /// Adds inline text, collapsing runs of whitespace to a single space as a browser would.  Words
+    /// hyphenated across lines in the source are joined first.
+    fn push_text(&mut self, text: &str) {
+        let text = hyphenation::dehyphenate(text, self.language);
+        let leading_space = text.starts_with(char::is_whitespace);
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 || leading_space {
+                self.space();
+            }
+            self.start_languages();
+            self.text.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.space();
+        }
+    }
+
+    /// Adds preformatted text as is
+    fn push_preformatted(&mut self, text: &str) {
+        self.start_languages();
+        self.text.push_str(text);
+    }
+
+    /// Writes the start markers of language spans that have no text yet
+    fn start_languages(&mut self) {
+        for (tag, started) in &mut self.languages {
+            if !*started {
+                self.text.push(LANGUAGE_START);
+                self.text.push_str(tag);
+                self.text.push(LANGUAGE_TAG_END);
+                *started = true;
+            }
+        }
+    }
+
+    /// Ends the innermost language span, before any trailing space or line breaks so they stay
+    /// outside it
+    fn end_language(&mut self) {
+        if let Some((_, true)) = self.languages.pop() {
+            let trimmed = self.text.trim_end_matches([' ', '\n']).len();
+            let space = self.text.split_off(trimmed);
+            self.text.push(LANGUAGE_END);
+            self.text.push_str(&space);
+        }
+    }
+
+    /// Marks the end of a lead in, if everything written since `start` is a single word or part of
+    /// one, eg "T" or "HE"
+    fn end_lead_in(&mut self, start: usize) {
+        let trimmed = self.text.trim_end_matches([' ', '\n']).len();
+        let written = self.text.get(start..trimmed).unwrap_or_default().trim();
+        if !written.is_empty() && !written.contains(char::is_whitespace) {
+            let space = self.text.split_off(trimmed);
+            self.text.push(LEAD_IN_END);
+            self.text.push_str(&space);
+        }
+    }
+
+    /// Strips trailing whitespace from every line, joins drop caps and words hyphenated across
+    /// line breaks, and ends the text with a single newline
+    fn finish(self) -> String {
+        let text = self
+            .text
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = hyphenation::dehyphenate(&hyphenation::rejoin_lead_ins(&text), self.language);
+        let text = text.trim_end_matches('\n');
+        if text.is_empty() {
+            String::new()
+        } else {
+            format!("{text}\n")
+        }
+    }
+}
+
+fn walk(node: NodeRef<Node>, builder: &mut TextBuilder, preformatted: bool) {
+    for child in node.children() {
+        match child.value() {
+            Node::Text(text) => {
+                if preformatted {
+                    builder.push_preformatted(text);
+                } else {
+                    builder.push_text(text);
+                }
+            }
+            Node::Element(element) => {
+                let name = element.name();
+                if SKIPPED_ELEMENTS.contains(&name) {
+                    continue;
+                }
+                if name == "br" {
+                    builder.line_break(1);
+                    continue;
+                }
+                if name == "td" || name == "th" {
+                    builder.space();
+                }
+
+                let block = BLOCK_ELEMENTS.contains(&name);
+                if block {
+                    builder.line_break(2);
+                }
+                let language = element.attr("xml:lang").or_else(|| element.attr("lang"));
+                if let Some(language) = language {
+                    builder.languages.push((language.trim().to_string(), false));
+                }
+                let start = builder.text.len();
+                walk(child, builder, preformatted || name == "pre");
+                if is_lead_in(element) {
+                    builder.end_lead_in(start);
+                }
+                if language.is_some() {
+                    builder.end_language();
+                }
+                if block {
+                    builder.line_break(2);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Extracts text stream from html
+///
+/// Block elements (`p`, `div`, `h1`-`h6`, `li`, `blockquote`, `tr`...) are separated by a blank
+/// line and `<br/>` becomes a line break, so TTS engines pause between paragraphs and headings
+/// don't run into the first sentence.  Whitespace inside a block is collapsed as a browser would,
+/// and `script`, `style` and `head` content is skipped.  The text of elements with a `lang` or
+/// `xml:lang` attribute is wrapped in `LANGUAGE_START`/`LANGUAGE_END` markers, which
+/// `spans::read_spans` turns into language spans.  Drop caps and small caps lead ins are joined to
+/// the rest of their word, and words hyphenated across lines are joined.
+///
+/// # Arguments
+/// * `html` - String to convert
+/// * `language` - The language of the book
+/// # Returns
+/// String of unfiltered text
+pub fn extract_text_from_html(html: &str, language: Language) -> String {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse("body").unwrap();
+    let mut builder = TextBuilder {
+        language,
+        ..TextBuilder::default()
+    };
+    for body in document.select(&selector) {
+        walk(*body, &mut builder, false);
+    }
+    builder.finish()
+}
+
+/// Extracts the text of a single node (and everything inside it) the same way as
+/// `extract_text_from_html`
+///
+/// # Arguments
+/// * `node` - The node to convert
+/// * `language` - The language of the book
+/// # Returns
+/// String of unfiltered text
+pub fn extract_text_from_node(node: NodeRef<Node>, language: Language) -> String {
+    let mut builder = TextBuilder {
+        language,
+        ..TextBuilder::default()
+    };
+    walk(node, &mut builder, false);
+    builder.finish()
+}
+
+#[test]
+fn paragraphs_are_separated_by_blank_lines() {
+    let html = "<html><body><p>First paragraph.</p><p>Second\n   paragraph.</p></body></html>";
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "First paragraph.\n\nSecond paragraph.\n"
+    );
+}
+
+#[test]
+fn headings_do_not_run_into_the_first_sentence() {
+    let html = "<body><h2>CHAPTER III.<br/>\nA Caucus-Race</h2><p>\nThey were indeed a queer-looking party.\n</p></body>";
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "CHAPTER III.\nA Caucus-Race\n\nThey were indeed a queer-looking party.\n"
+    );
+}
+
+#[test]
+fn inline_elements_keep_their_spacing() {
+    let html = "<body><p>out, “Sit down! <i>I’ll</i> soon make <b>you</b>dry<span> enough</span>!”</p></body>";
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "out, “Sit down! I’ll soon make youdry enough!”\n"
+    );
+}
+
+#[test]
+fn script_style_and_head_are_skipped() {
+    let html = "<html><head><title>Title</title><style>p { color: red }</style></head><body><script>var x = 1;</script><p>Text</p></body></html>";
+    assert_eq!(extract_text_from_html(html, Language::English), "Text\n");
+}
+
+#[test]
+fn lists_tables_and_quotes_are_blocks() {
+    let html = "<body><ul><li>one</li><li>two</li></ul><blockquote>quote</blockquote><table><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr></table></body>";
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "one\n\ntwo\n\nquote\n\na b\n\nc\n"
+    );
+}
+
+#[test]
+fn extract_text_handles_empty_string() {
+    assert_eq!(extract_text_from_html("", Language::English), "");
+}
+
+#[test]
+fn language_spans_are_marked() {
+    let html = r#"<body><p>He said <i xml:lang="fr">bonjour </i>and left.</p><blockquote lang="de"><p>Guten Tag</p></blockquote><p lang="es"></p></body>"#;
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "He said \u{E003}fr\u{E004}bonjour\u{E005} and left.\n\n\u{E003}de\u{E004}Guten Tag\u{E005}\n"
+    );
+}
+
+#[test]
+fn drop_caps_and_hyphenated_words_are_joined() {
+    let html = r#"<body><div class="dropcap">T</div><p>he day was <span class="sc">exam-
+ple</span> and <span class="drop">I</span> was <span style="font-variant: small-caps">A</span> <span>round</span> one.</p></body>"#;
+    assert_eq!(
+        extract_text_from_html(html, Language::English),
+        "The day was example and I was A round one.\n"
+    );
+}
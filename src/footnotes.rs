@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+
+use clap::ValueEnum;
+use ego_tree::{NodeId, NodeRef};
+use epub::doc::EpubDoc;
+use regex::Regex;
+use scraper::node::{Element, Text};
+use scraper::{ElementRef, Html, Node};
+
+use crate::extract::extract_text_from_node;
+use crate::language::Language;
+use crate::matter::resolve_href;
+
+/// Marks where a note was referenced in the extracted text, until the notes are placed.  Private
+/// use characters so they can't clash with anything in the book.
+const PLACEHOLDER_START: char = '\u{E001}';
+const PLACEHOLDER_END: char = '\u{E002}';
+
+/// Elements that can hold a note.  A link to an inline element is widened to the nearest of these.
+const NOTE_BLOCKS: [&str; 8] = ["aside", "blockquote", "dd", "div", "dt", "li", "p", "td"];
+
+/// Elements a note never extends past, so a bad link can't take a whole chapter with it
+const NOTE_BOUNDARIES: [&str; 10] = [
+    "article", "body", "h1", "h2", "h3", "h4", "h5", "h6", "html", "section",
+];
+
+/// Anything longer than this is assumed to be a link to the text, not to a note
+const MAXIMUM_NOTE_LENGTH: usize = 4000;
+
+/// How footnotes and endnotes are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FootnoteMode {
+    /// Remove the notes and their markers
+    Drop,
+    /// Read each note after the paragraph that references it
+    Inline,
+    /// Read the notes referenced in a chapter at the end of the chapter
+    #[default]
+    End,
+}
+
+#[derive(Debug)]
+struct Note {
+    text: String,
+    /// Whether any link in the book points at the note
+    referenced: bool,
+}
+
+/// Every footnote and endnote in the book, by the path and id of the note
+#[derive(Debug, Default)]
+pub struct Footnotes {
+    notes: HashMap<(String, String), Note>,
+}
+
+/// Returns the `epub:type` and `role` values of an element
+fn semantic_types(element: &Element) -> Vec<&str> {
+    let mut types: Vec<&str> = element
+        .attr("epub:type")
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    types.extend(element.attr("role").unwrap_or_default().split_whitespace());
+    types
+}
+
+fn is_note_body(element: &Element) -> bool {
+    semantic_types(element).iter().any(|semantic_type| {
+        matches!(
+            *semantic_type,
+            "footnote" | "endnote" | "rearnote" | "note" | "doc-footnote" | "doc-endnote"
+        )
+    })
+}
+
+/// Returns true if the element is an aside or is marked up as a note
+fn is_note_like(element: &Element) -> bool {
+    element.name() == "aside" || is_note_body(element)
+}
+
+/// Returns true if the link is marked up as a note reference
+fn is_noteref(element: &Element) -> bool {
+    semantic_types(element)
+        .iter()
+        .any(|semantic_type| *semantic_type == "noteref" || *semantic_type == "doc-noteref")
+}
+
+/// Returns true for the text of a note marker, eg `1`, `[12]`, `*` or `†`
+fn is_marker(text: &str) -> bool {
+    let re = Regex::new(r"^\s*[\[(]?\s*(\d{1,4}|[*†‡§¶↩]+)\s*[\])]?\s*$").unwrap();
+    re.is_match(text)
+}
+
+fn text_of(node: NodeRef<Node>) -> String {
+    node.descendants()
+        .filter_map(|node| node.value().as_text())
+        .map(|text| &**text)
+        .collect()
+}
+
+fn is_element(node: NodeRef<Node>, name: &str) -> bool {
+    node.value()
+        .as_element()
+        .is_some_and(|element| element.name() == name)
+}
+
+/// Returns the href if the node is a link to a note
+fn noteref_href(node: NodeRef<'_, Node>) -> Option<&str> {
+    let element = node.value().as_element()?;
+    if element.name() != "a" {
+        return None;
+    }
+    let href = element.attr("href")?;
+    let (_, fragment) = href.split_once('#')?;
+    if is_noteref(element) {
+        return Some(href);
+    }
+    if !is_marker(&text_of(node)) {
+        return None;
+    }
+
+    // A bare number is only a note marker if it is superscript or points at a note-like id
+    // (Calibre conversions use `filepos` ids for everything)
+    let note_fragment = Regex::new(r"(?i)(fn|note|filepos)").unwrap();
+    let superscript = node.ancestors().any(|ancestor| is_element(ancestor, "sup"))
+        || node
+            .descendants()
+            .any(|descendant| is_element(descendant, "sup"));
+    (superscript || note_fragment.is_match(fragment)).then_some(href)
+}
+
+/// Resolves a link to `(path, fragment)`, treating a bare `#fragment` as the same document
+fn resolve_link(base: &str, href: &str) -> (String, Option<String>) {
+    match href.strip_prefix('#') {
+        Some(fragment) => (base.to_string(), Some(fragment.to_string())),
+        None => resolve_href(base, href),
+    }
+}
+
+/// Returns the id of an element, or the name of an old style `<a name="...">` anchor
+fn anchor_id(element: &Element) -> Option<&str> {
+    element.id().or_else(|| {
+        if element.name() == "a" {
+            element.attr("name")
+        } else {
+            None
+        }
+    })
+}
+
+/// Widens the target of a note link to the whole note
+fn note_block(node: NodeRef<Node>) -> Option<NodeRef<Node>> {
+    let mut current = Some(node);
+    while let Some(candidate) = current {
+        if let Some(element) = candidate.value().as_element() {
+            if is_note_body(element) || NOTE_BLOCKS.contains(&element.name()) {
+                return Some(candidate);
+            }
+            if NOTE_BOUNDARIES.contains(&element.name()) {
+                return None;
+            }
+        }
+        current = candidate.parent();
+    }
+    None
+}
+
+/// Returns the text of a note, without the marker and the link back to the text
+fn note_text(block: NodeRef<Node>, language: Language) -> String {
+    let Some(element) = ElementRef::wrap(block) else {
+        return String::new();
+    };
+    let mut fragment = Html::parse_fragment(&element.html());
+    let markers: Vec<NodeId> = fragment
+        .tree
+        .root()
+        .descendants()
+        .filter(|node| is_element(*node, "a") || is_element(*node, "sup"))
+        .filter(|node| is_marker(&text_of(*node)))
+        .map(|node| node.id())
+        .collect();
+    for id in markers {
+        if let Some(mut node) = fragment.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+
+    let text = extract_text_from_node(fragment.tree.root(), language);
+    let leading_marker = Regex::new(r"^\s*(\[\d+\]|\(\d+\)|\d+[.)]|[*†‡§¶]+)\s+").unwrap();
+    leading_marker.replace(&text, "").trim().to_string()
+}
+
+/// Returns true if a note links back to the reference that points at it
+///
+/// # Arguments
+/// * `block` - The note
+/// * `path` - The path of the document the note is in
+/// * `documents` - `(path, html)` of every spine document
+/// * `document` - The index of the document with the reference
+/// * `link` - The reference
+fn links_back(
+    block: NodeRef<Node>,
+    path: &str,
+    documents: &[(String, Html)],
+    document: usize,
+    link: NodeId,
+) -> bool {
+    let (link_path, html) = &documents[document];
+    let Some(link) = html.tree.get(link) else {
+        return false;
+    };
+    let link_ids: Vec<&str> = std::iter::once(link)
+        .chain(link.ancestors())
+        .filter_map(|node| node.value().as_element().and_then(anchor_id))
+        .collect();
+    block
+        .descendants()
+        .filter_map(|node| node.value().as_element())
+        .filter(|element| element.name() == "a")
+        .filter_map(|element| element.attr("href"))
+        .any(|href| match resolve_link(path, href) {
+            (back_path, Some(fragment)) => {
+                back_path == *link_path && link_ids.contains(&fragment.as_str())
+            }
+            _ => false,
+        })
+}
+
+impl Footnotes {
+    /// Finds every note in the book by following the note links in every spine document
+    ///
+    /// # Arguments
+    /// * `doc` - the epub document
+    /// * `language` - the language of the book
+    /// # Returns
+    /// The notes, ready to be placed by `process_html`
+    pub fn collect(doc: &mut EpubDoc<BufReader<File>>, language: Language) -> Self {
+        let spine = doc.spine.clone();
+        let mut documents: Vec<(String, Html)> = Vec::new();
+        for item in &spine {
+            let Some(resource) = doc.resources.get(&item.idref) else {
+                continue;
+            };
+            let path = resource.path.clone();
+            if let Some(html) = doc.get_resource_str_by_path(&path) {
+                documents.push((path.to_string_lossy().into(), Html::parse_document(&html)));
+            }
+        }
+        Self::from_documents(&documents, language)
+    }
+
+    /// Finds every note in already parsed documents
+    ///
+    /// # Arguments
+    /// * `documents` - `(path, html)` of every spine document in reading order
+    /// * `language` - the language of the book
+    fn from_documents(documents: &[(String, Html)], language: Language) -> Self {
+        // Every element id, by document path
+        let mut ids: HashMap<&str, (usize, HashMap<&str, NodeId>)> = HashMap::new();
+        // Every note link in reading order, and where it is in that order
+        let mut links: Vec<(usize, NodeId, &str)> = Vec::new();
+        let mut link_order: HashMap<(usize, NodeId), usize> = HashMap::new();
+
+        for (i, (path, html)) in documents.iter().enumerate() {
+            let mut document_ids = HashMap::new();
+            for node in html.tree.root().descendants() {
+                if let Some(id) = node.value().as_element().and_then(anchor_id) {
+                    document_ids.entry(id).or_insert_with(|| node.id());
+                }
+                if let Some(href) = noteref_href(node) {
+                    link_order.insert((i, node.id()), links.len());
+                    links.push((i, node.id(), href));
+                }
+            }
+            ids.insert(path.as_str(), (i, document_ids));
+        }
+
+        let mut notes = HashMap::new();
+        for (order, (i, link, href)) in links.iter().enumerate() {
+            let (path, Some(fragment)) = resolve_link(&documents[*i].0, href) else {
+                continue;
+            };
+            let Some((target_document, document_ids)) = ids.get(path.as_str()) else {
+                continue;
+            };
+            let Some(target_id) = document_ids.get(fragment.as_str()) else {
+                continue;
+            };
+            let Some(target) = documents[*target_document].1.tree.get(*target_id) else {
+                continue;
+            };
+
+            // A link to an earlier note link is the way back from a note, not a reference
+            let back_link = std::iter::once(target)
+                .chain(target.ancestors())
+                .any(|node| {
+                    link_order
+                        .get(&(*target_document, node.id()))
+                        .is_some_and(|target_order| *target_order < order)
+                });
+            if back_link {
+                continue;
+            }
+
+            let Some(block) = note_block(target) else {
+                continue;
+            };
+            if *target_document == *i && block.descendants().any(|node| node.id() == *link) {
+                continue;
+            }
+
+            // Only take text out of the book when the target is clearly a note: marked up as one,
+            // or linking back to the reference
+            let note_like = std::iter::once(target)
+                .chain(target.ancestors())
+                .take_while(|node| node.id() != block.id())
+                .chain(std::iter::once(block))
+                .filter_map(|node| node.value().as_element())
+                .any(is_note_like);
+            if !note_like
+                && !links_back(block, &documents[*target_document].0, documents, *i, *link)
+            {
+                continue;
+            }
+            let text = note_text(block, language);
+            if text.is_empty() || text.len() > MAXIMUM_NOTE_LENGTH {
+                continue;
+            }
+            notes.insert(
+                (path, fragment),
+                Note {
+                    text,
+                    referenced: true,
+                },
+            );
+        }
+
+        // Notes marked up as notes, even if nothing links to them
+        for (path, html) in documents {
+            for node in html.tree.root().descendants() {
+                if let Some(element) = node.value().as_element()
+                    && is_note_body(element)
+                    && let Some(id) = element.id()
+                {
+                    notes
+                        .entry((path.clone(), id.to_string()))
+                        .or_insert_with(|| Note {
+                            text: note_text(node, language),
+                            referenced: false,
+                        });
+                }
+            }
+        }
+
+        Self { notes }
+    }
+
+    /// Returns the number of notes that are referenced from the text
+    pub fn len(&self) -> usize {
+        self.notes.values().filter(|note| note.referenced).count()
+    }
+
+    /// Removes the notes and note markers from the html of a spine document.  Unless the mode is
+    /// `Drop`, each note reference is replaced by a placeholder that `place_notes` turns into the
+    /// text of the note once the text has been extracted.
+    ///
+    /// # Arguments
+    /// * `html` - The html of the document
+    /// * `path` - The path of the document inside the epub
+    /// * `mode` - How the notes will be read
+    /// * `chapter_notes` - The text of the notes referenced so far in the chapter, which the
+    ///   placeholders index into
+    /// # Returns
+    /// The html without notes
+    pub fn process_html(
+        &self,
+        html: &str,
+        path: &str,
+        mode: FootnoteMode,
+        chapter_notes: &mut Vec<String>,
+    ) -> String {
+        let mut document = Html::parse_document(html);
+        let mut notes: Vec<NodeId> = Vec::new();
+        let mut markers: Vec<(NodeId, Option<String>, bool)> = Vec::new();
+
+        for node in document.tree.root().descendants() {
+            let Some(element) = node.value().as_element() else {
+                continue;
+            };
+            if node
+                .ancestors()
+                .any(|ancestor| notes.contains(&ancestor.id()))
+            {
+                continue;
+            }
+
+            let note = anchor_id(element)
+                .and_then(|id| self.notes.get(&(path.to_string(), id.to_string())));
+            if let Some(note) = note
+                && (note.referenced || mode == FootnoteMode::Drop)
+                && let Some(block) = note_block(node)
+            {
+                notes.push(block.id());
+                continue;
+            }
+            if is_note_body(element) && mode == FootnoteMode::Drop {
+                notes.push(node.id());
+                continue;
+            }
+
+            if let Some(href) = noteref_href(node) {
+                let (target_path, fragment) = resolve_link(path, href);
+                let note = fragment.and_then(|fragment| self.notes.get(&(target_path, fragment)));
+                // A number that doesn't lead to a note is part of the text
+                if note.is_none() && !is_noteref(element) {
+                    continue;
+                }
+                let placeholder = note.filter(|_| mode != FootnoteMode::Drop).map(|note| {
+                    chapter_notes.push(note.text.clone());
+                    format!(
+                        "{PLACEHOLDER_START}{}{PLACEHOLDER_END}",
+                        chapter_notes.len() - 1
+                    )
+                });
+
+                // Take a superscript wrapped around the link with it
+                let marker = node
+                    .parent()
+                    .filter(|parent| is_element(*parent, "sup") && is_marker(&text_of(*parent)))
+                    .unwrap_or(node);
+                markers.push((marker.id(), placeholder, is_marker(&text_of(node))));
+            } else if element.name() == "sup"
+                && is_marker(&text_of(node))
+                && !node
+                    .descendants()
+                    .any(|descendant| is_element(descendant, "a"))
+                && !node.ancestors().any(|ancestor| is_element(ancestor, "a"))
+            {
+                // A marker that doesn't link anywhere, eg `*` with the note at the bottom
+                markers.push((node.id(), None, true));
+            }
+        }
+
+        for (id, placeholder, remove) in markers {
+            let Some(mut marker) = document.tree.get_mut(id) else {
+                continue;
+            };
+            if let Some(placeholder) = placeholder {
+                marker.insert_after(Node::Text(Text {
+                    text: placeholder.into(),
+                }));
+            }
+            if remove {
+                marker.detach();
+            }
+        }
+        for id in notes {
+            if let Some(mut note) = document.tree.get_mut(id) {
+                note.detach();
+            }
+        }
+
+        document.html()
+    }
+}
+
+/// Replaces the note placeholders in the extracted text of a chapter with the notes
+///
+/// # Arguments
+/// * `text` - The text of the chapter
+/// * `notes` - The notes the placeholders refer to
+/// * `mode` - Where the notes should be read
+/// # Returns
+/// The text with the notes in place
+pub fn place_notes(text: &str, notes: &[String], mode: FootnoteMode) -> String {
+    let placeholder = Regex::new(&format!(r"{PLACEHOLDER_START}(\d+){PLACEHOLDER_END}")).unwrap();
+    let note = |index: &str| {
+        index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| notes.get(index))
+            .map(|note| note.trim().to_string())
+    };
+
+    match mode {
+        FootnoteMode::Drop => placeholder.replace_all(text, "").to_string(),
+        FootnoteMode::Inline => {
+            let paragraphs: Vec<String> = text
+                .trim_end_matches('\n')
+                .split("\n\n")
+                .map(|paragraph| {
+                    let mut result = placeholder.replace_all(paragraph, "").to_string();
+                    for capture in placeholder.captures_iter(paragraph) {
+                        if let Some(note) = note(&capture[1]) {
+                            result.push_str("\n\nFootnote: ");
+                            result.push_str(&note);
+                        }
+                    }
+                    result
+                })
+                .collect();
+            let result = paragraphs.join("\n\n");
+            if result.is_empty() {
+                result
+            } else {
+                result + "\n"
+            }
+        }
+        FootnoteMode::End => {
+            let result = placeholder.replace_all(text, "").to_string();
+            let chapter_notes: Vec<String> = placeholder
+                .captures_iter(text)
+                .filter_map(|capture| note(&capture[1]))
+                .collect();
+            if chapter_notes.is_empty() {
+                return result;
+            }
+
+            let chapter_notes: Vec<String> = chapter_notes
+                .iter()
+                .enumerate()
+                .map(|(i, note)| format!("Footnote {}: {note}", i + 1))
+                .collect();
+            format!(
+                "{}\n\nFootnotes.\n\n{}\n",
+                result.trim_end(),
+                chapter_notes.join("\n\n")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+fn convert(documents: &[(&str, &str)], path: &str, mode: FootnoteMode) -> String {
+    let parsed: Vec<(String, Html)> = documents
+        .iter()
+        .map(|(path, html)| (path.to_string(), Html::parse_document(html)))
+        .collect();
+    let footnotes = Footnotes::from_documents(&parsed, Language::English);
+    let html = documents
+        .iter()
+        .find(|(document_path, _)| *document_path == path)
+        .unwrap()
+        .1;
+    let mut notes = Vec::new();
+    let html = footnotes.process_html(html, path, mode, &mut notes);
+    place_notes(
+        &crate::extract::extract_text_from_html(&html, Language::English),
+        &notes,
+        mode,
+    )
+}
+
+#[cfg(test)]
+const EPUB3_CHAPTER: &str = r##"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+<p>First paragraph.<a epub:type="noteref" href="#n1">1</a></p>
+<p>Second paragraph.</p>
+<aside epub:type="footnote" id="n1"><p>The note.</p></aside>
+</body></html>"##;
+
+#[test]
+fn drop_mode_removes_notes_and_markers() {
+    assert_eq!(
+        convert(
+            &[("OEBPS/ch1.xhtml", EPUB3_CHAPTER)],
+            "OEBPS/ch1.xhtml",
+            FootnoteMode::Drop
+        ),
+        "First paragraph.\n\nSecond paragraph.\n"
+    );
+}
+
+#[test]
+fn inline_mode_reads_note_after_the_paragraph() {
+    assert_eq!(
+        convert(
+            &[("OEBPS/ch1.xhtml", EPUB3_CHAPTER)],
+            "OEBPS/ch1.xhtml",
+            FootnoteMode::Inline
+        ),
+        "First paragraph.\n\nFootnote: The note.\n\nSecond paragraph.\n"
+    );
+}
+
+#[test]
+fn end_mode_gathers_notes_at_the_end_of_the_chapter() {
+    assert_eq!(
+        convert(
+            &[("OEBPS/ch1.xhtml", EPUB3_CHAPTER)],
+            "OEBPS/ch1.xhtml",
+            FootnoteMode::End
+        ),
+        "First paragraph.\n\nSecond paragraph.\n\nFootnotes.\n\nFootnote 1: The note.\n"
+    );
+}
+
+#[test]
+fn notes_in_another_document_are_resolved() {
+    let documents = [
+        (
+            "text/ch1.html",
+            r#"<body><p>Text.<sup><a href="notes.html#fn1" id="r1">1</a></sup> More.</p></body>"#,
+        ),
+        (
+            "text/notes.html",
+            r#"<body><h2>Notes</h2><p id="fn1"><a href="ch1.html#r1">1.</a> A note in the back.</p></body>"#,
+        ),
+    ];
+    assert_eq!(
+        convert(&documents, "text/ch1.html", FootnoteMode::Inline),
+        "Text. More.\n\nFootnote: A note in the back.\n"
+    );
+    // The note is read where it is referenced, so it is removed from the notes file
+    assert_eq!(
+        convert(&documents, "text/notes.html", FootnoteMode::Inline),
+        "Notes\n"
+    );
+}
+
+#[test]
+fn calibre_filepos_asterisk_notes_are_resolved() {
+    let documents = [
+        (
+            "index_split_003.html",
+            r#"<body><p>The foundation of the Hughes fortune had been laid.<span><a id="filepos96901" href="index_split_045.html#filepos2319695"><sup class="calibre11">*</sup></a></span></p></body>"#,
+        ),
+        (
+            "index_split_045.html",
+            r#"<body><p id="filepos2319695"><a href="index_split_003.html#filepos96901">*</a> The drill bit.</p></body>"#,
+        ),
+    ];
+    assert_eq!(
+        convert(&documents, "index_split_003.html", FootnoteMode::End),
+        "The foundation of the Hughes fortune had been laid.\n\nFootnotes.\n\nFootnote 1: The drill bit.\n"
+    );
+    assert_eq!(
+        convert(&documents, "index_split_045.html", FootnoteMode::End),
+        ""
+    );
+}
+
+#[test]
+fn unlinked_markers_are_removed() {
+    let html = "<body><p>A claim.<sup>*</sup> Another<sup>†</sup>.</p><p><sup>TM</sup></p></body>";
+    assert_eq!(
+        convert(&[("a.html", html)], "a.html", FootnoteMode::Drop),
+        "A claim. Another.\n\nTM\n"
+    );
+}
+
+#[test]
+fn ordinary_links_are_left_alone() {
+    let html = r##"<body><p>See <a href="#ch2">chapter 2</a> and <a href="ch3.html#top">3</a>.</p><p id="ch2">Two</p></body>"##;
+    assert_eq!(
+        convert(&[("a.html", html)], "a.html", FootnoteMode::Inline),
+        "See chapter 2 and 3.\n\nTwo\n"
+    );
+}
+
+#[test]
+fn links_to_text_that_is_not_a_note_are_left_alone() {
+    let documents = [
+        (
+            "index_split_003.html",
+            r#"<body><p>See table<sup><a href="index_split_004.html#filepos2001">2</a></sup>.</p></body>"#,
+        ),
+        (
+            "index_split_004.html",
+            r#"<body><p id="filepos2001">Table 2 shows the results.</p></body>"#,
+        ),
+    ];
+    assert_eq!(
+        convert(&documents, "index_split_003.html", FootnoteMode::Drop),
+        "See table2.\n"
+    );
+    assert_eq!(
+        convert(&documents, "index_split_004.html", FootnoteMode::Drop),
+        "Table 2 shows the results.\n"
+    );
+}
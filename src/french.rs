@@ -0,0 +1,265 @@
+use crate::abbreviations::Context;
+use crate::language::{Currency, Pack, Unit, currency, feminine, masculine};
+
+const ONES: [&str; 17] = [
+    "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf", "dix", "onze",
+    "douze", "treize", "quatorze", "quinze", "seize",
+];
+
+const TENS: [&str; 9] = [
+    "",
+    "",
+    "vingt",
+    "trente",
+    "quarante",
+    "cinquante",
+    "soixante",
+    "",
+    "quatre-vingt",
+];
+
+/// Returns 0 to 99 in words.  Seventy and ninety count on from sixty and eighty, eg 71 ->
+/// "soixante et onze", and eighty takes an "s" when nothing follows it.
+fn below_hundred(number: usize, last: bool) -> String {
+    match number {
+        0..=16 => ONES[number].to_string(),
+        17..=19 => format!("dix-{}", ONES[number - 10]),
+        _ => {
+            let (tens, units) = match number / 10 {
+                7 => (6, number - 60),
+                9 => (8, number - 80),
+                tens => (tens, number % 10),
+            };
+            match units {
+                0 if tens == 8 && last => "quatre-vingts".to_string(),
+                0 => TENS[tens].to_string(),
+                1 | 11 if tens != 8 => format!("{} et {}", TENS[tens], ONES[units]),
+                _ => format!("{}-{}", TENS[tens], below_hundred(units, last)),
+            }
+        }
+    }
+}
+
+/// Returns 1 to 999 in words, eg 200 -> "deux cents", 201 -> "deux cent un"
+fn below_thousand(number: u64, last: bool) -> String {
+    let hundreds = usize::try_from(number / 100).unwrap_or_default();
+    let rest = usize::try_from(number % 100).unwrap_or_default();
+    let mut words = match hundreds {
+        0 => String::new(),
+        1 => "cent".to_string(),
+        _ if rest == 0 && last => format!("{} cents", ONES[hundreds]),
+        _ => format!("{} cent", ONES[hundreds]),
+    };
+    if rest > 0 {
+        if !words.is_empty() {
+            words.push(' ');
+        }
+        words.push_str(&below_hundred(rest, last));
+    }
+    words
+}
+
+/// Returns a whole number in words, eg 80 -> "quatre-vingts", 1999 -> "mille neuf cent
+/// quatre-vingt-dix-neuf"
+pub fn cardinal(number: u64) -> String {
+    if number == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut words: Vec<String> = Vec::new();
+    let scales = [(1_000_000_000, "milliard"), (1_000_000, "million")];
+    for (scale, name) in scales {
+        let count = number / scale % 1000;
+        match count {
+            0 => {}
+            1 => words.push(format!("un {name}")),
+            // Milliard and million are nouns, so "deux cents millions" keeps its "s"
+            _ => words.push(format!("{} {name}s", below_thousand(count, true))),
+        }
+    }
+    match number / 1000 % 1000 {
+        0 => {}
+        1 => words.push("mille".to_string()),
+        thousands => words.push(format!("{} mille", below_thousand(thousands, false))),
+    }
+    if !number.is_multiple_of(1000) {
+        words.push(below_thousand(number % 1000, true));
+    }
+    words.join(" ")
+}
+
+/// Returns a count in front of a noun, which agrees with it, eg "une heure", "vingt et une
+/// heures"
+fn count(number: u64, feminine: bool) -> String {
+    let words = cardinal(number);
+    match words.strip_suffix("un") {
+        Some(start) if feminine && (start.is_empty() || start.ends_with([' ', '-'])) => {
+            format!("{start}une")
+        }
+        _ => words,
+    }
+}
+
+/// Endings of feminine nouns, eg "nation", "semaine", "année"
+const FEMININE_ENDINGS: [&str; 15] = [
+    "tion", "sion", "té", "ette", "ence", "ance", "ure", "ée", "euse", "rice", "ie", "ude", "ière",
+    "elle", "aine",
+];
+
+/// Common feminine nouns without one of the endings
+const FEMININE_NOUNS: [&str; 10] = [
+    "femme", "fille", "personne", "heure", "fois", "page", "chose", "nuit", "main", "ville",
+];
+
+/// Returns Some(true) if a word is a feminine noun, eg "femmes", or None so the number is read
+/// as it is, which is also its masculine form
+fn feminine_noun(word: &str) -> Option<bool> {
+    let word = word.to_lowercase();
+    let singular = word.strip_suffix(['s', 'x']).unwrap_or(&word);
+    (FEMININE_NOUNS.contains(&word.as_str())
+        || FEMININE_NOUNS.contains(&singular)
+        || FEMININE_ENDINGS.iter().any(|e| singular.ends_with(e)))
+    .then_some(true)
+}
+
+/// Returns an ordinal in words, eg "1er" -> "premier", "1re" -> "première", "21e" ->
+/// "vingt et unième", "5e" -> "cinquième"
+fn ordinal(number: u64, suffix: &str) -> String {
+    if number == 1 {
+        return if matches!(suffix, "re" | "ère") {
+            "première".to_string()
+        } else {
+            "premier".to_string()
+        };
+    }
+    let words = cardinal(number);
+    let words = words
+        .strip_suffix("vingts")
+        .map_or_else(|| words.clone(), |start| format!("{start}vingt"));
+    let words = words
+        .strip_suffix("cents")
+        .map_or_else(|| words.clone(), |start| format!("{start}cent"));
+    let stem = if let Some(start) = words.strip_suffix("cinq") {
+        format!("{start}cinqu")
+    } else if let Some(start) = words.strip_suffix("neuf") {
+        format!("{start}neuv")
+    } else if let Some(start) = words.strip_suffix('e') {
+        start.to_string()
+    } else {
+        words
+    };
+    format!("{stem}ième")
+}
+
+const CURRENCIES: [Currency; 5] = [
+    currency(&["€", "EUR"], ["euro", "euros"], ["centime", "centimes"]),
+    currency(&["$", "USD"], ["dollar", "dollars"], ["cent", "cents"]),
+    Currency {
+        feminine: true,
+        ..currency(&["£", "GBP"], ["livre", "livres"], ["penny", "pence"])
+    },
+    currency(&["CHF", "FS"], ["franc", "francs"], ["centime", "centimes"]),
+    currency(&["¥", "JPY"], ["yen", "yens"], ["sen", "sens"]),
+];
+
+const UNITS: [Unit; 25] = [
+    masculine(&["km/h"], "kilomètre par heure", "kilomètres par heure"),
+    masculine(&["m/s"], "mètre par seconde", "mètres par seconde"),
+    masculine(&["mm"], "millimètre", "millimètres"),
+    masculine(&["cm"], "centimètre", "centimètres"),
+    masculine(&["m"], "mètre", "mètres"),
+    masculine(&["km"], "kilomètre", "kilomètres"),
+    masculine(&["m²"], "mètre carré", "mètres carrés"),
+    masculine(&["km²"], "kilomètre carré", "kilomètres carrés"),
+    masculine(&["ha"], "hectare", "hectares"),
+    masculine(&["m³"], "mètre cube", "mètres cubes"),
+    masculine(&["ml", "mL"], "millilitre", "millilitres"),
+    masculine(&["cl", "cL"], "centilitre", "centilitres"),
+    masculine(&["l", "L"], "litre", "litres"),
+    masculine(&["mg"], "milligramme", "milligrammes"),
+    masculine(&["g"], "gramme", "grammes"),
+    masculine(&["kg"], "kilogramme", "kilogrammes"),
+    feminine(&["t"], "tonne", "tonnes"),
+    masculine(&["°C"], "degré Celsius", "degrés Celsius"),
+    masculine(&["°"], "degré", "degrés"),
+    feminine(&["min"], "minute", "minutes"),
+    feminine(&["sec"], "seconde", "secondes"),
+    masculine(&["Mo"], "mégaoctet", "mégaoctets"),
+    masculine(&["Go"], "gigaoctet", "gigaoctets"),
+    masculine(&["kWh"], "kilowattheure", "kilowattheures"),
+    masculine(&["kW"], "kilowatt", "kilowatts"),
+];
+
+const ABBREVIATIONS: [(&str, &str, Context); 22] = [
+    ("M.", "Monsieur", Context::BeforeName),
+    ("MM.", "Messieurs", Context::BeforeName),
+    ("Mme", "Madame", Context::Leading),
+    ("Mmes", "Mesdames", Context::Leading),
+    ("Mlle", "Mademoiselle", Context::Leading),
+    ("Mlles", "Mesdemoiselles", Context::Leading),
+    ("Dr", "Docteur", Context::BeforeName),
+    ("Pr", "Professeur", Context::BeforeName),
+    ("St", "Saint", Context::BeforeName),
+    ("Ste", "Sainte", Context::BeforeName),
+    ("etc.", "et cetera", Context::Always),
+    ("p. ex.", "par exemple", Context::Leading),
+    ("c.-à-d.", "c'est-à-dire", Context::Leading),
+    ("cf.", "confer", Context::Leading),
+    ("env.", "environ", Context::Leading),
+    ("n°", "numéro", Context::BeforeNumber),
+    ("p.", "page", Context::BeforeNumber),
+    ("vol.", "volume", Context::BeforeNumber),
+    ("chap.", "chapitre", Context::BeforeNumber),
+    ("av. J.-C.", "avant Jésus-Christ", Context::Always),
+    ("apr. J.-C.", "après Jésus-Christ", Context::Always),
+    ("bd", "boulevard", Context::BeforeName),
+];
+
+/// French number words, currencies, units and abbreviations
+pub const PACK: Pack = Pack {
+    cardinal,
+    count,
+    feminine_noun,
+    year: cardinal,
+    year_context: &[],
+    ordinal: Some((r"ᵉʳ|ʳᵉ|ᵉ|ère|ème|er|re|e", ordinal)),
+    dotted_ordinal: None,
+    decimal_separator: ',',
+    thousands_separators: &[' ', '\u{a0}', '\u{202f}', '.'],
+    decimal_point: "virgule",
+    digits_after_the_point: false,
+    percent: "pour cent",
+    and: "et",
+    currencies: &CURRENCIES,
+    units: &UNITS,
+    abbreviations: &ABBREVIATIONS,
+};
+
+#[test]
+fn seventy_eighty_and_ninety_count_in_twenties() {
+    assert_eq!(cardinal(21), "vingt et un");
+    assert_eq!(cardinal(71), "soixante et onze");
+    assert_eq!(cardinal(77), "soixante-dix-sept");
+    assert_eq!(cardinal(80), "quatre-vingts");
+    assert_eq!(cardinal(81), "quatre-vingt-un");
+    assert_eq!(cardinal(99), "quatre-vingt-dix-neuf");
+}
+
+#[test]
+fn hundreds_and_thousands_follow_the_plural_rules() {
+    assert_eq!(cardinal(200), "deux cents");
+    assert_eq!(cardinal(201), "deux cent un");
+    assert_eq!(cardinal(1999), "mille neuf cent quatre-vingt-dix-neuf");
+    assert_eq!(cardinal(80_000), "quatre-vingt mille");
+    assert_eq!(cardinal(200_000_000), "deux cents millions");
+}
+
+#[test]
+fn counts_and_ordinals_agree_with_the_noun() {
+    assert_eq!(count(21, true), "vingt et une");
+    assert_eq!(count(1, true), "une");
+    assert_eq!(ordinal(1, "re"), "première");
+    assert_eq!(ordinal(5, "e"), "cinquième");
+    assert_eq!(ordinal(21, "e"), "vingt et unième");
+    assert_eq!(ordinal(80, "e"), "quatre-vingtième");
+}
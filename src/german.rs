@@ -0,0 +1,382 @@
+use crate::abbreviations::Context;
+use crate::language::{Currency, Pack, Unit, currency, feminine, masculine};
+
+const ONES: [&str; 20] = [
+    "null",
+    "eins",
+    "zwei",
+    "drei",
+    "vier",
+    "fünf",
+    "sechs",
+    "sieben",
+    "acht",
+    "neun",
+    "zehn",
+    "elf",
+    "zwölf",
+    "dreizehn",
+    "vierzehn",
+    "fünfzehn",
+    "sechzehn",
+    "siebzehn",
+    "achtzehn",
+    "neunzehn",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig", "neunzig",
+];
+
+/// Returns 1 to 999 in words, written as one word.  A one at the end is "eins" when the number
+/// stands alone, but "ein" when something follows, eg "einhunderteintausend".
+fn below_thousand(number: u64, standalone: bool) -> String {
+    let digit = |n: u64| {
+        if n == 1 {
+            "ein"
+        } else {
+            ONES[usize::try_from(n).unwrap_or_default()]
+        }
+    };
+    let mut words = String::new();
+    if number >= 100 {
+        words.push_str(digit(number / 100));
+        words.push_str("hundert");
+    }
+    let rest = number % 100;
+    match rest {
+        0 => {}
+        1 if !standalone => words.push_str("ein"),
+        1..=19 => words.push_str(ONES[usize::try_from(rest).unwrap_or_default()]),
+        _ => {
+            if !rest.is_multiple_of(10) {
+                words.push_str(digit(rest % 10));
+                words.push_str("und");
+            }
+            words.push_str(TENS[usize::try_from(rest / 10).unwrap_or_default()]);
+        }
+    }
+    words
+}
+
+/// Returns a whole number in words, eg 1234 -> "eintausendzweihundertvierunddreißig",
+/// 2000000 -> "zwei Millionen"
+pub fn cardinal(number: u64) -> String {
+    if number == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut words: Vec<String> = Vec::new();
+    let scales = [
+        (1_000_000_000_000, "Billion", "Billionen"),
+        (1_000_000_000, "Milliarde", "Milliarden"),
+        (1_000_000, "Million", "Millionen"),
+    ];
+    for (scale, singular, plural) in scales {
+        let count = number / scale % 1000;
+        match count {
+            0 => {}
+            1 => words.push(format!("eine {singular}")),
+            _ => words.push(format!("{} {plural}", below_thousand(count, false))),
+        }
+    }
+
+    let mut rest = String::new();
+    let thousands = number / 1000 % 1000;
+    if thousands > 0 {
+        rest.push_str(&below_thousand(thousands, false));
+        rest.push_str("tausend");
+    }
+    if !number.is_multiple_of(1000) {
+        rest.push_str(&below_thousand(number % 1000, true));
+    }
+    if !rest.is_empty() {
+        words.push(rest);
+    }
+    words.join(" ")
+}
+
+/// Returns a count in front of a noun, which agrees with it, eg "ein Euro", "eine Stunde",
+/// "hundertein Euro"
+fn count(number: u64, feminine: bool) -> String {
+    let words = cardinal(number);
+    match words.strip_suffix("eins") {
+        Some(start) if feminine => format!("{start}eine"),
+        Some(start) => format!("{start}ein"),
+        None => words,
+    }
+}
+
+/// Endings of feminine nouns, eg "Stunde", "Zeitung", "Nation"
+const FEMININE_ENDINGS: [&str; 12] = [
+    "e", "ung", "heit", "keit", "schaft", "ion", "tät", "ik", "ei", "ur", "enz", "anz",
+];
+
+/// Common feminine nouns without one of the endings
+const FEMININE_NOUNS: [&str; 16] = [
+    "frau", "hand", "nacht", "stadt", "welt", "zeit", "art", "tür", "uhr", "zahl", "kuh", "maus",
+    "wand", "bank", "kraft", "milch",
+];
+
+/// Returns whether a capitalised word is a feminine noun, eg "Frau", or None for any other word
+fn feminine_noun(word: &str) -> Option<bool> {
+    if !word.starts_with(char::is_uppercase) {
+        return None;
+    }
+    let word = word.to_lowercase();
+    Some(
+        FEMININE_NOUNS.contains(&word.as_str())
+            || FEMININE_ENDINGS.iter().any(|ending| word.ends_with(ending)),
+    )
+}
+
+/// Months, after which a number with a full stop is a date, eg "3. Mai"
+const MONTHS: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+/// Words before an ordinal that take the "-e" ending, eg "der zweite Weltkrieg"
+const ORDINAL_E: [&str; 3] = ["der", "die", "das"];
+
+/// Words before an ordinal that take the "-en" ending, eg "am dritten Mai"
+const ORDINAL_EN: [&str; 11] = [
+    "am", "im", "vom", "zum", "zur", "beim", "den", "dem", "des", "ab", "bis",
+];
+
+/// Returns the stem of an ordinal, eg 3 -> "dritt", 21 -> "einundzwanzigst"
+fn ordinal_stem(number: u64) -> String {
+    let rest = number % 100;
+    if rest == 0 || rest >= 20 {
+        return format!("{}st", cardinal(number));
+    }
+    let start = if number >= 100 {
+        cardinal(number - rest)
+    } else {
+        String::new()
+    };
+    let end = match rest {
+        1 => "erst",
+        3 => "dritt",
+        7 => "siebt",
+        8 => "acht",
+        _ => {
+            return format!(
+                "{start}{}t",
+                ONES[usize::try_from(rest).unwrap_or_default()]
+            );
+        }
+    };
+    format!("{start}{end}")
+}
+
+/// Reads a number with a full stop as an ordinal when the word before is an article or the word
+/// after a month, eg "am 3. Mai" -> "am dritten Mai", "der 2. Weltkrieg" -> "der zweite Weltkrieg".
+/// Anything else, eg "Er war 3. Dann", is the end of a sentence.
+fn dotted_ordinal(number: u64, before: &str, after: &str) -> Option<String> {
+    let before = before.to_lowercase();
+    let ending = if ORDINAL_E.contains(&before.as_str()) {
+        "e"
+    } else if ORDINAL_EN.contains(&before.as_str()) || MONTHS.contains(&after) {
+        "en"
+    } else {
+        return None;
+    };
+    Some(format!("{}{ending}", ordinal_stem(number)))
+}
+
+/// Returns a year the way it is spoken, eg 1904 -> "neunzehnhundertvier", 2024 ->
+/// "zweitausendvierundzwanzig"
+pub fn year(number: u64) -> String {
+    if !(1100..2000).contains(&number) {
+        return cardinal(number);
+    }
+    let mut words = below_thousand(number / 100, false);
+    words.push_str("hundert");
+    if !number.is_multiple_of(100) {
+        words.push_str(&below_thousand(number % 100, true));
+    }
+    words
+}
+
+const CURRENCIES: [Currency; 5] = [
+    currency(&["€", "EUR"], ["Euro", "Euro"], ["Cent", "Cent"]),
+    currency(&["$", "USD"], ["Dollar", "Dollar"], ["Cent", "Cent"]),
+    currency(&["£", "GBP"], ["Pfund", "Pfund"], ["Penny", "Pence"]),
+    currency(
+        &["CHF", "Fr."],
+        ["Franken", "Franken"],
+        ["Rappen", "Rappen"],
+    ),
+    currency(&["¥", "JPY"], ["Yen", "Yen"], ["Sen", "Sen"]),
+];
+
+const UNITS: [Unit; 25] = [
+    masculine(&["km/h"], "Kilometer pro Stunde", "Kilometer pro Stunde"),
+    masculine(&["m/s"], "Meter pro Sekunde", "Meter pro Sekunde"),
+    masculine(&["mm"], "Millimeter", "Millimeter"),
+    masculine(&["cm"], "Zentimeter", "Zentimeter"),
+    masculine(&["m"], "Meter", "Meter"),
+    masculine(&["km"], "Kilometer", "Kilometer"),
+    masculine(&["m²", "qm"], "Quadratmeter", "Quadratmeter"),
+    masculine(&["km²"], "Quadratkilometer", "Quadratkilometer"),
+    masculine(&["ha"], "Hektar", "Hektar"),
+    masculine(&["m³"], "Kubikmeter", "Kubikmeter"),
+    masculine(&["ml"], "Milliliter", "Milliliter"),
+    masculine(&["l"], "Liter", "Liter"),
+    masculine(&["mg"], "Milligramm", "Milligramm"),
+    masculine(&["g"], "Gramm", "Gramm"),
+    masculine(&["kg"], "Kilogramm", "Kilogramm"),
+    feminine(&["t"], "Tonne", "Tonnen"),
+    masculine(&["°C"], "Grad Celsius", "Grad Celsius"),
+    masculine(&["°"], "Grad", "Grad"),
+    feminine(&["Std."], "Stunde", "Stunden"),
+    feminine(&["Min.", "min"], "Minute", "Minuten"),
+    feminine(&["Sek."], "Sekunde", "Sekunden"),
+    masculine(&["MB"], "Megabyte", "Megabyte"),
+    masculine(&["GB"], "Gigabyte", "Gigabyte"),
+    feminine(&["kWh"], "Kilowattstunde", "Kilowattstunden"),
+    masculine(&["kW"], "Kilowatt", "Kilowatt"),
+];
+
+const ABBREVIATIONS: [(&str, &str, Context); 34] = [
+    ("z. B.", "zum Beispiel", Context::Leading),
+    ("z.B.", "zum Beispiel", Context::Leading),
+    ("d. h.", "das heißt", Context::Leading),
+    ("d.h.", "das heißt", Context::Leading),
+    ("u. a.", "unter anderem", Context::Always),
+    ("u.a.", "unter anderem", Context::Always),
+    ("usw.", "und so weiter", Context::Always),
+    ("etc.", "et cetera", Context::Always),
+    ("bzw.", "beziehungsweise", Context::Leading),
+    ("ca.", "circa", Context::Leading),
+    ("vgl.", "vergleiche", Context::Leading),
+    ("evtl.", "eventuell", Context::Leading),
+    ("ggf.", "gegebenenfalls", Context::Leading),
+    ("inkl.", "inklusive", Context::Leading),
+    ("zzgl.", "zuzüglich", Context::Leading),
+    ("Mio.", "Millionen", Context::Leading),
+    ("Mrd.", "Milliarden", Context::Leading),
+    ("Nr.", "Nummer", Context::BeforeNumber),
+    ("S.", "Seite", Context::BeforeNumber),
+    ("Bd.", "Band", Context::BeforeNumber),
+    ("Abb.", "Abbildung", Context::BeforeNumber),
+    ("Kap.", "Kapitel", Context::BeforeNumber),
+    ("Jh.", "Jahrhundert", Context::Always),
+    ("v. Chr.", "vor Christus", Context::Always),
+    ("n. Chr.", "nach Christus", Context::Always),
+    ("Hr.", "Herr", Context::Leading),
+    ("Hrn.", "Herrn", Context::Leading),
+    ("Fr.", "Frau", Context::BeforeName),
+    ("Dr.", "Doktor", Context::Leading),
+    ("Prof.", "Professor", Context::Leading),
+    ("St.", "Sankt", Context::BeforeName),
+    ("Str.", "Straße", Context::Always),
+    ("Tel.", "Telefon", Context::Leading),
+    ("bzgl.", "bezüglich", Context::Leading),
+];
+
+/// German number words, currencies, units and abbreviations
+pub const PACK: Pack = Pack {
+    cardinal,
+    count,
+    feminine_noun,
+    year,
+    year_context: &[
+        "Jahr",
+        "Jahre",
+        "Jahres",
+        "seit",
+        "bis",
+        "von",
+        "ab",
+        "um",
+        "anno",
+        "Anno",
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+        "Frühjahr",
+        "Sommer",
+        "Herbst",
+        "Winter",
+    ],
+    ordinal: None,
+    dotted_ordinal: Some(dotted_ordinal),
+    decimal_separator: ',',
+    thousands_separators: &['.', '\u{a0}', '\u{202f}'],
+    decimal_point: "Komma",
+    digits_after_the_point: true,
+    percent: "Prozent",
+    and: "und",
+    currencies: &CURRENCIES,
+    units: &UNITS,
+    abbreviations: &ABBREVIATIONS,
+};
+
+#[test]
+fn cardinals_are_single_words_below_a_million() {
+    assert_eq!(cardinal(1), "eins");
+    assert_eq!(cardinal(21), "einundzwanzig");
+    assert_eq!(cardinal(101), "einhunderteins");
+    assert_eq!(cardinal(1234), "eintausendzweihundertvierunddreißig");
+    assert_eq!(cardinal(101_000), "einhunderteintausend");
+    assert_eq!(cardinal(2_300_001), "zwei Millionen dreihunderttausendeins");
+    assert_eq!(cardinal(1_000_000), "eine Million");
+}
+
+#[test]
+fn counts_agree_with_the_noun() {
+    assert_eq!(count(1, false), "ein");
+    assert_eq!(count(1, true), "eine");
+    assert_eq!(count(21, true), "einundzwanzig");
+    assert_eq!(count(101, false), "einhundertein");
+}
+
+#[test]
+fn years_are_read_in_hundreds() {
+    assert_eq!(year(1904), "neunzehnhundertvier");
+    assert_eq!(year(1066), "eintausendsechsundsechzig");
+    assert_eq!(year(2024), "zweitausendvierundzwanzig");
+}
+
+#[test]
+fn ordinals_with_a_full_stop_follow_the_article() {
+    assert_eq!(dotted_ordinal(3, "am", "Mai"), Some("dritten".to_string()));
+    assert_eq!(
+        dotted_ordinal(2, "der", "Weltkrieg"),
+        Some("zweite".to_string())
+    );
+    assert_eq!(
+        dotted_ordinal(1, "Berlin,", "Januar"),
+        Some("ersten".to_string())
+    );
+    assert_eq!(
+        dotted_ordinal(21, "die", "Etage"),
+        Some("einundzwanzigste".to_string())
+    );
+    assert_eq!(
+        dotted_ordinal(107, "das", "Mal"),
+        Some("einhundertsiebte".to_string())
+    );
+    assert_eq!(dotted_ordinal(3, "war", "Dann"), None);
+}
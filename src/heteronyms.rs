@@ -0,0 +1,408 @@
+use std::fs;
+
+use crate::lexicon::Pronunciation;
+
+/// Classes of words the rules can name, eg `after:@determiner`
+const WORD_CLASSES: [(&str, &[&str]); 4] = [
+    (
+        "@determiner",
+        &[
+            "a", "an", "the", "this", "that", "these", "those", "my", "your", "his", "her", "its",
+            "our", "their", "every", "each", "no", "some", "any", "one",
+        ],
+    ),
+    (
+        "@pronoun",
+        &["i", "you", "he", "she", "it", "we", "they", "who"],
+    ),
+    (
+        "@modal",
+        &[
+            "to",
+            "will",
+            "would",
+            "can",
+            "could",
+            "shall",
+            "should",
+            "may",
+            "might",
+            "must",
+            "do",
+            "does",
+            "did",
+            "don't",
+            "doesn't",
+            "didn't",
+            "won't",
+            "can't",
+            "cannot",
+            "couldn't",
+            "wouldn't",
+            "shouldn't",
+            "let's",
+            "please",
+        ],
+    ),
+    (
+        "@perfect",
+        &[
+            "have", "has", "had", "having", "i've", "you've", "we've", "they've", "i'd", "you'd",
+            "he'd", "she'd", "we'd", "they'd", "was", "were", "been", "be", "being", "is", "are",
+            "am", "get", "gets", "got",
+        ],
+    ),
+];
+
+/// Heteronyms, a pronunciation for each reading, and the neighbouring words that choose it.  The
+/// first reading whose rule matches is used.  If none does the word is left as it is, since
+/// "they read" or "a bow" could be either.
+const HETERONYMS: [(&str, &str, &str); 23] = [
+    (
+        "read",
+        "red /ɹɛd/",
+        "after:@perfect,he,she,it,who,well,widely",
+    ),
+    ("read", "reed /ɹiːd/", "after:@modal"),
+    (
+        "lead",
+        "led /lɛd/",
+        "after:of,with,red,white,molten before:pipe,pipes,poisoning,paint,pencil,pencils,weight,weights,shot,soldier,soldiers,balloon,sheet,sheets,roof,mine,mines,ore,crystal",
+    ),
+    ("lead", "leed /liːd/", "after:@modal,i,you,we,they"),
+    (
+        "live",
+        "lyve /laɪv/",
+        "before:music,show,shows,broadcast,performance,wire,wires,bait,coverage,audience,concert,recording,stream,television,animal,animals,shell,shells,ammunition,coal,coals,oak",
+    ),
+    ("live", "liv /lɪv/", "after:@modal,i,you,we,they,who"),
+    (
+        "lives",
+        "livz /lɪvz/",
+        "after:he,she,it,who,that,which,one,everyone,nobody,someone",
+    ),
+    (
+        "lives",
+        "lyves /laɪvz/",
+        "after:@determiner,many,nine,two,whose",
+    ),
+    ("wind", "wind /wɪnd/", "after:@determiner"),
+    (
+        "wind",
+        "wynd /waɪnd/",
+        "after:@modal,@pronoun before:up,down,around,round,back,it,them,the,his,her,its,their",
+    ),
+    (
+        "winds",
+        "wynds /waɪndz/",
+        "after:he,she,it,who before:up,down,around,round,back,it,them",
+    ),
+    ("winds", "winds /wɪndz/", "after:@determiner"),
+    ("tear", "teer /tɪɹ/", "after:@determiner"),
+    (
+        "tear",
+        "tair /tɛɹ/",
+        "after:@modal,@pronoun before:up,off,down,apart,open,out,at,into,through,it,them,away",
+    ),
+    (
+        "tears",
+        "tairs /tɛɹz/",
+        "after:he,she,it,who before:up,off,down,apart,open,it,them",
+    ),
+    (
+        "tears",
+        "teers /tɪɹz/",
+        "after:@determiner,in,into,to,with,without,of,bring,brought,burst",
+    ),
+    (
+        "bow",
+        "boh /boʊ/",
+        "before:and,tie,ties,tied,string,strings",
+    ),
+    ("bow", "bough /baʊ/", "after:@modal before:down,low,deeply"),
+    ("bows", "bohz /boʊz/", "before:and"),
+    ("wound", "woond /wuːnd/", "after:@determiner"),
+    (
+        "wound",
+        "wownd /waʊnd/",
+        "after:@pronoun,had,was,were,been,is,having before:up,round,around,down,through,back,his,her,its,their,the,it,them,itself",
+    ),
+    ("dove", "duv /dʌv/", "after:@determiner,white"),
+    (
+        "dove",
+        "dohv /doʊv/",
+        "after:@pronoun before:into,in,under,off,down,through",
+    ),
+];
+
+/// A reading of a heteronym and the neighbouring words that choose it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reading {
+    pronunciation: Pronunciation,
+    /// The reading is chosen if the word before is one of these
+    after: Vec<String>,
+    /// The reading is chosen if the word after is one of these
+    before: Vec<String>,
+}
+
+impl Reading {
+    /// Reads a reading from its pronunciation and rule, eg "after:@modal,he before:up".  Word
+    /// classes in the rule are expanded to their words.
+    fn parse(pronunciation: &str, rule: &str) -> Self {
+        let mut reading = Self {
+            pronunciation: Pronunciation::parse(pronunciation),
+            after: Vec::new(),
+            before: Vec::new(),
+        };
+        for condition in rule.split_whitespace() {
+            let (words, list) = if let Some(list) = condition.strip_prefix("after:") {
+                (&mut reading.after, list)
+            } else if let Some(list) = condition.strip_prefix("before:") {
+                (&mut reading.before, list)
+            } else {
+                println!(
+                    "Heteronyms File Syntax error - Ignoring rule, no 'after:' or 'before:' found: {condition}"
+                );
+                continue;
+            };
+            for word in list.split(',').filter(|word| !word.is_empty()) {
+                if word.starts_with('@') {
+                    match WORD_CLASSES.iter().find(|(class, _)| *class == word) {
+                        Some((_, class)) => words.extend(class.iter().map(ToString::to_string)),
+                        None => {
+                            println!("Heteronyms File Syntax error - Unknown word class: {word}");
+                        }
+                    }
+                } else {
+                    words.push(word.to_lowercase());
+                }
+            }
+        }
+        reading
+    }
+
+    /// Returns true if the reading is chosen for a word between these two
+    fn applies(&self, previous: Option<&str>, next: Option<&str>) -> bool {
+        let listed = |words: &[String], word: Option<&str>| {
+            word.is_some_and(|word| words.iter().any(|listed| listed == word))
+        };
+        (self.after.is_empty() && self.before.is_empty())
+            || listed(&self.after, previous)
+            || listed(&self.before, next)
+    }
+}
+
+/// Adverbs that are skipped looking for the word before, eg "have often read" is read as "have
+/// read"
+const ADVERBS: [&str; 15] = [
+    "so", "often", "never", "already", "just", "ever", "also", "once", "not", "only", "always",
+    "recently", "seldom", "rarely", "all",
+];
+
+/// Returns the word right before the text's end, in lower case, unless punctuation comes between.
+/// Adverbs are skipped.
+fn previous_word(text: &str) -> Option<String> {
+    let text = text.trim_end();
+    let start = text
+        .rfind(|c: char| !(c.is_alphabetic() || c == '\''))
+        .map_or(0, |index| index + 1);
+    let word = text[start..].trim_matches('\'').to_lowercase();
+    if ADVERBS.contains(&word.as_str()) {
+        return previous_word(&text[..start]).or(Some(word));
+    }
+    (!word.is_empty()).then_some(word)
+}
+
+/// Returns the word right at the text's start, in lower case, unless punctuation comes between
+fn next_word(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !(c.is_alphabetic() || c == '\''))
+        .unwrap_or(text.len());
+    let word = text[..end].trim_matches('\'');
+    (!word.is_empty()).then(|| word.to_lowercase())
+}
+
+/// Reads a line of a heteronyms file, `word==pronunciation==rule`
+fn process_line(text: &str) -> Option<(String, Reading)> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+        return None;
+    }
+    let mut fields = text.split("==");
+    let (Some(word), Some(pronunciation)) = (fields.next(), fields.next()) else {
+        println!("Heteronyms File Syntax error - Ignoring line, no '==' found: {text}");
+        return None;
+    };
+    let rule = fields.next().unwrap_or_default();
+    Some((
+        word.trim().to_lowercase(),
+        Reading::parse(pronunciation, rule),
+    ))
+}
+
+/// Words spelled the same but said differently, eg "read" and "lead", with rules that choose
+/// a reading from the words either side
+#[derive(Debug, Default)]
+pub struct Heteronyms {
+    words: Vec<(String, Vec<Reading>)>,
+}
+
+impl Heteronyms {
+    /// Returns the built in English heteronyms
+    pub fn built_in() -> Self {
+        let mut heteronyms = Self::default();
+        for (word, pronunciation, rule) in HETERONYMS {
+            heteronyms.add(word, Reading::parse(pronunciation, rule), false);
+        }
+        heteronyms
+    }
+
+    /// Adds a reading of a word, before the ones it already has or after them
+    fn add(&mut self, word: &str, reading: Reading, first: bool) {
+        match self.words.iter_mut().find(|(existing, _)| existing == word) {
+            Some((_, readings)) if first => readings.insert(0, reading),
+            Some((_, readings)) => readings.push(reading),
+            None => self.words.push((word.to_string(), vec![reading])),
+        }
+    }
+
+    /// Adds readings, which are tried before the built in readings of the same word
+    ///
+    /// # Arguments
+    /// * `text` - The contents of a heteronyms file
+    pub fn extend_from_text(&mut self, text: &str) {
+        for (word, reading) in text.lines().rev().filter_map(process_line) {
+            self.add(&word, reading, true);
+        }
+    }
+
+    /// Loads the built in heteronyms, extended by a user file if it exists
+    ///
+    /// # Arguments
+    /// * `filename` - The user's heteronyms file
+    pub fn load(filename: &str) -> Self {
+        let mut heteronyms = Self::built_in();
+        if let Ok(file_text) = fs::read_to_string(filename) {
+            println!("Opening user heteronyms: {filename}");
+            heteronyms.extend_from_text(&file_text);
+        }
+        heteronyms
+    }
+
+    /// Returns every heteronym
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.iter().map(|(word, _)| word.as_str())
+    }
+
+    /// Returns the pronunciation of every reading
+    pub fn pronunciations(&self) -> impl Iterator<Item = &Pronunciation> {
+        self.words
+            .iter()
+            .flat_map(|(_, readings)| readings.iter().map(|reading| &reading.pronunciation))
+    }
+
+    /// Chooses the pronunciation of a heteronym from the words around it
+    ///
+    /// # Arguments
+    /// * `word` - The heteronym, in any case
+    /// * `before` - The text before it
+    /// * `after` - The text after it
+    /// # Returns
+    /// The pronunciation of the first reading whose rule matches, or None if it isn't a heteronym
+    pub fn choose(&self, word: &str, before: &str, after: &str) -> Option<&Pronunciation> {
+        let word = word.to_lowercase();
+        let (_, readings) = self.words.iter().find(|(existing, _)| *existing == word)?;
+        let previous = previous_word(before);
+        let next = next_word(after);
+        readings
+            .iter()
+            .find(|reading| reading.applies(previous.as_deref(), next.as_deref()))
+            .map(|reading| &reading.pronunciation)
+    }
+}
+
+#[cfg(test)]
+fn respell(text: &str) -> String {
+    use crate::language::Language;
+    use crate::lexicon::Lexicon;
+    Lexicon::new(Heteronyms::built_in()).respell(text, Language::English)
+}
+
+#[test]
+fn readings_are_chosen_from_the_words_around_them() {
+    assert_eq!(
+        respell("I had read it. Read it to me, please read it."),
+        "I had red it. Read it to me, please reed it."
+    );
+    assert_eq!(
+        respell("We lead, the lead pipe is there."),
+        "We leed, the led pipe is there."
+    );
+    assert_eq!(
+        respell("They live near the live music."),
+        "They liv near the lyve music."
+    );
+    assert_eq!(
+        respell("The wind blew, so wind up the clock."),
+        "The wind blew, so wynd up the clock."
+    );
+    assert_eq!(
+        respell("A tear fell; don't tear it."),
+        "A teer fell; don't tair it."
+    );
+    assert_eq!(
+        respell("She wound the clock over the wound."),
+        "She wownd the clock over the woond."
+    );
+}
+
+#[test]
+fn punctuation_breaks_the_context() {
+    assert_eq!(respell("I had, read"), "I had, read");
+    assert_eq!(respell("I've so often read"), "I've so often red");
+    assert_eq!(
+        respell("to bring tears into her eyes"),
+        "to bring teers into her eyes"
+    );
+    assert_eq!(previous_word("they've "), Some("they've".to_string()));
+    assert_eq!(next_word(" 'up"), Some("up".to_string()));
+}
+
+#[test]
+fn words_are_left_alone_when_no_rule_matches() {
+    for text in [
+        "They read it last night.",
+        "the White Rabbit read out, as follows",
+        "the players were live hedgehogs",
+        "She took a bow.",
+        "Take the lead.",
+    ] {
+        assert_eq!(respell(text), text);
+    }
+}
+
+#[test]
+fn users_can_add_readings_that_win() {
+    let mut heteronyms = Heteronyms::built_in();
+    heteronyms.extend_from_text(
+        "# comment\nbass==bayss /beɪs/==before:guitar,player after:@determiner\nbass==bass\nlead==led==after:pure\n",
+    );
+    let lexicon = crate::lexicon::Lexicon::new(heteronyms);
+    let respell = |text| lexicon.respell(text, crate::language::Language::English);
+    assert_eq!(
+        respell("the bass guitar and a bass caught in pure lead"),
+        "the bayss guitar and a bayss caught in pure led"
+    );
+    assert_eq!(respell("fish for bass"), "fish for bass");
+}
+
+#[test]
+fn ssml_gets_the_chosen_phoneme() {
+    use crate::language::Language;
+    use crate::lexicon::Lexicon;
+    assert_eq!(
+        Lexicon::new(Heteronyms::built_in()).to_ssml("has read", Language::English),
+        "has <phoneme alphabet=\"ipa\" ph=\"ɹɛd\">read</phoneme>"
+    );
+}
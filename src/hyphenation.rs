@@ -0,0 +1,147 @@
+use regex::{Captures, Regex};
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use crate::extract::LEAD_IN_END;
+use crate::language::Language;
+
+/// English words and their inflections in lower case, one per line.  See data/README.md for
+/// where the list comes from.
+static WORDS: LazyLock<HashSet<&'static str>> =
+    LazyLock::new(|| include_str!("../data/english-words.txt").lines().collect());
+
+/// Returns true if the word, or the word without a possessive "'s", is in the word list
+///
+/// # Arguments
+/// * `word` - The word to check, in any case
+pub fn is_word(word: &str) -> bool {
+    let word = word.to_lowercase();
+    WORDS.contains(word.as_str())
+        || word
+            .strip_suffix("'s")
+            .is_some_and(|stem| WORDS.contains(stem))
+}
+
+/// Decides whether two pieces of text are one word that was split.  They are if together they
+/// make a word, unless each is also a word on its own, eg "T"+"he" and "I"+"t" are joined but
+/// "I"+"was" and "A"+"round" are not.
+fn is_split_word(start: &str, end: &str) -> bool {
+    is_word(&format!("{start}{end}")) && !(is_word(start) && is_word(end))
+}
+
+/// Joins the letter of a drop cap, or a small caps lead in, to the rest of its word, eg "T he
+/// day" -> "The day".  The end of each lead in is marked with `LEAD_IN_END`, which is removed.
+///
+/// # Arguments
+/// * `text` - The text, with the markers from `extract_text_from_html`
+/// # Returns
+/// The text with the lead ins joined
+pub fn rejoin_lead_ins(text: &str) -> String {
+    let re = Regex::new(&format!(r"(\p{{L}}*){LEAD_IN_END}(\s*)(\p{{L}}*)")).unwrap();
+    re.replace_all(text, |caps: &Captures| {
+        let (start, space, end) = (&caps[1], &caps[2], &caps[3]);
+        if !space.is_empty() && is_split_word(start, end) {
+            format!("{start}{end}")
+        } else {
+            format!("{start}{space}{end}")
+        }
+    })
+    .to_string()
+}
+
+/// Returns true if two pieces of a word hyphenated across a line break are one word.  In English
+/// they are unless each is a word on its own and together they aren't.  There is only an English
+/// word list, so in any other language they never are, eg French "peut-\nêtre".
+fn is_broken_word(start: &str, end: &str, language: Language) -> bool {
+    match language {
+        Language::English => is_word(&format!("{start}{end}")) || !(is_word(start) && is_word(end)),
+        Language::German | Language::French | Language::Spanish => false,
+    }
+}
+
+/// Joins words hyphenated across a line break, eg "exam-\nple" -> "example".  A compound of
+/// two words the word list doesn't know as one keeps its hyphen, eg "well-\nknown" ->
+/// "well-known".  A capitalized second part, eg "Smith-\nJones", is left alone.
+///
+/// # Arguments
+/// * `text` - The text to join
+/// * `language` - The language of the book, which decides the word list
+/// # Returns
+/// The text with the line broken words joined
+pub fn dehyphenate(text: &str, language: Language) -> String {
+    let re = Regex::new(r"(\p{L}+)-[ \t]*\r?\n[ \t]*(\p{Ll}+)").unwrap();
+    re.replace_all(text, |caps: &Captures| {
+        let (start, end) = (&caps[1], &caps[2]);
+        if is_broken_word(start, end, language) {
+            format!("{start}{end}")
+        } else {
+            format!("{start}-{end}")
+        }
+    })
+    .to_string()
+}
+
+#[test]
+fn drop_caps_are_joined_to_their_word() {
+    let text = "T\u{E006} he day was \u{E006}warm.\nO\u{E006}\n\nNCE upon a time.\n";
+    assert_eq!(
+        rejoin_lead_ins(text),
+        "The day was warm.\nONCE upon a time.\n"
+    );
+}
+
+#[test]
+fn drop_caps_that_are_words_are_left_apart() {
+    assert_eq!(rejoin_lead_ins("I\u{E006} was born"), "I was born");
+    assert_eq!(rejoin_lead_ins("A\u{E006} round table"), "A round table");
+    assert_eq!(rejoin_lead_ins("“I\u{E006} t was"), "“It was");
+}
+
+#[test]
+fn words_split_across_lines_are_joined() {
+    assert_eq!(
+        dehyphenate(
+            "an exam-\nple of wonder-\n  ing and well-\nknown text",
+            Language::English
+        ),
+        "an example of wondering and well-known text"
+    );
+    assert_eq!(
+        dehyphenate("Smith-\nJones", Language::English),
+        "Smith-\nJones"
+    );
+    assert_eq!(
+        dehyphenate("well-known\n\nnot-\n\njoined", Language::English),
+        "well-known\n\nnot-\n\njoined"
+    );
+}
+
+#[test]
+fn other_languages_keep_the_hyphen() {
+    assert_eq!(
+        dehyphenate("peut-\nêtre, c'est-à-\ndire", Language::French),
+        "peut-être, c'est-à-dire"
+    );
+    assert_eq!(dehyphenate("peut-\nêtre", Language::English), "peutêtre");
+}
+
+#[test]
+fn the_word_list_has_inflected_english_words() {
+    for word in [
+        "aardvark",
+        "telephones",
+        "bicycled",
+        "straightened",
+        "daisies",
+        "o'clock",
+    ] {
+        assert!(is_word(word), "{word}");
+    }
+    for word in ["strlcat", "whoami", "somethink", "repeatually"] {
+        assert!(!is_word(word), "{word}");
+    }
+    assert_eq!(
+        dehyphenate("a tele-\nphone", Language::English),
+        "a telephone"
+    );
+}
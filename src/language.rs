@@ -0,0 +1,601 @@
+use clap::ValueEnum;
+use epub::doc::EpubDoc;
+use regex::Regex;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+
+use crate::abbreviations::{Abbreviations, Context};
+use crate::dates::{self, DateOrder, replace_with_rest};
+use crate::{french, german, numbers, replace_text, roman, spanish, units};
+
+/// The language a book is read in, which decides how numbers, money, units and abbreviations are
+/// written out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    /// English
+    #[default]
+    #[value(name = "en")]
+    English,
+    /// German
+    #[value(name = "de")]
+    German,
+    /// French
+    #[value(name = "fr")]
+    French,
+    /// Spanish
+    #[value(name = "es")]
+    Spanish,
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::English => "English",
+            Self::German => "German",
+            Self::French => "French",
+            Self::Spanish => "Spanish",
+        };
+        f.pad(name)
+    }
+}
+
+/// A currency, and how its main and minor units are said
+pub struct Currency {
+    pub symbols: &'static [&'static str],
+    /// Singular and plural, eg "euro", "euros"
+    pub name: [&'static str; 2],
+    /// Singular and plural of the hundredth part, eg "centime", "centimes"
+    pub minor: [&'static str; 2],
+    /// The count in front agrees with a feminine name, eg "une livre"
+    pub feminine: bool,
+}
+
+pub const fn currency(
+    symbols: &'static [&'static str],
+    name: [&'static str; 2],
+    minor: [&'static str; 2],
+) -> Currency {
+    Currency {
+        symbols,
+        name,
+        minor,
+        feminine: false,
+    }
+}
+
+/// A unit of measure, and the noun it is read as
+pub struct Unit {
+    pub symbols: &'static [&'static str],
+    pub singular: &'static str,
+    pub plural: &'static str,
+    pub feminine: bool,
+}
+
+pub const fn masculine(
+    symbols: &'static [&'static str],
+    singular: &'static str,
+    plural: &'static str,
+) -> Unit {
+    Unit {
+        symbols,
+        singular,
+        plural,
+        feminine: false,
+    }
+}
+
+pub const fn feminine(
+    symbols: &'static [&'static str],
+    singular: &'static str,
+    plural: &'static str,
+) -> Unit {
+    Unit {
+        symbols,
+        singular,
+        plural,
+        feminine: true,
+    }
+}
+
+/// Reads a number with an ordinal suffix, eg `(1, "re")` -> "première"
+pub type OrdinalReader = fn(u64, &str) -> String;
+
+/// Reads a number written with a full stop as an ordinal, given the words before and after it, eg
+/// `(3, "am", "Mai")` -> "dritten", or None if the full stop ends a sentence
+pub type DottedOrdinalReader = fn(u64, &str, &str) -> Option<String>;
+
+/// The rules for reading one language other than English.  English has richer rules of its own
+/// in `numbers`, `dates`, `roman`, `units` and `replace_text`.
+pub struct Pack {
+    /// A whole number in words
+    pub cardinal: fn(u64) -> String,
+    /// A whole number in front of a noun, which agrees with it if the flag says it is feminine
+    pub count: fn(u64, bool) -> String,
+    /// Guesses from its ending whether the word after a number is a feminine noun, or None if it
+    /// doesn't look like a noun.  Some nouns are guessed wrong, and German case isn't followed,
+    /// eg "mit 1 Frau" is read "mit eine Frau".
+    pub feminine_noun: fn(&str) -> Option<bool>,
+    /// A year in words, for numbers after one of the `year_context` words
+    pub year: fn(u64) -> String,
+    pub year_context: &'static [&'static str],
+    /// The regex of the suffixes that make a number an ordinal, and the function that reads it
+    pub ordinal: Option<(&'static str, OrdinalReader)>,
+    /// Ordinals written with a full stop, eg "der 2. Weltkrieg"
+    pub dotted_ordinal: Option<DottedOrdinalReader>,
+    pub decimal_separator: char,
+    pub thousands_separators: &'static [char],
+    /// The word for the decimal separator, eg "Komma"
+    pub decimal_point: &'static str,
+    /// Read the digits after the decimal separator one by one, eg "drei Komma eins vier"
+    pub digits_after_the_point: bool,
+    pub percent: &'static str,
+    /// Joins the main and minor units of an amount of money, eg "cinq euros et dix centimes"
+    pub and: &'static str,
+    pub currencies: &'static [Currency],
+    pub units: &'static [Unit],
+    pub abbreviations: &'static [(&'static str, &'static str, Context)],
+}
+
+impl Language {
+    /// Finds the language of a language tag or name, eg "de", "fr-CA", "spa" or "English"
+    ///
+    /// # Arguments
+    /// * `tag` - The language tag
+    /// # Returns
+    /// The language, or None if there are no rules for it
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_lowercase();
+        let primary = tag.split(['-', '_']).next().unwrap_or_default();
+        match primary {
+            "en" | "eng" | "english" => Some(Self::English),
+            "de" | "deu" | "ger" | "german" | "deutsch" => Some(Self::German),
+            "fr" | "fra" | "fre" | "french" | "français" => Some(Self::French),
+            "es" | "spa" | "spanish" | "español" => Some(Self::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Returns the language's tag, eg "de"
+    pub fn tag(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Returns the rules for the language, or None for English
+    pub const fn pack(self) -> Option<&'static Pack> {
+        match self {
+            Self::English => None,
+            Self::German => Some(&german::PACK),
+            Self::French => Some(&french::PACK),
+            Self::Spanish => Some(&spanish::PACK),
+        }
+    }
+
+    /// Writes out dates, abbreviations, money and units.  Plain numbers are left for
+    /// `convert_numbers_to_words`, so custom replacements can still match them.
+    ///
+    /// # Arguments
+    /// * `text` - The text to convert
+    /// * `abbreviations` - The abbreviations to expand
+    /// * `date_order` - Whether all-number dates are month or day first
+    /// # Returns
+    /// The converted text
+    pub fn normalize(
+        self,
+        text: &str,
+        abbreviations: &Abbreviations,
+        date_order: DateOrder,
+    ) -> String {
+        let Some(pack) = self.pack() else {
+            let text = dates::convert_dates_to_words(text, date_order);
+            let text = abbreviations.expand(&text);
+            let text = roman::convert_roman_numerals(&text);
+            let text = replace_text::convert_money_to_words(&text);
+            return units::convert_units_to_words(&text);
+        };
+        let text = abbreviations.expand(text);
+        let text = convert_money_to_words(&text, pack);
+        convert_units_to_words(&text, pack)
+    }
+
+    /// Writes out the numbers that are left after `normalize` and the custom replacements
+    ///
+    /// # Arguments
+    /// * `text` - The text to convert
+    /// # Returns
+    /// The text with numbers in words
+    pub fn convert_numbers_to_words(self, text: &str) -> String {
+        self.pack().map_or_else(
+            || numbers::convert_numbers_to_words(text),
+            |pack| convert_numbers_to_words(text, pack),
+        )
+    }
+}
+
+/// Finds the book's language tag from the `dc:language` metadata, or failing that the `xml:lang`
+/// of the title or the first spine document
+fn language_tag(doc: &mut EpubDoc<BufReader<File>>) -> Option<String> {
+    if let Some(language) = doc.mdata("language") {
+        return Some(language.value.clone());
+    }
+    if let Some(language) = doc.mdata("title").and_then(|title| title.lang.clone()) {
+        return Some(language);
+    }
+    let idref = doc.spine.first()?.idref.clone();
+    let path = doc.resources.get(&idref)?.path.clone();
+    let html = doc.get_resource_str_by_path(&path)?;
+    let re = Regex::new(r#"<html[^>]*\s(?:xml:)?lang\s*=\s*["']([^"']+)["']"#).unwrap();
+    re.captures(&html).map(|caps| caps[1].to_string())
+}
+
+/// Works out which language the book is written in
+///
+/// # Arguments
+/// * `doc` - the epub document
+/// # Returns
+/// The language, English if the book doesn't say or there are no rules for its language
+pub fn detect(doc: &mut EpubDoc<BufReader<File>>) -> Language {
+    let Some(tag) = language_tag(doc) else {
+        return Language::default();
+    };
+    Language::from_tag(&tag).unwrap_or_else(|| {
+        println!("No rules for language '{tag}', reading it as English");
+        Language::default()
+    })
+}
+
+/// Builds a regex alternation, longest first so "km²" wins over "km"
+fn alternation<'a>(symbols: impl Iterator<Item = &'a str>) -> String {
+    let mut symbols: Vec<&str> = symbols.collect();
+    symbols.sort_by_key(|symbol| std::cmp::Reverse(symbol.chars().count()));
+    symbols
+        .iter()
+        .map(|symbol| regex::escape(symbol))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// The regex of a number written the language's way, eg "1.234,5" in German
+fn number_pattern(pack: &Pack) -> String {
+    let thousands: String = pack
+        .thousands_separators
+        .iter()
+        .map(|separator| regex::escape(&separator.to_string()))
+        .collect();
+    let decimal = regex::escape(&pack.decimal_separator.to_string());
+    format!(r"\d{{1,3}}(?:[{thousands}]\d{{3}})+(?:{decimal}\d+)?|\d+(?:{decimal}\d+)?")
+}
+
+/// Splits a number into its whole part and the digits after the decimal separator
+fn split_number<'a>(pack: &Pack, number: &'a str) -> (u64, Option<&'a str>) {
+    let (whole, fraction) = number
+        .split_once(pack.decimal_separator)
+        .map_or((number, None), |(whole, fraction)| (whole, Some(fraction)));
+    let whole: String = whole.chars().filter(char::is_ascii_digit).collect();
+    (whole.parse().unwrap_or_default(), fraction)
+}
+
+/// Reads a number in words.  A whole number in front of a noun agrees with it if `feminine` is
+/// given, eg "eine Stunde", but a decimal never does, eg "eins Komma fünf Stunden".
+fn read_number(pack: &Pack, number: &str, feminine: Option<bool>) -> String {
+    let (whole, fraction) = split_number(pack, number);
+    let Some(fraction) = fraction else {
+        return feminine.map_or_else(
+            || (pack.cardinal)(whole),
+            |feminine| (pack.count)(whole, feminine),
+        );
+    };
+
+    let mut words = vec![(pack.cardinal)(whole), pack.decimal_point.to_string()];
+    let significant = fraction.trim_start_matches('0');
+    if pack.digits_after_the_point {
+        words.extend(
+            fraction
+                .chars()
+                .filter_map(|digit| digit.to_digit(10))
+                .map(|digit| (pack.cardinal)(u64::from(digit))),
+        );
+    } else {
+        // Leading zeros are read, then the rest as a number, eg "trois virgule zéro cinq"
+        let zeros = fraction.len() - significant.len();
+        words.extend(std::iter::repeat_n((pack.cardinal)(0), zeros));
+        if let Ok(rest) = significant.parse() {
+            words.push((pack.cardinal)(rest));
+        }
+    }
+    words.join(" ")
+}
+
+/// Reads an amount of money, eg "5,50" euros -> "fünf Euro und fünfzig Cent"
+fn amount_to_words(pack: &Pack, amount: &str, currency: &Currency) -> String {
+    let (whole, fraction) = split_number(pack, amount);
+    let name = |count: u64, names: [&'static str; 2]| if count == 1 { names[0] } else { names[1] };
+    match fraction {
+        Some(cents) if cents.len() == 2 => {
+            let cents: u64 = cents.parse().unwrap_or_default();
+            let minor = format!(
+                "{} {}",
+                (pack.count)(cents, false),
+                name(cents, currency.minor)
+            );
+            if whole == 0 {
+                return minor;
+            }
+            let main = format!(
+                "{} {}",
+                (pack.count)(whole, currency.feminine),
+                name(whole, currency.name)
+            );
+            if cents == 0 {
+                main
+            } else {
+                format!("{main} {} {minor}", pack.and)
+            }
+        }
+        Some(_) => format!("{} {}", read_number(pack, amount, None), currency.name[1]),
+        None => format!(
+            "{} {}",
+            (pack.count)(whole, currency.feminine),
+            name(whole, currency.name)
+        ),
+    }
+}
+
+/// Writes out amounts of money with the symbol or code before or after, eg "5 €", "€5", "12,50
+/// EUR"
+fn convert_money_to_words(text: &str, pack: &Pack) -> String {
+    let symbols = alternation(
+        pack.currencies
+            .iter()
+            .flat_map(|currency| currency.symbols.iter().copied()),
+    );
+    let number = number_pattern(pack);
+    let find = |symbol: &str| {
+        pack.currencies
+            .iter()
+            .find(|currency| currency.symbols.contains(&symbol))
+    };
+
+    let re = Regex::new(&format!(
+        r"(?<symbol>{symbols})[ \u{{a0}}\u{{202f}}]?(?<amount>{number})\b"
+    ))
+    .unwrap();
+    let text = replace_with_rest(&re, text, |caps, _| {
+        Some(amount_to_words(
+            pack,
+            &caps["amount"],
+            find(&caps["symbol"])?,
+        ))
+    });
+    let re = Regex::new(&format!(
+        r"\b(?<amount>{number})[ \u{{a0}}\u{{202f}}]?(?<symbol>{symbols})"
+    ))
+    .unwrap();
+    replace_with_rest(&re, &text, |caps, _| {
+        Some(amount_to_words(
+            pack,
+            &caps["amount"],
+            find(&caps["symbol"])?,
+        ))
+    })
+}
+
+/// Writes out units of measure after a number, eg "5 km" -> "fünf Kilometer", "1 h" -> "una
+/// hora"
+fn convert_units_to_words(text: &str, pack: &Pack) -> String {
+    let symbols = alternation(
+        pack.units
+            .iter()
+            .flat_map(|unit| unit.symbols.iter().copied()),
+    );
+    let re = Regex::new(&format!(
+        r"\b(?<number>{})[ \u{{a0}}\u{{202f}}]?(?<symbol>{symbols})",
+        number_pattern(pack)
+    ))
+    .unwrap();
+    replace_with_rest(&re, text, |caps, _| {
+        let unit = pack
+            .units
+            .iter()
+            .find(|unit| unit.symbols.contains(&&caps["symbol"]))?;
+        let number = &caps["number"];
+        let name = if number == "1" {
+            unit.singular
+        } else {
+            unit.plural
+        };
+        Some(format!(
+            "{} {name}",
+            read_number(pack, number, Some(unit.feminine))
+        ))
+    })
+}
+
+/// Writes out ordinals, percentages, years and the remaining numbers
+fn convert_numbers_to_words(text: &str, pack: &Pack) -> String {
+    let number = number_pattern(pack);
+
+    let mut text = text.to_string();
+    if let Some((suffixes, ordinal)) = pack.ordinal {
+        let re = Regex::new(&format!(r"\b(?<number>\d+)(?<suffix>{suffixes})")).unwrap();
+        text = replace_with_rest(&re, &text, |caps, _| {
+            Some(ordinal(caps["number"].parse().ok()?, &caps["suffix"]))
+        });
+    }
+    if let Some(ordinal) = pack.dotted_ordinal {
+        let re = Regex::new(r"\b(?<number>\d{1,3})\.[ \u{a0}](?<next>\p{L}+)").unwrap();
+        text = replace_with_rest(&re, &text, |caps, _| {
+            let before = text[..caps.get(0)?.start()]
+                .split_whitespace()
+                .next_back()
+                .unwrap_or_default();
+            let words = ordinal(caps["number"].parse().ok()?, before, &caps["next"])?;
+            Some(format!("{words} {}", &caps["next"]))
+        });
+    }
+
+    let re = Regex::new(&format!(r"\b(?<number>{number})[ \u{{a0}}\u{{202f}}]?%")).unwrap();
+    let mut text = re
+        .replace_all(&text, |caps: &regex::Captures| {
+            format!(
+                "{} {}",
+                read_number(pack, &caps["number"], None),
+                pack.percent
+            )
+        })
+        .to_string();
+
+    if !pack.year_context.is_empty() {
+        let re = Regex::new(&format!(
+            r"\b(?<context>{})\s+(?<year>1[1-9]\d\d|20\d\d)\b",
+            alternation(pack.year_context.iter().copied())
+        ))
+        .unwrap();
+        text = re
+            .replace_all(&text, |caps: &regex::Captures| {
+                let year = caps["year"].parse().unwrap_or_default();
+                format!("{} {}", &caps["context"], (pack.year)(year))
+            })
+            .to_string();
+    }
+
+    let re = Regex::new(&format!(r"\b(?<number>{number})\b")).unwrap();
+    replace_with_rest(&re, &text, |caps, rest| {
+        let before = &text[..caps.get(0)?.start()];
+        if joined_digits(before.chars().rev()) || joined_digits(rest.chars()) {
+            return None;
+        }
+        let noun = rest
+            .strip_prefix([' ', '\u{a0}', '\u{202f}'])
+            .and_then(|rest| rest.split(|c: char| !c.is_alphabetic()).next())
+            .filter(|word| !word.is_empty());
+        Some(read_number(
+            pack,
+            &caps["number"],
+            noun.and_then(pack.feminine_noun),
+        ))
+    })
+}
+
+/// Returns true if the characters start with a ".", ":" or "/" and a digit, so the number next
+/// to them is part of a date, time or reference, eg "12.03.1998", "10:45" or "1/2", and is left
+/// as it is
+fn joined_digits(mut chars: impl Iterator<Item = char>) -> bool {
+    matches!(chars.next(), Some('.' | ':' | '/'))
+        && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+fn read(language: Language, text: &str) -> String {
+    let abbreviations = Abbreviations::built_in(language);
+    let text = language.normalize(text, &abbreviations, DateOrder::default());
+    language.convert_numbers_to_words(&text)
+}
+
+#[test]
+fn languages_are_found_from_their_tags() {
+    assert_eq!(Language::from_tag("de-DE"), Some(Language::German));
+    assert_eq!(Language::from_tag("fr_CA"), Some(Language::French));
+    assert_eq!(Language::from_tag("spa"), Some(Language::Spanish));
+    assert_eq!(Language::from_tag("EN"), Some(Language::English));
+    assert_eq!(Language::from_tag("it"), None);
+}
+
+#[test]
+fn language_is_read_from_the_metadata() {
+    let fixture = "fixtures/alice_in_wonderland_by_lewis_carroll.epub";
+    let mut doc = EpubDoc::new(fixture).unwrap();
+    assert_eq!(language_tag(&mut doc), Some("en".to_string()));
+    assert_eq!(detect(&mut doc), Language::English);
+}
+
+#[test]
+fn german_numbers_money_and_units() {
+    assert_eq!(
+        read(
+            Language::German,
+            "Im Jahr 1904 kostete es 5,50 € und wog 1 t, z. B. 2,5 kg oder 1.500 g."
+        ),
+        "Im Jahr neunzehnhundertvier kostete es fünf Euro und fünfzig Cent und wog eine Tonne, \
+         zum Beispiel zwei Komma fünf Kilogramm oder eintausendfünfhundert Gramm."
+    );
+    assert_eq!(
+        read(Language::German, "Nur 3 % der 21 Gäste, S. 12"),
+        "Nur drei Prozent der einundzwanzig Gäste, Seite zwölf"
+    );
+}
+
+#[test]
+fn french_numbers_money_and_units() {
+    assert_eq!(
+        read(
+            Language::French,
+            "M. Dupont a payé 21 € pour 2 min au 2e étage, soit 12,5 % de 1 000 £."
+        ),
+        "Monsieur Dupont a payé vingt et un euros pour deux minutes au deuxième étage, soit douze virgule \
+         cinq pour cent de mille livres."
+    );
+}
+
+#[test]
+fn spanish_numbers_money_and_units() {
+    assert_eq!(
+        read(
+            Language::Spanish,
+            "El Sr. García vivió en el 2.º piso durante 21 h y pagó $1,05 por 200 g."
+        ),
+        "El señor García vivió en el segundo piso durante veintiuna horas y pagó un dólar con \
+         cinco centavos por doscientos gramos."
+    );
+}
+
+#[test]
+fn dates_and_times_are_left_as_they_are() {
+    assert_eq!(
+        read(Language::German, "Am 12.03.1998 um 10:45 Uhr, Kapitel 1.2"),
+        "Am 12.03.1998 um 10:45 Uhr, Kapitel 1.2"
+    );
+    assert_eq!(
+        read(
+            Language::German,
+            "Am 3. Mai 1998 endete der 2. Weltkrieg nicht."
+        ),
+        "Am dritten Mai neunzehnhundertachtundneunzig endete der zweite Weltkrieg nicht."
+    );
+    assert_eq!(
+        read(Language::German, "Er war 3. Dann ging er."),
+        "Er war drei. Dann ging er."
+    );
+    assert_eq!(
+        read(Language::French, "Le 12/03/1998 à 10:45"),
+        "Le 12/03/1998 à 10:45"
+    );
+    assert_eq!(
+        read(
+            Language::Spanish,
+            "El 12/03/1998 a las 10:45, 1.500 libros."
+        ),
+        "El 12/03/1998 a las 10:45, mil quinientos libros."
+    );
+}
+
+#[test]
+fn counts_agree_with_the_noun_after_them() {
+    assert_eq!(
+        read(Language::German, "Da kam 1 Frau und 1 Mann, 1 oder 2 Mal."),
+        "Da kam eine Frau und ein Mann, eins oder zwei Mal."
+    );
+    assert_eq!(
+        read(Language::French, "21 femmes et 1 homme, 1 fois"),
+        "vingt et une femmes et un homme, une fois"
+    );
+    assert_eq!(
+        read(
+            Language::Spanish,
+            "21 personas, 21 libros, 21 millones y 1 de cada 3."
+        ),
+        "veintiuna personas, veintiún libros, veintiún millones y uno de cada tres."
+    );
+}
@@ -0,0 +1,418 @@
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::fs;
+use std::path::Path;
+
+use crate::heteronyms::Heteronyms;
+use crate::language::Language;
+use crate::spans::escape;
+
+/// How to say a word: a phoneme string for SSML, a respelling for plain text, or both
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pronunciation {
+    /// The alphabet (eg "ipa" or "x-sampa") and the pronunciation in it
+    phoneme: Option<(String, String)>,
+    alias: Option<String>,
+}
+
+impl Pronunciation {
+    /// Reads a pronunciation from a lexicon or heteronyms file: a respelling, an IPA
+    /// pronunciation between slashes, or both, eg "red /ɹɛd/"
+    ///
+    /// # Arguments
+    /// * `text` - The pronunciation
+    pub fn parse(text: &str) -> Self {
+        let re = Regex::new(r"/([^/]+)/").unwrap();
+        let phoneme = re
+            .captures(text)
+            .map(|caps| ("ipa".to_string(), caps[1].trim().to_string()));
+        let alias = re.replace(text, "").trim().to_string();
+        Self {
+            phoneme,
+            alias: (!alias.is_empty()).then_some(alias),
+        }
+    }
+
+    /// Returns the respelling, if there is one
+    pub fn alias(&self) -> Option<&str> {
+        self.alias.as_deref()
+    }
+
+    /// Returns the respelling of a word, in its case
+    fn respell(&self, word: &str) -> Option<String> {
+        self.alias.as_ref().map(|alias| match_case(alias, word))
+    }
+
+    /// Returns a word as an SSML `<phoneme>` element, or a `<sub>` element if there is only a
+    /// respelling
+    fn to_ssml(&self, word: &str) -> Option<String> {
+        if let Some((alphabet, phoneme)) = &self.phoneme {
+            Some(format!(
+                "<phoneme alphabet=\"{}\" ph=\"{}\">{}</phoneme>",
+                escape(alphabet),
+                escape(phoneme),
+                escape(word)
+            ))
+        } else {
+            self.respell(word)
+                .map(|alias| format!("<sub alias=\"{}\">{}</sub>", escape(&alias), escape(word)))
+        }
+    }
+}
+
+/// A word in the lexicon and how to say it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    grapheme: String,
+    pronunciation: Pronunciation,
+    /// The language the entry is for, or None for every language
+    language: Option<Language>,
+}
+
+impl Entry {
+    /// Returns true if the entry is for the text as it is written.  An all lower case grapheme
+    /// matches any case, eg "gif" matches "GIF" and "Gif".  A grapheme with capitals only matches
+    /// itself or the same word in capitals, eg "Hermione" matches "HERMIONE" but not "hermione".
+    fn matches(&self, text: &str) -> bool {
+        self.grapheme == text
+            || (self.grapheme == self.grapheme.to_lowercase()
+                && self.grapheme == text.to_lowercase())
+            || (text == text.to_uppercase() && self.grapheme.to_uppercase() == text)
+    }
+}
+
+/// A pronunciation lexicon, loaded from W3C PLS files or simple `word==respelling` files, and
+/// the heteronyms whose pronunciation depends on the words around them
+#[derive(Debug, Default)]
+pub struct Lexicon {
+    entries: Vec<Entry>,
+    heteronyms: Heteronyms,
+    /// Matches any grapheme as a whole word, ignoring case
+    pattern: Option<Regex>,
+}
+
+/// Reads a line of a simple lexicon file, `word==respelling`, `word==/IPA/` or both
+fn process_line(text: &str) -> Option<Entry> {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+        return None;
+    }
+    let Some((grapheme, pronunciation)) = text.split_once("==") else {
+        println!("Lexicon File Syntax error - Ignoring line, no '==' found: {text}");
+        return None;
+    };
+    Some(Entry {
+        grapheme: grapheme.trim().to_string(),
+        pronunciation: Pronunciation::parse(pronunciation),
+        language: None,
+    })
+}
+
+/// Returns the replacement written in the case of the text, eg "her-my-oh-nee" for "Hermione"
+/// is "Her-my-oh-nee"
+fn match_case(replacement: &str, text: &str) -> String {
+    let mut chars = replacement.chars();
+    match (chars.next(), text.chars().next()) {
+        (Some(first), Some(initial)) if initial.is_uppercase() && first.is_lowercase() => {
+            first.to_uppercase().chain(chars).collect()
+        }
+        _ => replacement.to_string(),
+    }
+}
+
+impl Lexicon {
+    /// Returns a lexicon with just the heteronyms
+    ///
+    /// # Arguments
+    /// * `heteronyms` - The heteronym rules
+    pub fn new(heteronyms: Heteronyms) -> Self {
+        let mut lexicon = Self {
+            heteronyms,
+            ..Self::default()
+        };
+        lexicon.compile();
+        lexicon
+    }
+
+    /// Adds the entries of a simple lexicon file
+    ///
+    /// # Arguments
+    /// * `text` - The contents of the file, one `word==respelling` or `word==/IPA/` per line
+    pub fn extend_from_text(&mut self, text: &str) {
+        self.entries.extend(text.lines().filter_map(process_line));
+        self.compile();
+    }
+
+    /// Adds the lexemes of a W3C Pronunciation Lexicon Specification file.  Each grapheme of a
+    /// lexeme gets its first phoneme and alias.
+    ///
+    /// # Arguments
+    /// * `pls` - The contents of the PLS file
+    pub fn extend_from_pls(&mut self, pls: &str) {
+        let document = Html::parse_document(pls);
+        let lexicon_selector = Selector::parse("lexicon").unwrap();
+        let lexeme_selector = Selector::parse("lexeme").unwrap();
+        let grapheme_selector = Selector::parse("grapheme").unwrap();
+        let phoneme_selector = Selector::parse("phoneme").unwrap();
+        let alias_selector = Selector::parse("alias").unwrap();
+
+        for lexicon in document.select(&lexicon_selector) {
+            let alphabet = lexicon.attr("alphabet").unwrap_or("ipa");
+            let language = lexicon.attr("xml:lang").and_then(Language::from_tag);
+            for lexeme in lexicon.select(&lexeme_selector) {
+                let text = |element: scraper::ElementRef| {
+                    element.text().collect::<String>().trim().to_string()
+                };
+                let phoneme = lexeme.select(&phoneme_selector).next().map(|phoneme| {
+                    let alphabet = phoneme.attr("alphabet").unwrap_or(alphabet);
+                    (alphabet.to_string(), text(phoneme))
+                });
+                let alias = lexeme.select(&alias_selector).next().map(text);
+                for grapheme in lexeme.select(&grapheme_selector) {
+                    self.entries.push(Entry {
+                        grapheme: text(grapheme),
+                        pronunciation: Pronunciation {
+                            phoneme: phoneme.clone(),
+                            alias: alias.clone(),
+                        },
+                        language,
+                    });
+                }
+            }
+        }
+        self.compile();
+    }
+
+    /// Adds the entries of a lexicon file, either PLS (`.pls` or XML) or simple
+    ///
+    /// # Arguments
+    /// * `filename` - The lexicon file
+    pub fn load(&mut self, filename: &str) {
+        match fs::read_to_string(filename) {
+            Ok(file_text) => {
+                println!("Opening pronunciation lexicon: {filename}");
+                let pls = Path::new(filename)
+                    .extension()
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case("pls"));
+                if pls || file_text.trim_start().starts_with('<') {
+                    self.extend_from_pls(&file_text);
+                } else {
+                    self.extend_from_text(&file_text);
+                }
+            }
+            Err(error) => eprintln!("Unable to open file: {error} {filename}"),
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns every word the lexicon pronounces, and every respelling it writes in their place
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        let entries = self.entries.iter().flat_map(|entry| {
+            std::iter::once(entry.grapheme.as_str()).chain(entry.pronunciation.alias())
+        });
+        entries.chain(self.heteronyms.words()).chain(
+            self.heteronyms
+                .pronunciations()
+                .filter_map(Pronunciation::alias),
+        )
+    }
+
+    /// Builds the pattern matching every grapheme, longest first so "New York" wins over "New"
+    fn compile(&mut self) {
+        let mut graphemes: Vec<&str> = self
+            .entries
+            .iter()
+            .map(|entry| entry.grapheme.as_str())
+            .chain(self.heteronyms.words())
+            .filter(|grapheme| !grapheme.is_empty())
+            .collect();
+        graphemes.sort_by_key(|grapheme| std::cmp::Reverse(grapheme.len()));
+        graphemes.dedup();
+        let alternatives: Vec<String> = graphemes
+            .iter()
+            .map(|grapheme| {
+                let start = if grapheme.starts_with(char::is_alphanumeric) {
+                    r"\b"
+                } else {
+                    ""
+                };
+                let end = if grapheme.ends_with(char::is_alphanumeric) {
+                    r"\b"
+                } else {
+                    ""
+                };
+                format!("{start}{}{end}", regex::escape(grapheme))
+            })
+            .collect();
+        self.pattern = (!alternatives.is_empty())
+            .then(|| Regex::new(&format!("(?i){}", alternatives.join("|"))).unwrap());
+    }
+
+    /// Returns the pronunciation of a matched word, preferring an entry written exactly the same
+    /// way.  English heteronyms not in the lexicon are read from the words around them.
+    fn find(
+        &self,
+        text: &str,
+        before: &str,
+        after: &str,
+        language: Language,
+    ) -> Option<&Pronunciation> {
+        let candidates: Vec<&Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .language
+                    .is_none_or(|entry_language| entry_language == language)
+            })
+            .filter(|entry| entry.matches(text))
+            .collect();
+        let entry = candidates
+            .iter()
+            .find(|entry| entry.grapheme == text)
+            .or_else(|| candidates.first());
+        match entry {
+            Some(entry) => Some(&entry.pronunciation),
+            None if language == Language::English => self.heteronyms.choose(text, before, after),
+            None => None,
+        }
+    }
+
+    /// Replaces each word in the lexicon with `replace`, and the text around them with `between`
+    fn replace(
+        &self,
+        text: &str,
+        language: Language,
+        between: impl Fn(&str) -> String,
+        replace: impl Fn(&Pronunciation, &str) -> Option<String>,
+    ) -> String {
+        let Some(pattern) = &self.pattern else {
+            return between(text);
+        };
+        let mut result = String::new();
+        let mut last = 0;
+        for word in pattern.find_iter(text) {
+            let replacement = self
+                .find(
+                    word.as_str(),
+                    &text[..word.start()],
+                    &text[word.end()..],
+                    language,
+                )
+                .and_then(|pronunciation| replace(pronunciation, word.as_str()));
+            if let Some(replacement) = replacement {
+                result.push_str(&between(&text[last..word.start()]));
+                result.push_str(&replacement);
+                last = word.end();
+            }
+        }
+        result.push_str(&between(&text[last..]));
+        result
+    }
+
+    /// Respells the words in the lexicon for plain text.  Entries with only a phoneme are left,
+    /// as plain text can't carry one.
+    ///
+    /// # Arguments
+    /// * `text` - The text to respell
+    /// * `language` - The language the text is in
+    /// # Returns
+    /// The respelled text
+    pub fn respell(&self, text: &str, language: Language) -> String {
+        self.replace(text, language, str::to_string, Pronunciation::respell)
+    }
+
+    /// Escapes text for SSML, wrapping the words in the lexicon in `<phoneme>` elements, or in
+    /// `<sub>` elements if they only have a respelling
+    ///
+    /// # Arguments
+    /// * `text` - The plain text
+    /// * `language` - The language the text is in
+    /// # Returns
+    /// The text as SSML content
+    pub fn to_ssml(&self, text: &str, language: Language) -> String {
+        self.replace(text, language, escape, Pronunciation::to_ssml)
+    }
+}
+
+#[cfg(test)]
+const PLS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<lexicon version="1.0" xmlns="http://www.w3.org/2005/01/pronunciation-lexicon"
+      alphabet="ipa" xml:lang="en">
+  <lexeme>
+    <grapheme>Hermione</grapheme>
+    <phoneme>hɜːˈmaɪ.ə.ni</phoneme>
+    <alias>her-my-oh-nee</alias>
+  </lexeme>
+  <lexeme>
+    <grapheme>gif</grapheme>
+    <grapheme>gifs</grapheme>
+    <phoneme alphabet="x-sampa">dZIf</phoneme>
+  </lexeme>
+  <lexeme>
+    <grapheme>New York</grapheme>
+    <alias>Noo York</alias>
+  </lexeme>
+</lexicon>"#;
+
+#[test]
+fn pls_lexemes_are_read() {
+    let mut lexicon = Lexicon::default();
+    lexicon.extend_from_pls(PLS);
+    assert_eq!(lexicon.len(), 4);
+    assert_eq!(
+        lexicon.entries[2],
+        Entry {
+            grapheme: "gifs".to_string(),
+            pronunciation: Pronunciation {
+                phoneme: Some(("x-sampa".to_string(), "dZIf".to_string())),
+                alias: None,
+            },
+            language: Some(Language::English),
+        }
+    );
+}
+
+#[test]
+fn whole_words_are_respelled_in_their_case() {
+    let mut lexicon = Lexicon::default();
+    lexicon.extend_from_pls(PLS);
+    assert_eq!(
+        lexicon.respell(
+            "Hermione, HERMIONE and hermione in New York. Hermiones gifs.",
+            Language::English
+        ),
+        "Her-my-oh-nee, Her-my-oh-nee and hermione in Noo York. Hermiones gifs."
+    );
+    assert_eq!(lexicon.respell("Hermione", Language::French), "Hermione");
+}
+
+#[test]
+fn ssml_uses_phonemes_and_aliases() {
+    let mut lexicon = Lexicon::default();
+    lexicon.extend_from_pls(PLS);
+    assert_eq!(
+        lexicon.to_ssml("Hermione & GIF in New York", Language::English),
+        "<phoneme alphabet=\"ipa\" ph=\"hɜːˈmaɪ.ə.ni\">Hermione</phoneme> &amp; <phoneme alphabet=\"x-sampa\" ph=\"dZIf\">GIF</phoneme> in <sub alias=\"Noo York\">New York</sub>"
+    );
+}
+
+#[test]
+fn simple_files_hold_respellings_and_ipa() {
+    let mut lexicon = Lexicon::default();
+    lexicon.extend_from_text(
+        "# comment\nHermione==her-my-oh-nee\nDr.==Doctor\nquay==/kiː/\nNew York==Noo York /nuː jɔːk/",
+    );
+    assert_eq!(
+        lexicon.respell("Ask Dr. Hermione at the quay", Language::German),
+        "Ask Doctor Her-my-oh-nee at the quay"
+    );
+    assert_eq!(
+        lexicon.to_ssml("quay", Language::English),
+        "<phoneme alphabet=\"ipa\" ph=\"kiː\">quay</phoneme>"
+    );
+    assert_eq!(lexicon.respell("New York", Language::English), "Noo York");
+}
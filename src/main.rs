@@ -6,7 +6,7 @@
     clippy::trivial_regex
 )]
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use epub::doc::EpubDoc;
 use regex::Regex;
 use scraper::{Html, Selector};
@@ -14,11 +14,47 @@ use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str;
 
+mod abbreviations;
+mod chapters;
 mod custom_replacements;
+mod dates;
+mod extract;
+mod footnotes;
+mod french;
+mod german;
+mod heteronyms;
+mod hyphenation;
+mod language;
+mod lexicon;
+mod manifest;
+mod matter;
+mod numbers;
 mod replace_text;
+mod roman;
+mod spanish;
+mod spans;
+mod title_selection;
+mod toc;
+mod typography;
+mod units;
+mod vocabulary;
+
+use abbreviations::Abbreviations;
+use custom_replacements::{ChapterContext, Replacement};
+use dates::DateOrder;
+use extract::extract_text_from_html;
+use footnotes::{FootnoteMode, Footnotes};
+use heteronyms::Heteronyms;
+use language::Language;
+use lexicon::Lexicon;
+use matter::MatterCategory;
+use spans::SpanMode;
+use title_selection::{ChapterTitle, TitleCandidates, TitleSource};
+use typography::{DashStyle, Typography};
+use vocabulary::ReportFormat;
 
 fn get_title_from_section_tag(html: &str) -> String {
     let document = Html::parse_document(html);
@@ -48,23 +84,23 @@ fn get_title_from_title_tag(html: &str) -> String {
     title
 }
 
-/// Return if all the strings are the same (or empty)
+/// Returns the text of the first `<h1>`, or the first `<h2>` if there is no `<h1>`
 ///
-/// # arguments
-/// * `strings` - A Vector of strings to search
-fn all_strings_the_same(strings: &Vec<String>) -> bool {
-    if strings.is_empty() {
-        return true; // Empty vector is considered to have all strings the same
-    }
+/// # Arguments
+/// * `html` - The chapter html to search
+/// # Returns
+/// String of the heading text, empty if no heading was found
+fn get_title_from_heading_tag(html: &str) -> String {
+    let document = Html::parse_document(html);
 
-    let first_string = &strings[0];
-    for string in strings {
-        if string != first_string {
-            return false;
+    for heading in ["h1", "h2"] {
+        let selector = Selector::parse(heading).unwrap();
+        if let Some(element) = document.select(&selector).next() {
+            return element.text().collect::<String>();
         }
     }
 
-    true
+    String::new()
 }
 
 /// Outputs a string to a filename.
@@ -149,21 +185,6 @@ fn sanitize_filename(input: &str) -> String {
     re.replace_all(input, "_").to_string()
 }
 
-/// Extracts text stream from html
-///
-/// # Arguments
-/// * `html` - String to convert
-/// # Returns
-/// String of unfiltered text
-fn extract_text_from_html(html: &str) -> String {
-    let document = Html::parse_document(html);
-    let selector = Selector::parse("body").unwrap();
-    document
-        .select(&selector)
-        .flat_map(|element| element.text().collect::<Vec<_>>())
-        .collect::<String>()
-}
-
 /// Removes `<sup>N</sup>` tags (where N is one or more digits) from HTML.
 /// Footnote markers like `<sup class="calibre11">1</sup>` or `<sup>12</sup>` are stripped.
 ///
@@ -181,14 +202,13 @@ fn filter_sup_numbers(html: &str) -> String {
 /// # Arguments
 /// * `doc` - the epub document
 /// # Returns
-/// Vector of all the chapter titles (note can contain empty strings, if wasn't able to determine a
-/// chapter title.
-fn get_chapter_titles(doc: &mut EpubDoc<BufReader<File>>) -> Vec<String> {
+/// Vector of all the chapter titles and where they came from (note titles can be empty, if wasn't
+/// able to determine a chapter title.
+fn get_chapter_titles(doc: &mut EpubDoc<BufReader<File>>) -> Vec<ChapterTitle> {
     let number_of_ids = doc.spine.len();
-    let mut title_tag_titles: Vec<String> = Vec::new();
-    let mut section_tag_titles: Vec<String> = Vec::new();
-    let mut toc_titles: Vec<String> = Vec::new();
+    let mut candidates: Vec<TitleCandidates> = Vec::new();
     let spine = doc.spine.clone();
+    let toc_entries = toc::flatten_toc(&doc.toc);
 
     for (i, current_section) in spine.iter().enumerate() {
         let path = doc.resources[&current_section.idref].path.clone();
@@ -202,119 +222,271 @@ fn get_chapter_titles(doc: &mut EpubDoc<BufReader<File>>) -> Vec<String> {
             chapter_number, number_of_ids, current_section.idref, path_string,
         );
 
-        // Find matching TOC entries, otherwise push an empty string
-        let toc_title = doc
-            .toc
-            .iter()
-            .find(|toc| toc.content.to_string_lossy().contains(&path_string))
+        // Find matching TOC entries (at any depth), otherwise push an empty string
+        let toc_title = toc::entries_for_path(&toc_entries, &path_string)
+            .first()
             .map(|toc| toc.label.clone())
             .unwrap_or_default();
-
-        toc_titles.push(toc_title.clone());
         println!("  - Title from TOC Tag: <{toc_title}>");
 
         let title_tag_title = get_title_from_title_tag(html);
-        if title_tag_title.ne("Cover") {
-            title_tag_titles.push(title_tag_title.clone());
-            println!("  - Title from Title Tag: <{title_tag_title}>");
-        } else {
-            println!("  - Title from Title Tag: <{title_tag_title}> - ignoring");
-        }
+        println!("  - Title from Title Tag: <{title_tag_title}>");
 
         let section_tag_title = get_title_from_section_tag(html);
-        section_tag_titles.push(section_tag_title.clone());
-        println!("  - Title from Section Tag: <{section_tag_title}>\n");
+        println!("  - Title from Section Tag: <{section_tag_title}>");
+
+        let heading_title = get_title_from_heading_tag(html);
+        println!("  - Title from Heading Tag: <{heading_title}>\n");
+
+        candidates.push(TitleCandidates {
+            toc: title_selection::normalize_title(&toc_title),
+            title_tag: title_selection::normalize_title(&title_tag_title),
+            section_tag: title_selection::normalize_title(&section_tag_title),
+            heading: title_selection::normalize_title(&heading_title),
+        });
     }
 
     println!("Applying Rules to decide Title Source");
     println!("-------------------------------------\n");
 
-    if all_strings_the_same(&title_tag_titles) {
-        // Title tag is the same - don't use.
-        println!("Title Tags all the same or all empty, don't use");
+    for (source, score) in title_selection::rank_sources(&candidates) {
+        println!("  - {source:<7} score: {score:.2}");
     }
-    //dbg!(title_tag_titles);
 
-    if all_strings_the_same(&section_tag_titles) {
-        // Title tag is the same - don't use.
-        println!("Section Tags all the same or all empty, don't use");
+    let titles = title_selection::select_titles(&candidates);
+    for (i, title) in titles.iter().enumerate() {
+        println!(
+            "  Chapter {:>3}: {:<7} <{}>",
+            i + 1,
+            title.source,
+            title.title
+        );
+    }
+    titles
+}
+
+/// The built in rules and the user's files that change how the text is written out
+struct TextRules {
+    /// The user's regex replacements
+    custom_replacement_library: Option<Vec<Replacement>>,
+    /// The abbreviations to expand
+    abbreviations: Abbreviations,
+    /// The mappings for characters the TTS engine misreads
+    typography: Typography,
+    /// The user's pronunciations
+    lexicon: Lexicon,
+}
+
+/// Writes out everything the TTS engine would misread, using the built in changes for the
+/// language and then the custom replacements
+///
+/// # Arguments
+/// * `text` - the text to cleanse
+/// * `language` - the language the text is in
+/// * `chapter` - the chapter the text is from, for replacements limited to some chapters
+/// * `hits` - counts how often each custom replacement matched
+/// * `rules` - the abbreviations and the user's replacements
+/// * `options` - conversion settings from the command line
+/// # Returns
+/// The cleansed text
+fn cleanse_text(
+    text: &str,
+    language: Language,
+    chapter: &ChapterContext,
+    hits: &mut custom_replacements::Report,
+    rules: &TextRules,
+    options: &ConvertOptions,
+) -> String {
+    let abbreviations = rules.abbreviations.in_language(language);
+    let mut cleansed_text = language.normalize(text, &abbreviations, options.date_order);
+
+    // Perform Text Custom Replacements
+    if let Some(library) = &rules.custom_replacement_library {
+        cleansed_text =
+            custom_replacements::process_user_replacements(&cleansed_text, library, chapter, hits);
     }
-    println!("Hardcoded to use TOC Tags for now.");
-    //let titles = toc_titles;
-    toc_titles
-    //dbg!(section_tag_titles);
+
+    // Read numbers as words, now that money and custom replacements have been dealt with
+    language.convert_numbers_to_words(&cleansed_text)
 }
 
 /// Performs the final processing and outputting of files
 ///
+/// Spine documents holding several TOC chapters are split so each becomes its own file.  Untitled
+/// spine documents are optionally merged into the chapter before them.  The chapter hierarchy from
+/// the TOC is written to `chapters.json`.  Front and back matter (cover, copyright, index...) is
+/// quarantined in a `skipped` directory or dropped.
+///
 /// # Arguments
 /// * `doc` - the epub document
 /// * `titles` - all the chapter titles
 /// * `output_directory` - directory to write to.
+/// * `rules` - the built in rules and the user's replacements, mappings and pronunciations
+/// * `language` - the language the book is read in
+/// * `options` - conversion settings from the command line
 /// # Returns nothing
+#[allow(clippy::too_many_lines)]
 fn convert_book(
     doc: &mut EpubDoc<BufReader<File>>,
-    titles: Vec<String>,
+    titles: Vec<ChapterTitle>,
     output_directory: &str,
-    custom_replacement_library: Option<Vec<(String, String)>>,
+    rules: &TextRules,
+    language: Language,
+    options: &ConvertOptions,
 ) {
-    let number_of_ids = doc.spine.len();
-    let spine = doc.spine.clone();
+    let mut chapters = chapters::build_chapters(doc, &titles);
 
-    for (i, current_section) in spine.iter().enumerate() {
-        let path = doc.resources[&current_section.idref].path.clone();
-        let text = doc.get_resource_by_path(&path).unwrap();
-        let html = str::from_utf8(&text).unwrap();
+    println!("\nDetecting front and back matter");
+    let landmarks = matter::read_landmarks(doc);
+    matter::classify_chapters(&mut chapters, &landmarks);
+
+    println!("\nResolving footnotes");
+    let footnotes = Footnotes::collect(doc, language);
+    println!("Found {} footnotes", footnotes.len());
+    println!();
+
+    if options.merge_untitled {
+        chapters = chapters::merge_untitled_chapters(chapters);
+    }
+    let number_of_chapters = chapters.len();
+    let mut manifest_chapters: Vec<(Vec<String>, String)> = Vec::new();
+    let skipped_directory = output_directory.to_owned() + "/skipped";
+    let mut typography_report = typography::Report::default();
+    let mut vocabulary_report = vocabulary::Report::new(rules.lexicon.words());
+    let mut replacement_hits = custom_replacements::Report::default();
+
+    for (i, chapter) in chapters.iter().enumerate() {
         let chapter_number = i + 1;
-        let title = &titles[i];
+        let title = &chapter.title;
+
+        let skip = chapter
+            .matter
+            .filter(|category| options.skips(*category) && options.matter_mode != MatterMode::Keep);
+        if let Some(category) = skip
+            && options.matter_mode == MatterMode::Drop
+        {
+            println!(
+                "Skipping   Chapter {:>3}/{}: {:<21} Matter: {}",
+                chapter_number, number_of_chapters, chapter.idref, category
+            );
+            continue;
+        }
+
+        // Quarantined chapters are written in full to the skipped directory so they can be moved
+        // back if they were wanted after all
+        let output_directory = if skip.is_some() {
+            create_directory_structure(skipped_directory.clone());
+            skipped_directory.as_str()
+        } else {
+            output_directory
+        };
 
-        let title_to_use = if title.len() > 2 {
-            title
+        let usable_title = title_selection::is_usable(title);
+        let (title_to_use, title_source) = if usable_title {
+            if options.flat_titles {
+                (title.clone(), chapter.title_source)
+            } else {
+                (chapter.full_title(), chapter.title_source)
+            }
         } else {
-            &current_section.idref
+            (chapter.idref.clone(), TitleSource::Idref)
         };
 
-        let filename = if title.len() > 2 {
+        let filename = if usable_title {
             format!("{:04}_{}", chapter_number, sanitize_filename(title))
         } else {
             format!(
                 "{:04}_{}",
                 chapter_number,
-                sanitize_filename(&current_section.idref)
+                sanitize_filename(&chapter.idref)
             )
         };
 
+        let action = if skip.is_some() {
+            "Quarantine"
+        } else {
+            "Converting"
+        };
         println!(
-            "Converting Chapter {:>3}/{}: {:<21} Title Source: TOC    Filename: {}",
-            chapter_number, number_of_ids, current_section.idref, filename
+            "{} Chapter {:>3}/{}: {:<21} Title Source: {:<7} Filename: {}",
+            action, chapter_number, number_of_chapters, chapter.idref, title_source, filename
         );
 
         output_to_file(
             output_directory.to_owned() + "/" + &filename + ".title",
-            title_to_use,
+            &title_to_use,
         );
 
+        if skip.is_none() {
+            let mut hierarchy = chapter.parents.clone();
+            hierarchy.push(if usable_title {
+                title.clone()
+            } else {
+                chapter.idref.clone()
+            });
+            manifest_chapters.push((hierarchy, filename.clone()));
+        }
+
         output_to_file(
             output_directory.to_owned() + "/HTML/" + &filename + ".html",
-            html,
+            &chapter
+                .documents
+                .iter()
+                .map(|document| document.html.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
         );
 
         // Write the original text un changed into the original-text directory
-        let html = filter_sup_numbers(html);
-        let text = extract_text_from_html(&html);
+        let mut notes = Vec::new();
+        let text = chapter
+            .documents
+            .iter()
+            .map(|document| {
+                let html = footnotes.process_html(
+                    &document.html,
+                    &document.path,
+                    options.footnote_mode,
+                    &mut notes,
+                );
+                extract_text_from_html(&filter_sup_numbers(&html), language)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = footnotes::place_notes(&text, &notes, options.footnote_mode);
         output_to_file(
             output_directory.to_owned() + "/original-text/" + &filename + ".txt",
-            &text,
+            &spans::strip_markers(&text),
         );
 
-        // Cleanse the original-text using built in changes
-        let mut cleansed_text = replace_text::clean_text(&text);
-        cleansed_text = replace_text::convert_money_to_words(&cleansed_text);
-        cleansed_text = replace_text::convert_speed_from_acronyms_to_full_text(&cleansed_text);
+        // Cleanse the original-text, reading passages quoted in another language with its rules
+        let text = rules.typography.normalize(&text);
+        typography_report.record(&text);
+        let cleansed_text = replace_text::clean_text(&text);
+        let context = ChapterContext {
+            number: chapter_number,
+            title,
+            idref: &chapter.idref,
+        };
+        let (cleansed_text, language_spans) = spans::read_spans(
+            &cleansed_text,
+            language,
+            options.span_mode,
+            &rules.lexicon,
+            |text, language| {
+                cleanse_text(
+                    text,
+                    language,
+                    &context,
+                    &mut replacement_hits,
+                    rules,
+                    options,
+                )
+            },
+        );
 
-        // Perform Text Custom Replacements
-        if let Some(ref library) = custom_replacement_library {
-            cleansed_text = custom_replacements::process_user_replacements(&cleansed_text, library);
+        if skip.is_none() && options.oov_report.is_some() {
+            vocabulary_report.record(&cleansed_text, &filename);
         }
 
         // Write the cleansed text to the root output directory
@@ -322,7 +494,65 @@ fn convert_book(
             output_directory.to_owned() + "/" + &filename + ".txt",
             &cleansed_text,
         );
+        if options.span_mode == SpanMode::Voices {
+            output_to_file(
+                output_directory.to_owned() + "/" + &filename + ".spans.json",
+                &serde_json::to_string_pretty(&language_spans).unwrap(),
+            );
+        }
     }
+
+    if !typography_report.is_empty() {
+        println!(
+            "\nFound {} characters with no typography mapping, see typography-report.txt",
+            typography_report.len()
+        );
+        output_to_file(
+            output_directory.to_owned() + "/typography-report.txt",
+            &typography_report.to_text(),
+        );
+    }
+
+    if let Some(library) = &rules.custom_replacement_library {
+        println!(
+            "\nCustom replacement hits, see replacements-report.json\n{}",
+            replacement_hits.to_text(library)
+        );
+        let unused = replacement_hits.unused(library);
+        if unused > 0 {
+            println!("{unused} custom replacements never matched\n");
+        }
+        output_to_file(
+            output_directory.to_owned() + "/replacements-report.json",
+            &replacement_hits.to_json(library),
+        );
+    }
+
+    if let Some(format) = options.oov_report {
+        println!(
+            "Found {} words that are not in the English word list, see {}",
+            vocabulary_report.len(),
+            format.filename()
+        );
+        output_to_file(
+            output_directory.to_owned() + "/" + format.filename(),
+            &vocabulary_report.to_text(format),
+        );
+    }
+
+    // Write the chapter hierarchy for players that support nested chapters
+    let manifest = manifest::Manifest {
+        title: doc.get_title().unwrap_or_default(),
+        author: doc
+            .mdata("creator")
+            .map(|creator| creator.value.clone())
+            .unwrap_or_default(),
+        chapters: manifest::build_entries(&manifest_chapters),
+    };
+    output_to_file(
+        output_directory.to_owned() + "/chapters.json",
+        &serde_json::to_string_pretty(&manifest).unwrap(),
+    );
 }
 
 /// Create a bash script to provide environment variables for later steps
@@ -349,6 +579,8 @@ fn create_bash_environment(output_directory: &str, title: &str, author: &str) {
 #[derive(Debug)]
 enum Epub2AudiobookError {
     EPUBDoesNotExist,
+    /// The number of lines in the custom replacements file that can't be used
+    InvalidReplacements(usize),
 }
 
 impl fmt::Display for Epub2AudiobookError {
@@ -357,10 +589,69 @@ impl fmt::Display for Epub2AudiobookError {
             Self::EPUBDoesNotExist => {
                 write!(f, "EPUB does not exist")
             }
+            Self::InvalidReplacements(count) => {
+                write!(f, "{count} invalid custom replacements")
+            }
         }
     }
 }
 
+/// What to do with chapters that are classified as front or back matter to skip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum MatterMode {
+    /// Write them to the `skipped` directory instead of the output directory
+    #[default]
+    Quarantine,
+    /// Don't write them at all
+    Drop,
+    /// Convert them like any other chapter
+    Keep,
+}
+
+/// Settings that change how a book is converted
+#[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct ConvertOptions {
+    /// What to do with front and back matter
+    matter_mode: MatterMode,
+    /// Categories to convert even though they are skipped by default
+    include_matter: Vec<MatterCategory>,
+    /// Categories to skip even though they are converted by default
+    exclude_matter: Vec<MatterCategory>,
+    /// Attach spine documents without a title to the previous titled chapter
+    merge_untitled: bool,
+    /// Only write the chapter's own TOC label to .title files, not the parts it is nested in
+    flat_titles: bool,
+    /// How footnotes and endnotes are read
+    footnote_mode: FootnoteMode,
+    /// Whether all-number dates are month or day first
+    date_order: DateOrder,
+    /// The language to read the book in, instead of the one in its metadata
+    language: Option<Language>,
+    /// How passages quoted in another language are marked
+    span_mode: SpanMode,
+    /// What dashes between words are read as
+    dashes: DashStyle,
+    /// Pronunciation lexicons, as well as lexicon.pls and lexicon.conf
+    lexicons: Vec<String>,
+    /// Choose the reading of words such as "read" and "lead" from the words around them
+    heteronyms: bool,
+    /// Report the words that aren't in the English word list, in this format
+    oov_report: Option<ReportFormat>,
+    /// Custom replacements files, applied after the custom-replacements.conf files found
+    replacements: Vec<String>,
+}
+
+impl ConvertOptions {
+    /// Returns true if chapters of this category should not be converted
+    fn skips(&self, category: MatterCategory) -> bool {
+        if self.exclude_matter.contains(&category) {
+            return true;
+        }
+        category.skipped_by_default() && !self.include_matter.contains(&category)
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(
     name = "epub2audiobook",
@@ -368,11 +659,71 @@ impl fmt::Display for Epub2AudiobookError {
     about = "Convert an EPUB into chapterized text files for TTS processing",
     long_about = None
 )]
+#[allow(clippy::struct_excessive_bools)]
 struct Cli {
-    #[arg(value_name = "EPUB_FILE")]
-    epub_filename: String,
-    #[arg(value_name = "OUTPUT_DIR")]
-    output_directory: String,
+    #[arg(
+        value_name = "EPUB_FILE",
+        required_unless_present = "check_replacements"
+    )]
+    epub_filename: Option<String>,
+    #[arg(
+        value_name = "OUTPUT_DIR",
+        required_unless_present = "check_replacements"
+    )]
+    output_directory: Option<String>,
+    /// Check every pattern and group reference in the custom replacements files, without
+    /// converting a book
+    #[arg(long)]
+    check_replacements: bool,
+    /// Apply the rules in this file after those in every custom-replacements.conf found (can be
+    /// repeated)
+    #[arg(long = "replacements", value_name = "FILE")]
+    replacements: Vec<String>,
+    /// Attach spine documents without a title to the previous titled chapter
+    #[arg(long)]
+    merge_untitled: bool,
+    /// Only write the chapter's own TOC label to .title files, not the parts it is nested in
+    #[arg(long)]
+    flat_titles: bool,
+    /// What to do with front and back matter such as the cover, copyright page and index
+    #[arg(long, value_enum, default_value_t = MatterMode::Quarantine)]
+    matter_mode: MatterMode,
+    /// Drop footnotes, read them after the paragraph that references them, or at the end of the
+    /// chapter
+    #[arg(long = "footnotes", value_enum, default_value_t = FootnoteMode::End)]
+    footnote_mode: FootnoteMode,
+    /// Read all-number dates such as 12/03/1998 month first (us) or day first (dmy)
+    #[arg(long, value_enum, default_value_t = DateOrder::Us)]
+    date_order: DateOrder,
+    /// Read numbers, money, units and abbreviations in this language instead of the one in the
+    /// book's metadata
+    #[arg(long, value_enum)]
+    language: Option<Language>,
+    /// Mark passages quoted in another language with SSML <lang> elements, or list them in a
+    /// .spans.json file per chapter so a multilingual TTS setup can switch voices
+    #[arg(long = "language-spans", value_enum, default_value_t = SpanMode::Plain)]
+    span_mode: SpanMode,
+    /// Read dashes between words as a comma or an ellipsis, or keep them
+    #[arg(long, value_enum, default_value_t = DashStyle::Comma)]
+    dashes: DashStyle,
+    /// Pronounce words from a W3C PLS lexicon or a simple `word==respelling` file (can be
+    /// repeated).  lexicon.pls and lexicon.conf in the current directory are always read.
+    #[arg(long = "lexicon", value_name = "FILE")]
+    lexicons: Vec<String>,
+    /// Choose the reading of words spelled the same but said differently, such as "read" and
+    /// "lead", from the words around them.  heteronyms.conf in the current directory is read too.
+    #[arg(long)]
+    heteronyms: bool,
+    /// List the words that aren't in the bundled English word list, with how often they are
+    /// found and the first chapter they are in, as a stub lexicon or custom replacements block
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    oov_report: Option<ReportFormat>,
+    /// Convert a category of front/back matter that is skipped by default (can be repeated)
+    #[arg(long, value_enum, value_name = "CATEGORY")]
+    include_matter: Vec<MatterCategory>,
+    /// Skip a category of front/back matter that is converted by default (can be repeated)
+    #[arg(long, value_enum, value_name = "CATEGORY")]
+    exclude_matter: Vec<MatterCategory>,
 }
 
 //
@@ -385,10 +736,81 @@ fn main() -> Result<(), Epub2AudiobookError> {
     println!("= EPUB to TXT Converter =");
     println!("=========================");
 
-    app(&cli.epub_filename, &cli.output_directory)
+    if cli.check_replacements {
+        return check_replacements(&custom_replacements::find_files(
+            cli.epub_filename.as_deref(),
+            cli.output_directory.as_deref(),
+            &cli.replacements,
+        ));
+    }
+
+    let options = ConvertOptions {
+        merge_untitled: cli.merge_untitled,
+        flat_titles: cli.flat_titles,
+        matter_mode: cli.matter_mode,
+        include_matter: cli.include_matter,
+        exclude_matter: cli.exclude_matter,
+        footnote_mode: cli.footnote_mode,
+        date_order: cli.date_order,
+        language: cli.language,
+        span_mode: cli.span_mode,
+        dashes: cli.dashes,
+        lexicons: cli.lexicons,
+        heteronyms: cli.heteronyms,
+        oov_report: cli.oov_report,
+        replacements: cli.replacements,
+    };
+
+    app(
+        &cli.epub_filename.unwrap(),
+        &cli.output_directory.unwrap(),
+        &options,
+    )
 }
 
-fn app(filename: &str, output_directory: &str) -> Result<(), Epub2AudiobookError> {
+/// Loads the custom replacements, printing each line that can't be used
+///
+/// # Arguments
+/// * `files` - The replacements files, in the order their rules are applied
+/// # Returns
+/// The replacements, None if there is no file, or an error if any line is invalid
+fn load_replacements(files: &[PathBuf]) -> Result<Option<Vec<Replacement>>, Epub2AudiobookError> {
+    custom_replacements::load_files(files).map_err(|errors| {
+        for error in &errors {
+            eprintln!("Custom Replacements Error - {error}");
+        }
+        Epub2AudiobookError::InvalidReplacements(errors.len())
+    })
+}
+
+/// Validates the custom replacements files without converting a book
+///
+/// # Arguments
+/// * `files` - The replacements files
+fn check_replacements(files: &[PathBuf]) -> Result<(), Epub2AudiobookError> {
+    let Some(replacements) = load_replacements(files)? else {
+        println!("No custom replacements found");
+        return Ok(());
+    };
+    println!("{} custom replacements are valid", replacements.len());
+    let mut sections: Vec<(&str, usize)> = Vec::new();
+    for replacement in &replacements {
+        match sections.last_mut() {
+            Some((section, count)) if *section == replacement.section() => *count += 1,
+            _ => sections.push((replacement.section(), 1)),
+        }
+    }
+    for (section, count) in sections.iter().filter(|(section, _)| !section.is_empty()) {
+        println!("  [{section}] {count}");
+    }
+    Ok(())
+}
+
+fn app(
+    filename: &str,
+    output_directory: &str,
+    options: &ConvertOptions,
+) -> Result<(), Epub2AudiobookError> {
     //let filename = &args[1];
     //let output_directory = &args[2];
 
@@ -397,6 +819,17 @@ fn app(filename: &str, output_directory: &str) -> Result<(), Epub2AudiobookError
         return Err(Epub2AudiobookError::EPUBDoesNotExist);
     }
 
+    // Check the custom replacements before anything is written, so a mistake doesn't stop the
+    // conversion part way through
+    let custom_replacement_library = load_replacements(&custom_replacements::find_files(
+        Some(filename),
+        Some(output_directory),
+        &options.replacements,
+    ))?;
+    if let Some(library) = &custom_replacement_library {
+        println!("\nFound {} custom text replacements\n", library.len());
+    }
+
     // Load the EPUB
     let doc = EpubDoc::new(filename);
     assert!(doc.is_ok());
@@ -412,7 +845,11 @@ fn app(filename: &str, output_directory: &str) -> Result<(), Epub2AudiobookError
     println!("Title: {}", title.clone().unwrap());
     println!("Author: {author}");
     println!("Number of Sections: {number_of_ids}");
-    println!("Number of Items in TOC: {number_of_toc}\n");
+    println!("Number of Items in TOC: {number_of_toc}");
+    let language = options
+        .language
+        .unwrap_or_else(|| language::detect(&mut doc));
+    println!("Language: {language}\n");
 
     // Save the book cover to the output directory
     save_cover(output_directory.to_string(), &mut doc);
@@ -429,18 +866,36 @@ fn app(filename: &str, output_directory: &str) -> Result<(), Epub2AudiobookError
     println!("\n\nConverting to Chapters");
     println!("----------------------\n");
 
-    let custom_replacement_library =
-        custom_replacements::load_custom_replacements("custom-replacements.conf");
-
-    if custom_replacement_library.is_some() {
-        println!("\nFound custom text replacement library\n");
+    let mut lexicon = Lexicon::new(if options.heteronyms {
+        Heteronyms::load("heteronyms.conf")
+    } else {
+        Heteronyms::default()
+    });
+    let default_lexicons = ["lexicon.pls", "lexicon.conf"]
+        .into_iter()
+        .filter(|filename| Path::new(filename).exists())
+        .map(String::from);
+    for filename in default_lexicons.chain(options.lexicons.iter().cloned()) {
+        lexicon.load(&filename);
+    }
+    if lexicon.len() > 0 {
+        println!("Found {} pronunciations\n", lexicon.len());
     }
 
+    let rules = TextRules {
+        custom_replacement_library,
+        abbreviations: Abbreviations::load("abbreviations.conf", language),
+        typography: Typography::load("typography.conf", options.dashes),
+        lexicon,
+    };
+
     convert_book(
         &mut doc,
         titles,
         output_directory,
-        custom_replacement_library,
+        &rules,
+        language,
+        options,
     );
 
     println!("\nDone.\n");
@@ -453,7 +908,11 @@ fn app(filename: &str, output_directory: &str) -> Result<(), Epub2AudiobookError
 #[test]
 fn invalid_filename_should_not_cause_app_to_panic() {
     let result = std::panic::catch_unwind(|| {
-        drop(app("filename-does-not-exist.epub", "/tmp/test-output"));
+        drop(app(
+            "filename-does-not-exist.epub",
+            "/tmp/test-output",
+            &ConvertOptions::default(),
+        ));
     });
 
     result.unwrap();
@@ -463,6 +922,24 @@ fn invalid_filename_should_not_cause_app_to_panic() {
 // TESTS
 // ************
 
+#[test]
+fn convert_options_skip_default_categories() {
+    let options = ConvertOptions::default();
+    assert!(options.skips(MatterCategory::Copyright));
+    assert!(!options.skips(MatterCategory::Dedication));
+}
+
+#[test]
+fn convert_options_include_and_exclude_matter() {
+    let options = ConvertOptions {
+        include_matter: vec![MatterCategory::Index],
+        exclude_matter: vec![MatterCategory::Dedication],
+        ..ConvertOptions::default()
+    };
+    assert!(!options.skips(MatterCategory::Index));
+    assert!(options.skips(MatterCategory::Dedication));
+}
+
 #[test]
 fn filter_sup_numbers_removes_simple_sup_number() {
     let html = r#"some text<sup class="calibre11">1</sup> more text"#;
@@ -563,6 +1040,27 @@ fn get_title_from_title_tag_returns_blank_if_section_tag_missing() {
     assert_eq!(get_title_from_title_tag(html), "");
 }
 
+#[test]
+fn get_title_from_heading_tag_prefers_h1() {
+    let html = "<html><body><h2>Second</h2><h1>First</h1></body></html>";
+    assert_eq!(get_title_from_heading_tag(html), "First");
+}
+
+#[test]
+fn get_title_from_heading_tag_falls_back_to_h2() {
+    let html = "<html><body><h2>CHAPTER III.<br/>
+A Caucus-Race and a Long Tale</h2><h3>Ignored</h3></body></html>";
+    assert_eq!(
+        get_title_from_heading_tag(html),
+        "CHAPTER III.\nA Caucus-Race and a Long Tale"
+    );
+}
+
+#[test]
+fn get_title_from_heading_tag_handles_empty_string() {
+    assert_eq!(get_title_from_heading_tag(""), "");
+}
+
 #[test]
 fn test_sanitize_filename_empty_string() {
     assert_eq!("", sanitize_filename(""));
@@ -580,15 +1078,6 @@ fn test_sanitize_filename_replace_space_with_underscore() {
     assert_eq!("/chapter_1", sanitize_filename("/chapter 1"));
 }
 
-#[test]
-fn test_all_strings_the_same() {
-    let strings: Vec<String> = vec!["one".to_string(), "two".to_string(), "three".to_string()];
-    assert!(!all_strings_the_same(&strings));
-
-    let strings: Vec<String> = vec!["one".to_string(), "one".to_string(), "one".to_string()];
-    assert!(all_strings_the_same(&strings));
-}
-
 #[test]
 fn test_loading_chapter_titles() {
     // Note: Alice in wonderland was obtained from Project Guttenberg (out of copywrite material) as a test book
@@ -622,5 +1111,42 @@ fn test_loading_chapter_titles() {
     let mut doc = doc.unwrap();
     let titles = get_chapter_titles(&mut doc);
 
-    assert_eq!(titles[4], "CHAPTER III. A Caucus-Race and a Long Tale");
+    assert_eq!(
+        titles[4].title,
+        "CHAPTER III. A Caucus-Race and a Long Tale"
+    );
+    assert_eq!(titles[4].source, TitleSource::Toc);
+    // The cover page has no title in any source
+    assert_eq!(titles[0].source, TitleSource::Idref);
+}
+
+#[test]
+fn cleansed_text_keeps_paragraph_breaks() {
+    let rules = TextRules {
+        custom_replacement_library: None,
+        abbreviations: Abbreviations::built_in(Language::English),
+        typography: Typography::new(DashStyle::Comma),
+        lexicon: Lexicon::new(Heteronyms::default()),
+    };
+    let text = extract_text_from_html(
+        "<h2>CHAPTER I.<br/>Down</h2><p>It was 5 o'clock. </p>\n\n\n<p>Dr. Smith left.</p>",
+        Language::English,
+    );
+    let text = replace_text::clean_text(&text);
+    let context = ChapterContext {
+        number: 1,
+        title: "Down",
+        idref: "ch1",
+    };
+    assert_eq!(
+        cleanse_text(
+            &text,
+            Language::English,
+            &context,
+            &mut custom_replacements::Report::default(),
+            &rules,
+            &ConvertOptions::default(),
+        ),
+        "CHAPTER ONE.\nDown\n\nIt was five o'clock.\n\nDoctor Smith left.\n"
+    );
 }
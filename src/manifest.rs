@@ -0,0 +1,109 @@
+use serde::Serialize;
+
+/// One level of the chapter hierarchy.  Levels that only exist in the TOC (eg a "Part One" entry
+/// with no text of its own) have no file.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub title: String,
+    pub file: Option<String>,
+    pub children: Vec<Self>,
+}
+
+/// Describes the converted book for players that support nested chapters
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub title: String,
+    pub author: String,
+    pub chapters: Vec<ManifestEntry>,
+}
+
+/// Builds the nested chapter tree from each chapter's TOC hierarchy.
+///
+/// # Arguments
+/// * `chapters` - `(hierarchy, file)` for every output chapter in reading order, where the
+///   hierarchy runs from the outermost TOC entry down to the chapter's own title
+/// # Returns
+/// Vector of the top level entries
+pub fn build_entries(chapters: &[(Vec<String>, String)]) -> Vec<ManifestEntry> {
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+
+    for (hierarchy, file) in chapters {
+        let Some((title, parents)) = hierarchy.split_last() else {
+            continue;
+        };
+
+        let mut level = &mut entries;
+        for parent in parents {
+            // Reuse the most recent entry at this level if it is the same parent
+            let reuse = level.last().is_some_and(|last| last.title == *parent);
+            if !reuse {
+                level.push(ManifestEntry {
+                    title: parent.clone(),
+                    file: None,
+                    children: Vec::new(),
+                });
+            }
+            level = &mut level.last_mut().unwrap().children;
+        }
+
+        level.push(ManifestEntry {
+            title: title.clone(),
+            file: Some(file.clone()),
+            children: Vec::new(),
+        });
+    }
+
+    entries
+}
+
+#[cfg(test)]
+fn hierarchy(labels: &[&str], file: &str) -> (Vec<String>, String) {
+    (
+        labels.iter().map(ToString::to_string).collect(),
+        file.to_string(),
+    )
+}
+
+#[test]
+fn build_entries_nests_chapters_under_their_part() {
+    let chapters = vec![
+        hierarchy(&["Part One"], "0001_Part_One"),
+        hierarchy(&["Part One", "Chapter 1"], "0002_Chapter_1"),
+        hierarchy(&["Part One", "Chapter 2"], "0003_Chapter_2"),
+        hierarchy(&["Part Two", "Chapter 3"], "0004_Chapter_3"),
+    ];
+    let entries = build_entries(&chapters);
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].file, Some("0001_Part_One".to_string()));
+    assert_eq!(entries[0].children.len(), 2);
+    assert_eq!(entries[0].children[1].title, "Chapter 2");
+    // Part Two has no file of its own
+    assert_eq!(entries[1].title, "Part Two");
+    assert_eq!(entries[1].file, None);
+    assert_eq!(entries[1].children[0].title, "Chapter 3");
+}
+
+#[test]
+fn build_entries_keeps_flat_books_flat() {
+    let chapters = vec![
+        hierarchy(&["One"], "0001_One"),
+        hierarchy(&["Two"], "0002_Two"),
+    ];
+    let entries = build_entries(&chapters);
+    assert_eq!(entries.len(), 2);
+    assert!(entries.iter().all(|entry| entry.children.is_empty()));
+}
+
+#[test]
+fn manifest_serializes_to_json() {
+    let manifest = Manifest {
+        title: "Book".to_string(),
+        author: "Author".to_string(),
+        chapters: build_entries(&[hierarchy(&["One"], "0001_One")]),
+    };
+    assert_eq!(
+        serde_json::to_string(&manifest).unwrap(),
+        r#"{"title":"Book","author":"Author","chapters":[{"title":"One","file":"0001_One","children":[]}]}"#
+    );
+}
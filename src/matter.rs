@@ -0,0 +1,528 @@
+use clap::ValueEnum;
+use epub::doc::EpubDoc;
+use regex::Regex;
+use scraper::{Html, Selector};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::chapters::{Chapter, starts_at_anchor};
+
+/// Kinds of front and back matter that are usually not worth converting to audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum MatterCategory {
+    Cover,
+    TitlePage,
+    Copyright,
+    Toc,
+    Dedication,
+    Epigraph,
+    Foreword,
+    Preface,
+    Acknowledgements,
+    AboutAuthor,
+    AlsoBy,
+    Appendix,
+    Glossary,
+    Bibliography,
+    Index,
+    Notes,
+    License,
+    Colophon,
+    /// Spine items marked `linear="no"`
+    NonLinear,
+}
+
+impl MatterCategory {
+    /// Returns true for categories that are skipped unless `--include-matter` asks for them
+    pub const fn skipped_by_default(self) -> bool {
+        matches!(
+            self,
+            Self::Cover
+                | Self::TitlePage
+                | Self::Copyright
+                | Self::Toc
+                | Self::AlsoBy
+                | Self::Bibliography
+                | Self::Index
+                | Self::License
+                | Self::Colophon
+                | Self::NonLinear
+        )
+    }
+
+    /// Maps an EPUB3 `epub:type` token or EPUB2 `<guide>` type to a category
+    fn from_epub_type(epub_type: &str) -> Option<Self> {
+        match epub_type.to_lowercase().as_str() {
+            "cover" => Some(Self::Cover),
+            "titlepage" | "title-page" | "halftitlepage" | "fulltitle" => Some(Self::TitlePage),
+            "copyright-page" | "copyright" => Some(Self::Copyright),
+            "toc" | "loi" | "lot" => Some(Self::Toc),
+            "dedication" => Some(Self::Dedication),
+            "epigraph" => Some(Self::Epigraph),
+            "foreword" => Some(Self::Foreword),
+            "preface" => Some(Self::Preface),
+            "acknowledgments" | "acknowledgements" => Some(Self::Acknowledgements),
+            "appendix" => Some(Self::Appendix),
+            "glossary" => Some(Self::Glossary),
+            "bibliography" => Some(Self::Bibliography),
+            "index" => Some(Self::Index),
+            "endnotes" | "rearnotes" | "footnotes" | "notes" => Some(Self::Notes),
+            "colophon" => Some(Self::Colophon),
+            _ => None,
+        }
+    }
+
+    /// Guesses the category from a chapter title
+    fn from_title(title: &str) -> Option<Self> {
+        let rules = [
+            (r"^cover$", Self::Cover),
+            (r"^(title page|half title)$", Self::TitlePage),
+            (r"^copyright", Self::Copyright),
+            (r"^(contents|table of contents)$", Self::Toc),
+            (r"^dedication$", Self::Dedication),
+            (r"^epigraph$", Self::Epigraph),
+            (r"^foreword$", Self::Foreword),
+            (r"^preface$", Self::Preface),
+            (r"^acknowledge?ments?$", Self::Acknowledgements),
+            (r"^about the authors?$", Self::AboutAuthor),
+            (
+                r"^(also by|other (books|titles) by|by the same author)",
+                Self::AlsoBy,
+            ),
+            (r"^appendix", Self::Appendix),
+            (r"^glossary$", Self::Glossary),
+            (
+                r"^(bibliography|references|further reading)$",
+                Self::Bibliography,
+            ),
+            (r"^index$", Self::Index),
+            (r"^(notes|endnotes)$", Self::Notes),
+            (r"project gutenberg(™)? license", Self::License),
+            (r"^colophon$", Self::Colophon),
+        ];
+
+        let title = title.trim().to_lowercase();
+        rules
+            .iter()
+            .find(|(pattern, _)| Regex::new(pattern).unwrap().is_match(&title))
+            .map(|(_, category)| *category)
+    }
+}
+
+impl fmt::Display for MatterCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        f.pad(&name)
+    }
+}
+
+/// A document (or part of one) that the epub's `<guide>` or landmarks nav says is front/back
+/// matter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Landmark {
+    pub path: String,
+    pub fragment: Option<String>,
+    pub category: MatterCategory,
+}
+
+/// Resolves an href relative to the directory of the file it was found in
+///
+/// # Arguments
+/// * `base` - The path of the file the href was found in
+/// * `href` - The (possibly relative) href
+/// # Returns
+/// `(path, fragment)` of the href inside the epub
+pub fn resolve_href(base: &str, href: &str) -> (String, Option<String>) {
+    let (href, fragment) = match href.split_once('#') {
+        Some((href, fragment)) if !fragment.is_empty() => (href, Some(fragment.to_string())),
+        Some((href, _)) => (href, None),
+        None => (href, None),
+    };
+
+    let mut parts: Vec<&str> = Path::new(base)
+        .parent()
+        .map(|parent| parent.to_str().unwrap_or_default())
+        .unwrap_or_default()
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    (parts.join("/"), fragment)
+}
+
+/// Reads the landmarks from html that has already been parsed
+///
+/// # Arguments
+/// * `document` - The OPF or EPUB3 nav document
+/// * `document_path` - Path of the document inside the epub, hrefs are relative to it
+/// * `selector` - Selects the elements holding the links
+/// * `type_attribute` - The attribute holding the landmark type
+fn landmarks_from(
+    document: &Html,
+    document_path: &str,
+    selector: &str,
+    type_attribute: &str,
+) -> Vec<Landmark> {
+    let selector = Selector::parse(selector).unwrap();
+    document
+        .select(&selector)
+        .filter_map(|element| {
+            let category = element
+                .attr(type_attribute)?
+                .split_whitespace()
+                .find_map(MatterCategory::from_epub_type)?;
+            let (path, fragment) = resolve_href(document_path, element.attr("href")?);
+            Some(Landmark {
+                path,
+                fragment,
+                category,
+            })
+        })
+        .collect()
+}
+
+/// Reads the EPUB2 `<guide>` from the OPF
+pub fn landmarks_from_guide(opf: &str, opf_path: &str) -> Vec<Landmark> {
+    let document = Html::parse_document(opf);
+    landmarks_from(&document, opf_path, "guide reference", "type")
+}
+
+/// Reads the EPUB3 landmarks `<nav>` from the navigation document
+pub fn landmarks_from_nav(nav: &str, nav_path: &str) -> Vec<Landmark> {
+    let document = Html::parse_document(nav);
+    let navs = Selector::parse("nav").unwrap();
+    document
+        .select(&navs)
+        .filter(|nav| {
+            nav.attr("epub:type")
+                .is_some_and(|t| t.split_whitespace().any(|t| t == "landmarks"))
+        })
+        .flat_map(|nav| {
+            let html = Html::parse_fragment(&nav.html());
+            landmarks_from(&html, nav_path, "a", "epub:type")
+        })
+        .collect()
+}
+
+/// Collects the landmarks from both the EPUB2 guide and the EPUB3 nav document
+///
+/// # Arguments
+/// * `doc` - the epub document
+pub fn read_landmarks(doc: &mut EpubDoc<BufReader<File>>) -> Vec<Landmark> {
+    let mut landmarks = Vec::new();
+
+    let opf_path = doc.root_file.clone();
+    if let Some(opf) = doc.get_resource_str_by_path(&opf_path) {
+        landmarks.extend(landmarks_from_guide(&opf, &opf_path.to_string_lossy()));
+    }
+
+    if let Some(nav_id) = doc.get_nav_id() {
+        let nav_path = doc.resources[&nav_id].path.clone();
+        if let Some(nav) = doc.get_resource_str_by_path(&nav_path) {
+            landmarks.extend(landmarks_from_nav(&nav, &nav_path.to_string_lossy()));
+        }
+    }
+
+    landmarks
+}
+
+/// Returns the category from the `epub:type` of the body or its sections
+fn category_from_content(html: &str) -> Option<MatterCategory> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse(r"body[epub\:type], body section[epub\:type]").unwrap();
+    document
+        .select(&selector)
+        .filter_map(|element| element.attr("epub:type"))
+        .flat_map(str::split_whitespace)
+        .find_map(MatterCategory::from_epub_type)
+}
+
+/// Returns true if the html has images but no text, eg a cover or plate
+fn is_image_only(html: &str) -> bool {
+    let document = Html::parse_document(html);
+    let body = Selector::parse("body").unwrap();
+    let images = Selector::parse("img, svg, image").unwrap();
+    let has_text = document
+        .select(&body)
+        .any(|body| body.text().any(|text| !text.trim().is_empty()));
+    !has_text && document.select(&images).next().is_some()
+}
+
+/// Returns true if the landmark points at the chapter.  A landmark with a fragment only matches
+/// the part of a split document that starts at that fragment, or a document that has no text
+/// before it.  A landmark for a whole document only matches its first part.
+fn landmark_matches(landmark: &Landmark, chapter: &Chapter) -> bool {
+    if landmark.path != chapter.path() {
+        return false;
+    }
+    match (&landmark.fragment, &chapter.anchor) {
+        (Some(fragment), Some(anchor)) => fragment == anchor,
+        (Some(fragment), None) => chapter
+            .documents
+            .first()
+            .is_some_and(|document| starts_at_anchor(&document.html, fragment)),
+        (None, _) => chapter.part == 0,
+    }
+}
+
+/// Works out whether a chapter is front or back matter
+///
+/// The epub's own guide/landmarks are trusted first, then `epub:type` in the content, then the
+/// spine `linear="no"` flag, and finally the chapter title.
+///
+/// # Arguments
+/// * `chapter` - The chapter to classify
+/// * `landmarks` - The landmarks read from the epub
+/// # Returns
+/// The category and a short description of why, or None for body matter
+pub fn classify(
+    chapter: &Chapter,
+    landmarks: &[Landmark],
+) -> Option<(MatterCategory, &'static str)> {
+    let landmark = landmarks
+        .iter()
+        .find(|landmark| landmark_matches(landmark, chapter));
+    if let Some(landmark) = landmark {
+        return Some((landmark.category, "landmark"));
+    }
+
+    let html = chapter
+        .documents
+        .first()
+        .map(|document| document.html.as_str())
+        .unwrap_or_default();
+    if let Some(category) = category_from_content(html) {
+        return Some((category, "epub:type"));
+    }
+
+    if !chapter.linear {
+        return Some((MatterCategory::NonLinear, "linear=\"no\""));
+    }
+
+    if let Some(category) = MatterCategory::from_title(&chapter.title) {
+        return Some((category, "title"));
+    }
+
+    let path = chapter.path().to_lowercase();
+    if (path.contains("cover") || chapter.idref.to_lowercase().contains("cover"))
+        && is_image_only(html)
+    {
+        return Some((MatterCategory::Cover, "image only"));
+    }
+
+    None
+}
+
+/// Sets the matter category of every chapter
+///
+/// # Arguments
+/// * `chapters` - The chapters to classify
+/// * `landmarks` - The landmarks read from the epub
+pub fn classify_chapters(chapters: &mut [Chapter], landmarks: &[Landmark]) {
+    for chapter in chapters {
+        if let Some((category, reason)) = classify(chapter, landmarks) {
+            println!("  - {:<25} {:<16} (from {reason})", chapter.idref, category);
+            chapter.matter = Some(category);
+        }
+    }
+}
+
+#[cfg(test)]
+fn chapter(path: &str, title: &str, html: &str) -> Chapter {
+    use crate::title_selection::TitleSource;
+    Chapter {
+        idref: "id".to_string(),
+        anchor: None,
+        part: 0,
+        linear: true,
+        documents: vec![crate::chapters::Document {
+            path: path.to_string(),
+            html: html.to_string(),
+        }],
+        title: title.to_string(),
+        title_source: TitleSource::Toc,
+        parents: Vec::new(),
+        matter: None,
+    }
+}
+
+#[test]
+fn resolve_href_handles_relative_paths() {
+    assert_eq!(
+        resolve_href("OEBPS/content.opf", "Text/copy.xhtml#top"),
+        ("OEBPS/Text/copy.xhtml".to_string(), Some("top".to_string()))
+    );
+    assert_eq!(
+        resolve_href("OEBPS/Nav/nav.xhtml", "../Text/index.xhtml"),
+        ("OEBPS/Text/index.xhtml".to_string(), None)
+    );
+    assert_eq!(
+        resolve_href("content.opf", "./cover.xhtml"),
+        ("cover.xhtml".to_string(), None)
+    );
+}
+
+#[test]
+fn landmarks_from_guide_reads_references() {
+    let opf = r#"<package><guide>
+        <reference type="cover" title="Cover" href="Text/cover.xhtml"/>
+        <reference type="text" title="Start" href="Text/ch1.xhtml"/>
+        <reference type="copyright-page" href="Text/copy.xhtml"/>
+        </guide></package>"#;
+    let landmarks = landmarks_from_guide(opf, "OEBPS/content.opf");
+    assert_eq!(landmarks.len(), 2);
+    assert_eq!(landmarks[0].path, "OEBPS/Text/cover.xhtml");
+    assert_eq!(landmarks[1].category, MatterCategory::Copyright);
+}
+
+#[test]
+fn landmarks_from_nav_only_reads_landmarks_nav() {
+    let nav = r#"<html><body>
+        <nav epub:type="toc"><ol><li><a href="index.xhtml">Index</a></li></ol></nav>
+        <nav epub:type="landmarks"><ol>
+        <li><a epub:type="bodymatter" href="ch1.xhtml">Start</a></li>
+        <li><a epub:type="index" href="index.xhtml#idx">Index</a></li>
+        </ol></nav></body></html>"#;
+    let landmarks = landmarks_from_nav(nav, "OEBPS/nav.xhtml");
+    assert_eq!(
+        landmarks,
+        vec![Landmark {
+            path: "OEBPS/index.xhtml".to_string(),
+            fragment: Some("idx".to_string()),
+            category: MatterCategory::Index,
+        }]
+    );
+}
+
+#[test]
+fn classify_uses_landmarks_first() {
+    let landmarks = vec![Landmark {
+        path: "OEBPS/a.xhtml".to_string(),
+        fragment: None,
+        category: MatterCategory::Copyright,
+    }];
+    let chapter = chapter("OEBPS/a.xhtml", "Index", "<p>text</p>");
+    assert_eq!(
+        classify(&chapter, &landmarks),
+        Some((MatterCategory::Copyright, "landmark"))
+    );
+}
+
+#[test]
+fn classify_reads_epub_type() {
+    let html = r#"<html><body><section epub:type="frontmatter dedication"><p>For Mum</p></section></body></html>"#;
+    let chapter = chapter("OEBPS/a.xhtml", "", html);
+    assert_eq!(
+        classify(&chapter, &[]),
+        Some((MatterCategory::Dedication, "epub:type"))
+    );
+}
+
+#[test]
+fn classify_flags_non_linear_items() {
+    let mut chapter = chapter("OEBPS/a.xhtml", "Chapter 1", "<p>text</p>");
+    chapter.linear = false;
+    assert_eq!(
+        classify(&chapter, &[]).map(|(category, _)| category),
+        Some(MatterCategory::NonLinear)
+    );
+}
+
+#[test]
+fn classify_falls_back_to_title() {
+    let classify_title = |title: &str| {
+        classify(&chapter("OEBPS/a.xhtml", title, "<p>text</p>"), &[]).map(|(category, _)| category)
+    };
+    assert_eq!(classify_title("Copyright"), Some(MatterCategory::Copyright));
+    assert_eq!(
+        classify_title("Also by Jane Doe"),
+        Some(MatterCategory::AlsoBy)
+    );
+    assert_eq!(
+        classify_title("THE FULL PROJECT GUTENBERG LICENSE"),
+        Some(MatterCategory::License)
+    );
+    assert_eq!(classify_title("INDEX"), Some(MatterCategory::Index));
+    assert_eq!(classify_title("Chapter 1: Index Cards"), None);
+}
+
+#[test]
+fn classify_detects_image_only_covers() {
+    let html = r#"<html><body><div><svg><image href="cover.jpg"/></svg></div></body></html>"#;
+    assert_eq!(
+        classify(&chapter("OEBPS/wrap0000.xhtml", "", html), &[]),
+        None
+    );
+    assert_eq!(
+        classify(&chapter("OEBPS/cover.xhtml", "", html), &[]),
+        Some((MatterCategory::Cover, "image only"))
+    );
+}
+
+#[test]
+fn matter_category_names_match_command_line() {
+    assert_eq!(MatterCategory::AboutAuthor.to_string(), "about-author");
+    assert_eq!(
+        MatterCategory::from_str("also-by", true),
+        Ok(MatterCategory::AlsoBy)
+    );
+}
+
+#[test]
+fn landmarks_only_match_the_part_they_point_at() {
+    let landmark = |fragment: Option<&str>| Landmark {
+        path: "OEBPS/a.xhtml".to_string(),
+        fragment: fragment.map(String::from),
+        category: MatterCategory::Index,
+    };
+    let html = r#"<body><h1>Chapter 30</h1><p>text</p><h1 id="idx">Index</h1></body>"#;
+    let whole = chapter("OEBPS/a.xhtml", "Chapter 30", html);
+    assert!(!landmark_matches(&landmark(Some("idx")), &whole));
+    assert!(landmark_matches(&landmark(None), &whole));
+
+    let mut first = chapter(
+        "OEBPS/a.xhtml",
+        "Chapter 30",
+        "<body><h1>Chapter 30</h1></body>",
+    );
+    first.anchor = Some("ch30".to_string());
+    let mut index = chapter(
+        "OEBPS/a.xhtml",
+        "Index",
+        r#"<body><h1 id="idx">Index</h1></body>"#,
+    );
+    index.anchor = Some("idx".to_string());
+    index.part = 1;
+    assert!(!landmark_matches(&landmark(Some("idx")), &first));
+    assert!(landmark_matches(&landmark(Some("idx")), &index));
+    assert!(landmark_matches(&landmark(None), &first));
+    assert!(!landmark_matches(&landmark(None), &index));
+    assert!(landmark_matches(
+        &landmark(Some("idx")),
+        &chapter(
+            "OEBPS/a.xhtml",
+            "",
+            r#"<body> <div id="idx">a, 1</div></body>"#
+        )
+    ));
+}
+
+#[test]
+fn appendices_are_converted_by_default() {
+    assert!(!MatterCategory::Appendix.skipped_by_default());
+    assert!(MatterCategory::Index.skipped_by_default());
+}
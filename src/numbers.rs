@@ -0,0 +1,587 @@
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Names of each power of a thousand, starting at a thousand
+const SCALES: [&str; 6] = [
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+/// Words before a four digit number that make it a year, eg "in 1904", "since 1066"
+const YEAR_CONTEXT_BEFORE: [&str; 36] = [
+    "in",
+    "since",
+    "until",
+    "till",
+    "by",
+    "during",
+    "circa",
+    "c",
+    "ca",
+    "year",
+    "early",
+    "late",
+    "mid",
+    "before",
+    "after",
+    "spring",
+    "summer",
+    "autumn",
+    "fall",
+    "winter",
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "ad",
+    "anno",
+    "domini",
+    "christmas",
+];
+
+/// Lower case words that can follow a year.  Any other lower case word after a four digit number
+/// makes it a quantity, eg "1500 men".
+const YEAR_CONTEXT_AFTER: [&str; 30] = [
+    "a", "an", "and", "as", "at", "became", "began", "brought", "but", "for", "had", "has", "he",
+    "his", "i", "is", "it", "its", "on", "onwards", "saw", "she", "that", "the", "their", "there",
+    "they", "was", "we", "when",
+];
+
+/// Converts a number to words, eg 1904 -> "one thousand nine hundred and four"
+///
+/// # Arguments
+/// * `number` - The number to convert
+/// # Returns
+/// The number in words
+pub fn cardinal(number: u64) -> String {
+    if number < 20 {
+        return ONES[usize::try_from(number).unwrap_or_default()].to_string();
+    }
+
+    // Split into groups of three digits, lowest first
+    let mut groups = Vec::new();
+    let mut remaining = number;
+    while remaining > 0 {
+        groups.push(remaining % 1000);
+        remaining /= 1000;
+    }
+
+    let mut words: Vec<String> = Vec::new();
+    for (i, group) in groups.iter().enumerate().rev() {
+        if *group == 0 {
+            continue;
+        }
+        let mut group_words = hundreds(*group);
+        // "one thousand and four"
+        if i == 0 && *group < 100 && !words.is_empty() {
+            group_words = format!("and {group_words}");
+        }
+        if i > 0 {
+            group_words = format!("{group_words} {}", SCALES[i - 1]);
+        }
+        words.push(group_words);
+    }
+    words.join(" ")
+}
+
+/// Converts a number below a thousand to words
+fn hundreds(number: u64) -> String {
+    let below_hundred = number % 100;
+    let tens = if below_hundred < 20 {
+        ONES[usize::try_from(below_hundred).unwrap_or_default()].to_string()
+    } else if below_hundred.is_multiple_of(10) {
+        TENS[usize::try_from(below_hundred / 10).unwrap_or_default()].to_string()
+    } else {
+        format!(
+            "{}-{}",
+            TENS[usize::try_from(below_hundred / 10).unwrap_or_default()],
+            ONES[usize::try_from(below_hundred % 10).unwrap_or_default()]
+        )
+    };
+
+    match (number / 100, below_hundred) {
+        (0, _) => tens,
+        (hundred, 0) => format!(
+            "{} hundred",
+            ONES[usize::try_from(hundred).unwrap_or_default()]
+        ),
+        (hundred, _) => format!(
+            "{} hundred and {tens}",
+            ONES[usize::try_from(hundred).unwrap_or_default()]
+        ),
+    }
+}
+
+/// Converts a number to an ordinal in words, eg 22 -> "twenty-second"
+///
+/// # Arguments
+/// * `number` - The number to convert
+/// # Returns
+/// The ordinal in words
+pub fn ordinal(number: u64) -> String {
+    let words = cardinal(number);
+    let split = words.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (start, last) = words.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        _ => match last.strip_suffix('y') {
+            Some(stem) => format!("{stem}ieth"),
+            None => format!("{last}th"),
+        },
+    };
+    format!("{start}{last}")
+}
+
+/// Converts a year to words the way it is spoken, eg 1904 -> "nineteen oh four",
+/// 1900 -> "nineteen hundred", 2004 -> "two thousand and four", 2024 -> "twenty twenty-four"
+///
+/// # Arguments
+/// * `year` - The year to convert
+/// # Returns
+/// The year in words
+pub fn year(year: u64) -> String {
+    match (year / 100, year % 100) {
+        _ if !(1000..=9999).contains(&year) => cardinal(year),
+        (_, _) if year.is_multiple_of(1000) || (2000..2010).contains(&year) => cardinal(year),
+        (century, 0) => format!("{} hundred", cardinal(century)),
+        (century, rest) if rest < 10 => format!("{} oh {}", cardinal(century), cardinal(rest)),
+        (century, rest) => format!("{} {}", cardinal(century), cardinal(rest)),
+    }
+}
+
+/// Makes the last word of a number plural, eg "nineteen sixty" -> "nineteen sixties"
+pub fn plural(words: &str) -> String {
+    match words.strip_suffix('y') {
+        Some(stem) => format!("{stem}ies"),
+        None => format!("{words}s"),
+    }
+}
+
+/// Reads each digit on its own, eg "007" -> "zero zero seven"
+fn digits(number: &str) -> String {
+    number
+        .chars()
+        .filter_map(|digit| digit.to_digit(10))
+        .map(|digit| ONES[digit as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Converts a whole number (possibly with comma grouping) to words, falling back to reading the
+/// digits for numbers with a leading zero or that are too big
+fn whole_number(number: &str) -> String {
+    let number = number.replace(',', "");
+    if number.len() > 1 && number.starts_with('0') {
+        return digits(&number);
+    }
+    number
+        .parse::<u64>()
+        .map_or_else(|_| digits(&number), cardinal)
+}
+
+/// Returns the word before `position`, in lower case, without any trailing full stop
+fn word_before(text: &str, position: usize) -> String {
+    text[..position]
+        .trim_end()
+        .rsplit(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_lowercase()
+}
+
+/// Returns the word after `position`
+fn word_after(text: &str, position: usize) -> &str {
+    text[position..]
+        .trim_start()
+        .split(|c: char| !c.is_alphabetic())
+        .next()
+        .unwrap_or_default()
+}
+
+/// An era after a number, eg "1066 AD"
+static ERA: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s?(AD|A\.D\.|BC|B\.C\.|BCE|CE)\b").unwrap());
+
+/// Decides whether a four digit number is a year or a quantity from the words around it
+fn is_year(text: &str, start: usize, end: usize) -> bool {
+    let Ok(number) = text[start..end].parse::<u64>() else {
+        return false;
+    };
+    if end - start != 4 || !(1000..2100).contains(&number) {
+        return false;
+    }
+
+    if ERA.is_match(&text[end..]) {
+        return true;
+    }
+    if YEAR_CONTEXT_BEFORE.contains(&word_before(text, start).as_str()) {
+        return true;
+    }
+
+    // "1500 men" is a quantity, but "1904 was" and "1904." are years
+    let after = word_after(text, end);
+    let next_is_word = text[end..].starts_with(' ') && !after.is_empty();
+    !(next_is_word && after.starts_with(char::is_lowercase) && !YEAR_CONTEXT_AFTER.contains(&after))
+}
+
+/// Converts the numbers in a text to words, reading each one as a year, ordinal, decimal, range,
+/// percentage, fraction, time or plain quantity depending on how it is written and the words
+/// around it.
+///
+/// # Arguments
+/// * `text` - The text to convert
+/// # Returns
+/// The text with numbers in words
+pub fn convert_numbers_to_words(text: &str) -> String {
+    // Times, eg 10:30 -> "ten thirty", 9:05 -> "nine oh five", 10:00 -> "ten o'clock"
+    let re = Regex::new(r"\b([01]?\d|2[0-3]):([0-5]\d)\b").unwrap();
+    let text = re.replace_all(text, |caps: &Captures| {
+        let hour = whole_number(&caps[1]);
+        match caps[2].parse::<u64>().unwrap_or_default() {
+            0 => format!("{hour} o'clock"),
+            minutes if minutes < 10 => format!("{hour} oh {}", cardinal(minutes)),
+            minutes => format!("{hour} {}", cardinal(minutes)),
+        }
+    });
+
+    // Year ranges, eg 1914–1918 or 1914-18
+    let re = Regex::new(r"\b(1\d{3}|20\d{2})\s?[-–—]\s?(1\d{3}|20\d{2}|\d{2})\b").unwrap();
+    let text = re.replace_all(&text, |caps: &Captures| {
+        let from = caps[1].parse::<u64>().unwrap_or_default();
+        let to = caps[2].parse::<u64>().unwrap_or_default();
+        if caps[2].len() == 2 {
+            format!("{} to {}", year(from), whole_number(&caps[2]))
+        } else if to > from {
+            format!("{} to {}", year(from), year(to))
+        } else {
+            caps[0].to_string()
+        }
+    });
+
+    // Identifiers such as phone numbers and ISBNs are read digit by digit, as are two groups of
+    // four digits that aren't a year range, eg 1234-5678
+    let re = Regex::new(r"\b\d+(?:-\d+)+\b").unwrap();
+    let text = re.replace_all(&text, |caps: &Captures| {
+        let groups: Vec<&str> = caps[0].split('-').collect();
+        if groups.len() > 2
+            || groups.iter().any(|group| group.len() > 4)
+            || groups.iter().all(|group| group.len() == 4)
+        {
+            groups
+                .iter()
+                .map(|group| digits(group))
+                .collect::<Vec<_>>()
+                .join(", ")
+        } else {
+            caps[0].to_string()
+        }
+    });
+
+    // Other ranges with an en dash, eg pages 10–20
+    let re = Regex::new(r"(\d)\s?[–—]\s?(\d)").unwrap();
+    let text = re.replace_all(&text, "$1 to $2");
+
+    // Decades, eg 1960s -> "nineteen sixties"
+    let re = Regex::new(r"\b(\d{3}0)'?s\b").unwrap();
+    let text = re.replace_all(&text, |caps: &Captures| {
+        plural(&year(caps[1].parse::<u64>().unwrap_or_default()))
+    });
+
+    // Ordinals, eg 1st, 22nd, 1,000th
+    let re = Regex::new(r"\b(\d{1,3}(?:,\d{3})+|\d+)(st|nd|rd|th)\b").unwrap();
+    let text = re.replace_all(&text, |caps: &Captures| {
+        caps[1]
+            .replace(',', "")
+            .parse::<u64>()
+            .map_or_else(|_| caps[0].to_string(), ordinal)
+    });
+
+    // Percentages, the number itself is converted below
+    let re = Regex::new(r"(\d)\s?%").unwrap();
+    let text = re.replace_all(&text, "$1 percent");
+
+    let text = convert_fractions(&text);
+
+    // Decimals, eg 3.14 -> "three point one four"
+    let re = Regex::new(r"\b(\d{1,3}(?:,\d{3})+|\d+)\.(\d+)\b").unwrap();
+    let text = re.replace_all(&text, |caps: &Captures| {
+        format!("{} point {}", whole_number(&caps[1]), digits(&caps[2]))
+    });
+
+    // Everything else is a year or a quantity, eg 1904 or 1,500
+    let re = Regex::new(r"\b\d{1,3}(?:,\d{3})+\b|\b\d+\b").unwrap();
+    let mut result = String::new();
+    let mut last = 0;
+    for number in re.find_iter(&text) {
+        // Leave dates the dates module didn't recognise as they are, eg 31/31/1990
+        if slash_joined(text[..number.start()].chars().rev())
+            || slash_joined(text[number.end()..].chars())
+        {
+            continue;
+        }
+        result.push_str(&text[last..number.start()]);
+        if is_year(&text, number.start(), number.end()) {
+            result.push_str(&year(number.as_str().parse().unwrap_or_default()));
+        } else {
+            result.push_str(&whole_number(number.as_str()));
+        }
+        last = number.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+/// Returns true if the characters start with a "/" and a digit, so the number next to them is
+/// part of a date or something else that isn't a fraction
+fn slash_joined(mut chars: impl Iterator<Item = char>) -> bool {
+    chars.next() == Some('/') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Converts fractions to words, eg ½, 2½, 3/4, 2 1/2
+fn convert_fractions(text: &str) -> String {
+    let re = Regex::new(r"(?:(\d+)(?:\s|-)?)?([½⅓⅔¼¾⅕⅖⅗⅘⅙⅚⅛⅜⅝⅞])").unwrap();
+    let text = re.replace_all(text, |caps: &Captures| {
+        let (numerator, denominator) = match &caps[2] {
+            "½" => (1, 2),
+            "⅓" => (1, 3),
+            "⅔" => (2, 3),
+            "¼" => (1, 4),
+            "¾" => (3, 4),
+            "⅕" => (1, 5),
+            "⅖" => (2, 5),
+            "⅗" => (3, 5),
+            "⅘" => (4, 5),
+            "⅙" => (1, 6),
+            "⅚" => (5, 6),
+            "⅛" => (1, 8),
+            "⅜" => (3, 8),
+            "⅝" => (5, 8),
+            _ => (7, 8),
+        };
+        mixed_fraction(
+            caps.get(1).map(|whole| whole.as_str()),
+            numerator,
+            denominator,
+        )
+    });
+    let re = Regex::new(r"\b(?:(\d+)\s)?(\d+(?:/\d+)+)\b").unwrap();
+    re.replace_all(&text, |caps: &Captures| {
+        let parts: Vec<u64> = caps[2].split('/').filter_map(|n| n.parse().ok()).collect();
+        match parts[..] {
+            // Leave dates such as 12/25/1990 and anything that isn't a simple fraction alone
+            [numerator, denominator] if (2..=100).contains(&denominator) => mixed_fraction(
+                caps.get(1).map(|whole| whole.as_str()),
+                numerator,
+                denominator,
+            ),
+            _ => caps[0].to_string(),
+        }
+    })
+    .to_string()
+}
+
+/// Converts a fraction, with an optional whole number, to words, eg "two and a half"
+fn mixed_fraction(whole: Option<&str>, numerator: u64, denominator: u64) -> String {
+    let fraction = match (numerator, denominator) {
+        (1, 2) if whole.is_some() => "a half".to_string(),
+        (1, 4) if whole.is_some() => "a quarter".to_string(),
+        (1, 2) => "one half".to_string(),
+        (1, 4) => "one quarter".to_string(),
+        (_, 2) => format!("{} halves", cardinal(numerator)),
+        (_, 4) => format!("{} quarters", cardinal(numerator)),
+        (1, _) => format!("one {}", ordinal(denominator)),
+        (_, _) => format!("{} {}", cardinal(numerator), plural(&ordinal(denominator))),
+    };
+    match whole {
+        Some(whole) => format!("{} and {fraction}", whole_number(whole)),
+        None => fraction,
+    }
+}
+
+#[test]
+fn cardinal_converts_numbers_to_words() {
+    assert_eq!(cardinal(0), "zero");
+    assert_eq!(cardinal(13), "thirteen");
+    assert_eq!(cardinal(40), "forty");
+    assert_eq!(cardinal(99), "ninety-nine");
+    assert_eq!(cardinal(100), "one hundred");
+    assert_eq!(cardinal(104), "one hundred and four");
+    assert_eq!(cardinal(1004), "one thousand and four");
+    assert_eq!(cardinal(1904), "one thousand nine hundred and four");
+    assert_eq!(cardinal(2_000_300), "two million three hundred");
+    assert_eq!(cardinal(1_000_000_000_000), "one trillion");
+}
+
+#[test]
+fn ordinal_converts_numbers_to_words() {
+    assert_eq!(ordinal(1), "first");
+    assert_eq!(ordinal(2), "second");
+    assert_eq!(ordinal(12), "twelfth");
+    assert_eq!(ordinal(20), "twentieth");
+    assert_eq!(ordinal(22), "twenty-second");
+    assert_eq!(ordinal(100), "one hundredth");
+    assert_eq!(ordinal(101), "one hundred and first");
+}
+
+#[test]
+fn year_is_read_in_pairs() {
+    assert_eq!(year(1904), "nineteen oh four");
+    assert_eq!(year(1066), "ten sixty-six");
+    assert_eq!(year(1900), "nineteen hundred");
+    assert_eq!(year(2000), "two thousand");
+    assert_eq!(year(2004), "two thousand and four");
+    assert_eq!(year(2024), "twenty twenty-four");
+}
+
+#[test]
+fn convert_numbers_uses_context_to_find_years() {
+    assert_eq!(
+        convert_numbers_to_words("In 1904 he moved."),
+        "In nineteen oh four he moved."
+    );
+    assert_eq!(
+        convert_numbers_to_words("It was 1904."),
+        "It was nineteen oh four."
+    );
+    assert_eq!(
+        convert_numbers_to_words("He led 1500 men."),
+        "He led one thousand five hundred men."
+    );
+    assert_eq!(
+        convert_numbers_to_words("The war ended in 1918 and 1,500 men came home."),
+        "The war ended in nineteen eighteen and one thousand five hundred men came home."
+    );
+    assert_eq!(
+        convert_numbers_to_words("Rome fell in 476 AD, Hastings was 1066 AD"),
+        "Rome fell in four hundred and seventy-six AD, Hastings was ten sixty-six AD"
+    );
+}
+
+#[test]
+fn convert_numbers_handles_ordinals_and_decades() {
+    assert_eq!(
+        convert_numbers_to_words("the 1st and 22nd of the 1960s"),
+        "the first and twenty-second of the nineteen sixties"
+    );
+}
+
+#[test]
+fn convert_numbers_handles_ranges() {
+    assert_eq!(
+        convert_numbers_to_words("the war of 1914–1918"),
+        "the war of nineteen fourteen to nineteen eighteen"
+    );
+    assert_eq!(
+        convert_numbers_to_words("from 1939-45"),
+        "from nineteen thirty-nine to forty-five"
+    );
+    assert_eq!(
+        convert_numbers_to_words("pages 10–20"),
+        "pages ten to twenty"
+    );
+    assert_eq!(
+        convert_numbers_to_words("call 1234-5678"),
+        "call one two three four, five six seven eight"
+    );
+}
+
+#[test]
+fn convert_numbers_handles_decimals_percentages_and_grouping() {
+    assert_eq!(
+        convert_numbers_to_words("3.14 is 45% of 1,000,000"),
+        "three point one four is forty-five percent of one million"
+    );
+    assert_eq!(
+        convert_numbers_to_words("1.25 million dollars"),
+        "one point two five million dollars"
+    );
+}
+
+#[test]
+fn convert_numbers_handles_fractions() {
+    assert_eq!(
+        convert_numbers_to_words("½ a cup, 2½ miles, 3/4 full, 2 1/3 parts and 5/8 inch"),
+        "one half a cup, two and a half miles, three quarters full, two and one third parts and five eighths inch"
+    );
+    assert_eq!(
+        convert_numbers_to_words("on 31/31/1990, a 3/4/5 triangle"),
+        "on 31/31/1990, a 3/4/5 triangle"
+    );
+    assert_eq!(
+        convert_numbers_to_words(&crate::dates::convert_dates_to_words(
+            "on 12/25/1990",
+            crate::dates::DateOrder::Us
+        )),
+        "on December twenty-fifth, nineteen ninety"
+    );
+}
+
+#[test]
+fn convert_numbers_handles_times_and_leading_zeros() {
+    assert_eq!(
+        convert_numbers_to_words("at 10:00, 9:05 and 17:45 agent 007"),
+        "at ten o'clock, nine oh five and seventeen forty-five agent zero zero seven"
+    );
+}
+
+#[test]
+fn convert_numbers_reads_identifiers_digit_by_digit() {
+    assert_eq!(
+        convert_numbers_to_words("EIN 64-6221541, ISBN 978-0-14"),
+        "EIN six four, six two two one five four one, ISBN nine seven eight, zero, one four"
+    );
+}
+
+#[test]
+fn convert_numbers_leaves_words_with_digits_alone() {
+    assert_eq!(
+        convert_numbers_to_words("an MP3 of the B52s"),
+        "an MP3 of the B52s"
+    );
+}
@@ -1,68 +1,145 @@
-use regex::Regex;
+use regex::{Captures, Regex};
 
-pub fn convert_money_to_words(text: &str) -> String {
-    let re = Regex::new(r"\$1$").unwrap();
-    let search_text = re.replace_all(text, "one dollar").to_string();
+/// Names of a currency, and of its minor unit if amounts are written with one
+struct Currency {
+    singular: &'static str,
+    plural: &'static str,
+    minor: Option<(&'static str, &'static str)>,
+}
+
+const CENTS: Option<(&str, &str)> = Some(("cent", "cents"));
+
+fn currency(symbol: &str) -> Option<Currency> {
+    let (singular, plural, minor) = match symbol.trim() {
+        "$" => ("dollar", "dollars", CENTS),
+        "US$" | "USD" => ("US dollar", "US dollars", CENTS),
+        "A$" | "AU$" | "AUD" => ("Australian dollar", "Australian dollars", CENTS),
+        "C$" | "CA$" | "CAD" => ("Canadian dollar", "Canadian dollars", CENTS),
+        "NZ$" | "NZD" => ("New Zealand dollar", "New Zealand dollars", CENTS),
+        "HK$" | "HKD" => ("Hong Kong dollar", "Hong Kong dollars", CENTS),
+        "£" | "GBP" => ("pound", "pounds", Some(("penny", "pence"))),
+        "€" | "EUR" => ("euro", "euros", CENTS),
+        "¥" | "JPY" => ("yen", "yen", None),
+        "CNY" | "RMB" => ("yuan", "yuan", None),
+        "₹" | "INR" => ("rupee", "rupees", Some(("paisa", "paise"))),
+        "CHF" => ("Swiss franc", "Swiss francs", Some(("centime", "centimes"))),
+        "₽" | "RUB" => ("rouble", "roubles", Some(("kopek", "kopeks"))),
+        "₩" | "KRW" => ("won", "won", None),
+        _ => return None,
+    };
+    Some(Currency {
+        singular,
+        plural,
+        minor,
+    })
+}
+
+fn magnitude(word: &str) -> &str {
+    match word {
+        "k" | "K" => "thousand",
+        "m" | "M" | "mn" | "mln" => "million",
+        "b" | "B" | "bn" => "billion",
+        "t" | "T" | "tn" | "tr" => "trillion",
+        _ => word,
+    }
+}
 
-    let re = Regex::new(r"\$([1-9][\.]*[0-9]*\s(million|billion|trillion))").unwrap();
-    let search_text = re.replace_all(&search_text, "$1 dollars").to_string();
+/// Amounts of one are written as a word ("one dollar"), everything else is left as digits for
+/// `numbers::convert_numbers_to_words`
+fn count(amount: &str, singular: &str, plural: &str) -> String {
+    if amount == "1" {
+        format!("one {singular}")
+    } else {
+        format!("{amount} {plural}")
+    }
+}
 
-    let re = Regex::new(r"\$(?<m>[,0-9]+)").unwrap();
-    re.replace_all(&search_text, "$m dollars").to_string()
+fn amount_to_words(amount: &str, magnitude_word: Option<&str>, currency: &Currency) -> String {
+    if let Some(magnitude_word) = magnitude_word {
+        return format!("{amount} {} {}", magnitude(magnitude_word), currency.plural);
+    }
+
+    let (major, minor) = match (amount.split_once('.'), currency.minor) {
+        (Some((major, minor)), Some(names)) if minor.len() == 2 => (major, Some((minor, names))),
+        _ => (amount, None),
+    };
+    let major_words = count(major, currency.singular, currency.plural);
+    let Some((minor, (minor_singular, minor_plural))) = minor else {
+        return major_words;
+    };
+
+    let minor = minor.trim_start_matches('0');
+    let minor_words = count(minor, minor_singular, minor_plural);
+    match (major.trim_start_matches('0').is_empty(), minor.is_empty()) {
+        (_, true) => major_words,
+        (true, false) => minor_words,
+        (false, false) => format!("{major_words} and {minor_words}"),
+    }
+}
+
+pub fn convert_money_to_words(text: &str) -> String {
+    const AMOUNT: &str = r"(?<amount>\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?)";
+    const MAGNITUDE: &str = r"(?:\s(?<word>thousand|million|billion|trillion|quadrillion|quintillion|sextillion|septillion|octillion|nonillion|decillion)|(?<abbreviation>k|K|mn|mln|m|M|bn|b|B|tn|tr|t|T))?\b";
+    const CODES: &str = "USD|AUD|CAD|NZD|HKD|GBP|EUR|JPY|CNY|RMB|INR|CHF|RUB|KRW";
+
+    // Symbol or code before the amount, eg $5, US$5, £3.50, €2.3bn, USD 100
+    let re = Regex::new(&format!(
+        r"(?<symbol>(?:US|AU?|CA?|NZ|HK)?\$|£|€|¥|₹|₽|₩|\b(?:{CODES})\s?)\s?{AMOUNT}{MAGNITUDE}"
+    ))
+    .unwrap();
+    let search_text = re.replace_all(text, |caps: &Captures| {
+        let magnitude_word = caps.name("word").or_else(|| caps.name("abbreviation"));
+        currency(&caps["symbol"]).map_or_else(
+            || caps[0].to_string(),
+            |currency| {
+                amount_to_words(
+                    &caps["amount"],
+                    magnitude_word.map(|m| m.as_str()),
+                    &currency,
+                )
+            },
+        )
+    });
+
+    // Code after the amount, eg 3.50 EUR
+    let re = Regex::new(&format!(r"\b{AMOUNT}\s(?<symbol>{CODES})\b")).unwrap();
+    let search_text = re.replace_all(&search_text, |caps: &Captures| {
+        currency(&caps["symbol"]).map_or_else(
+            || caps[0].to_string(),
+            |currency| amount_to_words(&caps["amount"], None, &currency),
+        )
+    });
+
+    // Minor units on their own, eg 5p, 50¢
+    let re = Regex::new(r"\b(?<amount>\d+)(?:p\b|¢)").unwrap();
+    re.replace_all(&search_text, |caps: &Captures| {
+        if caps[0].ends_with('p') {
+            count(&caps["amount"], "penny", "pence")
+        } else {
+            count(&caps["amount"], "cent", "cents")
+        }
+    })
+    .to_string()
 }
 
 pub fn clean_text(text: &str) -> String {
     let re = Regex::new(r"@BRK#").unwrap();
     let search_text = re.replace_all(text, ".").to_string();
 
-    let re = Regex::new(r"\s*\n").unwrap();
+    let re = Regex::new(r"[^\S\n]+\n").unwrap();
     let search_text = re.replace_all(&search_text, "\n").to_string();
 
-    let re = Regex::new(r"\n+").unwrap();
+    // Keep the blank line between paragraphs, but no more than one
+    let re = Regex::new(r"\n{3,}").unwrap();
+    let search_text = re.replace_all(&search_text, "\n\n").to_string();
+
+    let re = Regex::new(r"\n+$").unwrap();
     let search_text = re.replace_all(&search_text, "\n").to_string();
 
     let re = Regex::new(r"^\n+").unwrap();
     re.replace_all(&search_text, "").to_string()
 }
 
-pub fn convert_speed_from_acronyms_to_full_text(text: &str) -> String {
-    // KPH
-    let re = Regex::new(r"kph").unwrap();
-    let search_text = re.replace_all(text, "kilometers per hour").to_string();
-
-    let re = Regex::new(r"k\.p\.h\.\n").unwrap();
-    let search_text = re
-        .replace_all(&search_text, "kilometers per hour.\n")
-        .to_string();
-
-    let re = Regex::new(r"k\.p\.h\.(\s+[A-Z])").unwrap();
-    let search_text = re
-        .replace_all(&search_text, "kilometers per hour.$1")
-        .to_string();
-
-    let re = Regex::new(r"k\.p\.h\.").unwrap();
-    let search_text = re
-        .replace_all(&search_text, "kilometers per hour")
-        .to_string();
-
-    // MPH
-    let re = Regex::new(r"mph").unwrap();
-    let search_text = re.replace_all(&search_text, "miles per hour").to_string();
-
-    let re = Regex::new(r"m\.p\.h\.\n").unwrap();
-    let search_text = re
-        .replace_all(&search_text, "miles per hour.\n")
-        .to_string();
-
-    let re = Regex::new(r"m\.p\.h\.(\s+[A-Z])").unwrap();
-    let search_text = re
-        .replace_all(&search_text, "miles per hour.$1")
-        .to_string();
-
-    let re = Regex::new(r"m\.p\.h\.").unwrap();
-    re.replace_all(&search_text, "miles per hour").to_string()
-}
-
 #[test]
 fn test_convert_money_to_words() {
     // Special Case for a singular
@@ -121,6 +198,65 @@ fn test_convert_money_to_words_multiple_times() {
     );
 }
 
+#[test]
+fn test_convert_money_to_words_singular_anywhere() {
+    let text = "it cost $1 and a $1 coin.";
+    assert_eq!(
+        "it cost one dollar and a one dollar coin.".to_string(),
+        convert_money_to_words(text)
+    );
+}
+
+#[test]
+fn test_convert_money_to_words_minor_units() {
+    assert_eq!(
+        "3 dollars and 50 cents".to_string(),
+        convert_money_to_words("$3.50")
+    );
+    assert_eq!(
+        "one dollar and one cent".to_string(),
+        convert_money_to_words("$1.01")
+    );
+    assert_eq!("99 cents".to_string(), convert_money_to_words("$0.99"));
+    assert_eq!(
+        "5 pounds and 20 pence".to_string(),
+        convert_money_to_words("£5.20")
+    );
+    assert_eq!(
+        "5 pence, one penny and 50 cents".to_string(),
+        convert_money_to_words("5p, 1p and 50¢")
+    );
+}
+
+#[test]
+fn test_convert_money_to_words_other_currencies() {
+    let text = "€20, ¥500, ₹10, US$5, A$2.50, USD 100 and 3.50 EUR";
+    assert_eq!(
+        "20 euros, 500 yen, 10 rupees, 5 US dollars, 2 Australian dollars and 50 cents, 100 US dollars and 3 euros and 50 cents".to_string(),
+        convert_money_to_words(text)
+    );
+}
+
+#[test]
+fn test_convert_money_to_words_magnitudes() {
+    assert_eq!(
+        "5 million dollars".to_string(),
+        convert_money_to_words("$5M")
+    );
+    assert_eq!(
+        "2.3 billion pounds".to_string(),
+        convert_money_to_words("£2.3bn")
+    );
+    assert_eq!(
+        "4 quadrillion dollars".to_string(),
+        convert_money_to_words("$4 quadrillion")
+    );
+    assert_eq!(
+        "10 thousand euros".to_string(),
+        convert_money_to_words("€10k")
+    );
+}
+
 #[test]
 fn should_strip_first_line_if_blank() {
     let text = "\n\ntest\n";
@@ -136,13 +272,15 @@ fn test_strip_blank_lines() {
 #[test]
 fn test_strip_whitespace_only_lines() {
     let text = "test\n \n\ntest\n";
-    assert_eq!(clean_text(text), "test\ntest\n".to_string());
+    assert_eq!(clean_text(text), "test\n\ntest\n".to_string());
 }
 
 #[test]
-fn should_leave_one_new_line_after_text() {
+fn should_keep_one_blank_line_between_paragraphs() {
     let text = "test\n\ntest";
-    assert_eq!(clean_text(text), "test\ntest".to_string());
+    assert_eq!(clean_text(text), "test\n\ntest".to_string());
+    let text = "test\n\n\n\ntest\ntest";
+    assert_eq!(clean_text(text), "test\n\ntest\ntest".to_string());
 }
 
 #[test]
@@ -162,56 +300,3 @@ fn test_strip_spaces_at_end_of_line() {
     let text = "test \n";
     assert_eq!(clean_text(text), "test\n".to_string());
 }
-
-#[test]
-fn test_convert_speed_acronyms() {
-    let text = "kph";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "kilometers per hour".to_string()
-    );
-
-    let text = "k.p.h.";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "kilometers per hour".to_string()
-    );
-    // Check for m.p.h. being at end of sentence, and keep period
-    let text = "k.p.h. The";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "kilometers per hour. The".to_string()
-    );
-
-    // Check for m.p.h. being at end of a paragraph, and keep period
-    let text = "k.p.h.\n";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "kilometers per hour.\n".to_string()
-    );
-
-    let text = "mph";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "miles per hour".to_string()
-    );
-
-    let text = "m.p.h.";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "miles per hour".to_string()
-    );
-    // Check for m.p.h. being at end of sentence, and keep period
-    let text = "m.p.h. The";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "miles per hour. The".to_string()
-    );
-
-    // Check for m.p.h. being at end of a paragraph, and keep period
-    let text = "m.p.h.\n";
-    assert_eq!(
-        convert_speed_from_acronyms_to_full_text(text),
-        "miles per hour.\n".to_string()
-    );
-}
@@ -0,0 +1,283 @@
+use regex::Regex;
+
+use crate::numbers::{cardinal, ordinal};
+
+/// Words that number what follows them, so the numeral is read as a cardinal, eg "Chapter IX"
+/// -> "Chapter nine", "World War II" -> "World War two"
+const KEYWORDS: [&str; 18] = [
+    "act", "appendix", "article", "book", "canto", "chapter", "episode", "lesson", "level", "part",
+    "phase", "psalm", "scene", "section", "stage", "vol", "volume", "war",
+];
+
+/// Words that can follow a regnal "I", eg "Elizabeth I was crowned".  Anything else after a name
+/// and "I" is more likely the pronoun, eg "Alice I think".
+const AFTER_REGNAL_I: [&str; 12] = [
+    "and", "became", "died", "had", "in", "of", "reigned", "ruled", "was", "who", "whose", "'s",
+];
+
+/// Names of monarchs and popes numbered from one.  A numeral is only read as an ordinal after one
+/// of these, since after any other word "I" is the pronoun ("When I was young"), "V" a letter
+/// ("Type V engine") and "DC" or "MD" an abbreviation ("Washington DC").
+const REGNAL_NAMES: [&str; 40] = [
+    "Albert",
+    "Alexander",
+    "Alfonso",
+    "Anne",
+    "Benedict",
+    "Boniface",
+    "Carlos",
+    "Catherine",
+    "Charles",
+    "Clement",
+    "Constantine",
+    "Darius",
+    "Edward",
+    "Elizabeth",
+    "Ferdinand",
+    "Francis",
+    "Frederick",
+    "George",
+    "Gregory",
+    "Gustav",
+    "Henry",
+    "Innocent",
+    "Isabella",
+    "Ivan",
+    "James",
+    "John",
+    "Juan",
+    "Leo",
+    "Louis",
+    "Mary",
+    "Napoleon",
+    "Nicholas",
+    "Otto",
+    "Paul",
+    "Peter",
+    "Philip",
+    "Pius",
+    "Richard",
+    "Wilhelm",
+    "William",
+];
+
+/// Returns the value of a roman numeral, or None if it isn't a well formed one (so "MIX" is 1009,
+/// but "CIVIL" and "IIII" are not numerals)
+///
+/// # Arguments
+/// * `numeral` - upper case roman numeral
+pub fn roman_value(numeral: &str) -> Option<u64> {
+    let re = Regex::new(r"^M{0,3}(CM|CD|D?C{0,3})(XC|XL|L?X{0,3})(IX|IV|V?I{0,3})$").unwrap();
+    if numeral.is_empty() || !re.is_match(numeral) {
+        return None;
+    }
+
+    let value = |c: char| match c {
+        'I' => 1,
+        'V' => 5,
+        'X' => 10,
+        'L' => 50,
+        'C' => 100,
+        'D' => 500,
+        _ => 1000,
+    };
+    // Read from the right, so a smaller numeral before a bigger one is subtracted, eg IX
+    let mut total = 0;
+    let mut previous = 0;
+    for current in numeral.chars().rev().map(value) {
+        if current < previous {
+            total -= current;
+        } else {
+            total += current;
+            previous = current;
+        }
+    }
+    Some(total)
+}
+
+fn title_case(words: &str) -> String {
+    let mut result = String::new();
+    let mut capitalise = true;
+    for c in words.chars() {
+        if capitalise {
+            result.extend(c.to_uppercase());
+        } else {
+            result.push(c);
+        }
+        capitalise = c == ' ' || c == '-';
+    }
+    result
+}
+
+/// Converts roman numerals to words where the context makes it clear they are numbers: after a
+/// word such as "Chapter" or "War" they are cardinals ("Chapter nine"), after the name of a
+/// monarch or pope they are ordinals ("Henry the Eighth"), and a numeral on a line of its own is a
+/// heading ("nine.").  Anything else, such as the pronoun "I" or words like "MIX", is left alone.
+///
+/// # Arguments
+/// * `text` - The text to convert
+/// # Returns
+/// The text with roman numerals in words
+pub fn convert_roman_numerals(text: &str) -> String {
+    let re = Regex::new(r"\b[MDCLXVI]+\b").unwrap();
+    let initial_full_stop = Regex::new(r"^\.\s+[A-Z]").unwrap();
+
+    let mut result = String::new();
+    let mut last = 0;
+    for numeral in re.find_iter(text) {
+        let Some(value) = roman_value(numeral.as_str()) else {
+            continue;
+        };
+        let before = &text[..numeral.start()];
+        let after = &text[numeral.end()..];
+        if !before.is_empty() && !before.ends_with([' ', '\n']) {
+            continue;
+        }
+
+        let previous_word = before
+            .strip_suffix(' ')
+            .and_then(|before| before.split_whitespace().next_back())
+            .unwrap_or_default();
+        let next_word = after
+            .trim_start()
+            .split(|c: char| c.is_whitespace() || (c.is_ascii_punctuation() && c != '\''))
+            .next()
+            .unwrap_or_default();
+        let line_start = before.is_empty() || before.ends_with('\n');
+        let line_end = after
+            .trim_start_matches(['.', ':'])
+            .trim_end_matches(' ')
+            .is_empty()
+            || after.trim_start_matches(['.', ':']).starts_with('\n');
+
+        let words = if KEYWORDS.contains(&previous_word.to_lowercase().as_str()) {
+            // "the war I went to" is the pronoun, unlike "World War I." or "Book I"
+            let pronoun = numeral.as_str() == "I"
+                && previous_word.starts_with(char::is_lowercase)
+                && next_word.starts_with(char::is_lowercase);
+            if pronoun {
+                continue;
+            }
+            // Match the case of the keyword, eg "CHAPTER NINE"
+            if previous_word.chars().all(char::is_uppercase) {
+                cardinal(value).to_uppercase()
+            } else {
+                cardinal(value)
+            }
+        } else if line_start && line_end && numeral.as_str() != "I" {
+            cardinal(value)
+        } else if REGNAL_NAMES.contains(&previous_word) {
+            // Initials such as "John D. Rockefeller" aren't numbers
+            let followed_by_initial = initial_full_stop.is_match(after);
+            let pronoun = numeral.as_str() == "I"
+                && !next_word.is_empty()
+                && !AFTER_REGNAL_I.contains(&next_word);
+            if followed_by_initial || pronoun {
+                continue;
+            }
+            format!("the {}", title_case(&ordinal(value)))
+        } else {
+            continue;
+        };
+
+        result.push_str(&text[last..numeral.start()]);
+        result.push_str(&words);
+        last = numeral.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+#[test]
+fn roman_value_only_accepts_well_formed_numerals() {
+    assert_eq!(roman_value("IX"), Some(9));
+    assert_eq!(roman_value("VIII"), Some(8));
+    assert_eq!(roman_value("XIV"), Some(14));
+    assert_eq!(roman_value("MCMXCIX"), Some(1999));
+    assert_eq!(roman_value("MIX"), Some(1009));
+    assert_eq!(roman_value("CIVIL"), None);
+    assert_eq!(roman_value("IIII"), None);
+    assert_eq!(roman_value(""), None);
+}
+
+#[test]
+fn chapter_numerals_are_cardinals() {
+    assert_eq!(
+        convert_roman_numerals("CHAPTER IX.\nThe Mock Turtle’s Story"),
+        "CHAPTER NINE.\nThe Mock Turtle’s Story"
+    );
+    assert_eq!(
+        convert_roman_numerals("as told in Book III and World War II."),
+        "as told in Book three and World War two."
+    );
+}
+
+#[test]
+fn regnal_numerals_are_ordinals() {
+    assert_eq!(
+        convert_roman_numerals("Henry VIII married six times, Louis XIV built Versailles."),
+        "Henry the Eighth married six times, Louis the Fourteenth built Versailles."
+    );
+    assert_eq!(
+        convert_roman_numerals("Elizabeth I was crowned, as was Pope John XXIII."),
+        "Elizabeth the First was crowned, as was Pope John the Twenty-Third."
+    );
+}
+
+#[test]
+fn headings_on_their_own_line_are_cardinals() {
+    assert_eq!(convert_roman_numerals("IV.\nText"), "four.\nText");
+}
+
+#[test]
+fn pronouns_and_words_are_left_alone() {
+    let text = "I think, said Alice I know. MIX the CIVIL DID. Malcolm X and John D. Rockefeller";
+    assert_eq!(convert_roman_numerals(text), text);
+    assert_eq!(convert_roman_numerals("I\n"), "I\n");
+}
+
+#[test]
+fn the_pronoun_i_after_a_capitalised_word_is_left_alone() {
+    for text in [
+        "When I was young",
+        "Yesterday I had tea",
+        "and I'm I, and",
+        "Type V engine",
+        "Then I was",
+    ] {
+        assert_eq!(convert_roman_numerals(text), text);
+    }
+    assert_eq!(
+        convert_roman_numerals("King George V died"),
+        "King George the Fifth died"
+    );
+}
+
+#[test]
+fn the_pronoun_i_after_a_keyword_is_left_alone() {
+    for text in [
+        "After the war I went home.",
+        "the book I liked",
+        "the part I played",
+        "the scene I saw",
+    ] {
+        assert_eq!(convert_roman_numerals(text), text);
+    }
+    assert_eq!(
+        convert_roman_numerals("Book I begins, in Act I, after WAR I and the war I."),
+        "Book one begins, in Act one, after WAR ONE and the war one."
+    );
+}
+
+#[test]
+fn abbreviations_after_a_name_are_left_alone() {
+    for text in [
+        "Washington DC",
+        "John Smith MD",
+        "Send Bob CV",
+        "Gap XL",
+        "Captain Jones MC",
+    ] {
+        assert_eq!(convert_roman_numerals(text), text);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::abbreviations::Context;
+use crate::language::{Currency, Pack, Unit, currency, feminine, masculine};
+
+const ONES: [&str; 30] = [
+    "cero",
+    "uno",
+    "dos",
+    "tres",
+    "cuatro",
+    "cinco",
+    "seis",
+    "siete",
+    "ocho",
+    "nueve",
+    "diez",
+    "once",
+    "doce",
+    "trece",
+    "catorce",
+    "quince",
+    "dieciséis",
+    "diecisiete",
+    "dieciocho",
+    "diecinueve",
+    "veinte",
+    "veintiuno",
+    "veintidós",
+    "veintitrés",
+    "veinticuatro",
+    "veinticinco",
+    "veintiséis",
+    "veintisiete",
+    "veintiocho",
+    "veintinueve",
+];
+
+const TENS: [&str; 10] = [
+    "",
+    "",
+    "",
+    "treinta",
+    "cuarenta",
+    "cincuenta",
+    "sesenta",
+    "setenta",
+    "ochenta",
+    "noventa",
+];
+
+const HUNDREDS: [&str; 10] = [
+    "",
+    "ciento",
+    "doscientos",
+    "trescientos",
+    "cuatrocientos",
+    "quinientos",
+    "seiscientos",
+    "setecientos",
+    "ochocientos",
+    "novecientos",
+];
+
+const ORDINALS: [&str; 10] = [
+    "primero", "segundo", "tercero", "cuarto", "quinto", "sexto", "séptimo", "octavo", "noveno",
+    "décimo",
+];
+
+/// Returns 1 to 999 in words, eg 100 -> "cien", 101 -> "ciento uno"
+fn below_thousand(number: u64) -> String {
+    if number == 100 {
+        return "cien".to_string();
+    }
+    let hundreds = usize::try_from(number / 100).unwrap_or_default();
+    let rest = usize::try_from(number % 100).unwrap_or_default();
+    let tens = match rest {
+        0 => String::new(),
+        1..=29 => ONES[rest].to_string(),
+        _ if rest.is_multiple_of(10) => TENS[rest / 10].to_string(),
+        _ => format!("{} y {}", TENS[rest / 10], ONES[rest % 10]),
+    };
+    [HUNDREDS[hundreds], tens.as_str()]
+        .iter()
+        .filter(|words| !words.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// "Uno" is shortened in front of a noun, including "mil" and "millones", eg "veintiún mil"
+fn shorten(words: &str) -> String {
+    words.strip_suffix("veintiuno").map_or_else(
+        || {
+            words
+                .strip_suffix("uno")
+                .map_or_else(|| words.to_string(), |start| format!("{start}un"))
+        },
+        |start| format!("{start}veintiún"),
+    )
+}
+
+/// Returns 1 to 999,999 in words, eg 21000 -> "veintiún mil"
+fn below_million(number: u64) -> String {
+    let mut words = match number / 1000 {
+        0 => String::new(),
+        1 => "mil".to_string(),
+        thousands => format!("{} mil", shorten(&below_thousand(thousands))),
+    };
+    if !number.is_multiple_of(1000) {
+        if !words.is_empty() {
+            words.push(' ');
+        }
+        words.push_str(&below_thousand(number % 1000));
+    }
+    words
+}
+
+/// Returns a whole number in words, eg 1999 -> "mil novecientos noventa y nueve", 2000000 ->
+/// "dos millones"
+pub fn cardinal(number: u64) -> String {
+    if number == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut words: Vec<String> = Vec::new();
+    match number / 1_000_000 {
+        0 => {}
+        1 => words.push("un millón".to_string()),
+        millions => words.push(format!("{} millones", shorten(&below_million(millions)))),
+    }
+    if !number.is_multiple_of(1_000_000) {
+        words.push(below_million(number % 1_000_000));
+    }
+    words.join(" ")
+}
+
+/// Returns a count in front of a noun, which agrees with it, eg "un euro", "una hora",
+/// "doscientas libras"
+fn count(number: u64, feminine: bool) -> String {
+    let words = cardinal(number);
+    if feminine {
+        let words = words.replace("cientos", "cientas");
+        words
+            .strip_suffix("uno")
+            .map_or_else(|| words.clone(), |start| format!("{start}una"))
+    } else {
+        shorten(&words)
+    }
+}
+
+/// Endings of feminine nouns, eg "persona", "canción", "ciudad"
+const FEMININE_ENDINGS: [&str; 5] = ["a", "ión", "dad", "tad", "tud"];
+
+/// Nouns that end like feminine ones but are masculine
+const MASCULINE_NOUNS: [&str; 9] = [
+    "día", "mapa", "problema", "programa", "sistema", "tema", "millón", "avión", "camión",
+];
+
+/// Words that often follow a number but aren't nouns, eg "1 de cada 3"
+const NOT_NOUNS: [&str; 24] = [
+    "del", "por", "para", "con", "sin", "entre", "hasta", "desde", "sobre", "contra", "hacia",
+    "que", "más", "menos", "cada", "otra", "otro", "toda", "todo", "nada", "como", "pero", "son",
+    "era",
+];
+
+/// Returns whether a word is a feminine noun, eg "personas", or None if it isn't a noun
+fn feminine_noun(word: &str) -> Option<bool> {
+    let word = word.to_lowercase();
+    let singular = word
+        .strip_suffix("es")
+        .filter(|singular| singular.ends_with(['d', 'n']))
+        .or_else(|| word.strip_suffix('s'))
+        .unwrap_or(&word);
+    if word.chars().count() < 3 || NOT_NOUNS.contains(&singular) {
+        return None;
+    }
+    // "canciones" is "canción"
+    let singular = singular.replace("ion", "ión");
+    Some(
+        !MASCULINE_NOUNS.contains(&singular.as_str())
+            && FEMININE_ENDINGS
+                .iter()
+                .any(|ending| singular.ends_with(ending)),
+    )
+}
+
+/// Returns an ordinal in words, eg "1.º" -> "primero", "3ª" -> "tercera".  Ordinals above
+/// tenth are rarely spoken, so they are read as cardinals.
+fn ordinal(number: u64, suffix: &str) -> String {
+    let feminine = suffix.ends_with('ª');
+    match number {
+        1..=10 => {
+            let words = ORDINALS[usize::try_from(number - 1).unwrap_or_default()];
+            if feminine {
+                format!("{}a", &words[..words.len() - 1])
+            } else {
+                words.to_string()
+            }
+        }
+        _ => count(number, feminine),
+    }
+}
+
+const CURRENCIES: [Currency; 5] = [
+    currency(&["€", "EUR"], ["euro", "euros"], ["céntimo", "céntimos"]),
+    currency(&["$", "USD"], ["dólar", "dólares"], ["centavo", "centavos"]),
+    Currency {
+        feminine: true,
+        ..currency(&["£", "GBP"], ["libra", "libras"], ["penique", "peniques"])
+    },
+    currency(&["CHF"], ["franco", "francos"], ["céntimo", "céntimos"]),
+    currency(&["¥", "JPY"], ["yen", "yenes"], ["sen", "sen"]),
+];
+
+const UNITS: [Unit; 24] = [
+    masculine(&["km/h"], "kilómetro por hora", "kilómetros por hora"),
+    masculine(&["m/s"], "metro por segundo", "metros por segundo"),
+    masculine(&["mm"], "milímetro", "milímetros"),
+    masculine(&["cm"], "centímetro", "centímetros"),
+    masculine(&["m"], "metro", "metros"),
+    masculine(&["km"], "kilómetro", "kilómetros"),
+    masculine(&["m²"], "metro cuadrado", "metros cuadrados"),
+    masculine(&["km²"], "kilómetro cuadrado", "kilómetros cuadrados"),
+    feminine(&["ha"], "hectárea", "hectáreas"),
+    masculine(&["m³"], "metro cúbico", "metros cúbicos"),
+    masculine(&["ml", "mL"], "mililitro", "mililitros"),
+    masculine(&["l", "L"], "litro", "litros"),
+    masculine(&["mg"], "miligramo", "miligramos"),
+    masculine(&["g"], "gramo", "gramos"),
+    masculine(&["kg"], "kilogramo", "kilogramos"),
+    feminine(&["t"], "tonelada", "toneladas"),
+    masculine(&["°C", "ºC"], "grado Celsius", "grados Celsius"),
+    masculine(&["°"], "grado", "grados"),
+    feminine(&["h"], "hora", "horas"),
+    masculine(&["min"], "minuto", "minutos"),
+    masculine(&["MB"], "megabyte", "megabytes"),
+    masculine(&["GB"], "gigabyte", "gigabytes"),
+    masculine(&["kWh"], "kilovatio hora", "kilovatios hora"),
+    masculine(&["kW"], "kilovatio", "kilovatios"),
+];
+
+const ABBREVIATIONS: [(&str, &str, Context); 21] = [
+    ("Sr.", "señor", Context::BeforeName),
+    ("Sra.", "señora", Context::BeforeName),
+    ("Srta.", "señorita", Context::BeforeName),
+    ("Sres.", "señores", Context::BeforeName),
+    ("Dña.", "doña", Context::BeforeName),
+    ("Dr.", "doctor", Context::BeforeName),
+    ("Dra.", "doctora", Context::BeforeName),
+    ("Ud.", "usted", Context::Always),
+    ("Uds.", "ustedes", Context::Always),
+    ("Vd.", "usted", Context::Always),
+    ("etc.", "etcétera", Context::Always),
+    ("p. ej.", "por ejemplo", Context::Leading),
+    ("aprox.", "aproximadamente", Context::Leading),
+    ("pág.", "página", Context::BeforeNumber),
+    ("núm.", "número", Context::BeforeNumber),
+    ("n.º", "número", Context::BeforeNumber),
+    ("cap.", "capítulo", Context::BeforeNumber),
+    ("a. C.", "antes de Cristo", Context::Always),
+    ("d. C.", "después de Cristo", Context::Always),
+    ("Avda.", "avenida", Context::BeforeName),
+    ("Sto.", "santo", Context::BeforeName),
+];
+
+/// Spanish number words, currencies, units and abbreviations
+pub const PACK: Pack = Pack {
+    cardinal,
+    count,
+    feminine_noun,
+    year: cardinal,
+    year_context: &[],
+    ordinal: Some((r"\.?[ºª°]", ordinal)),
+    dotted_ordinal: None,
+    decimal_separator: ',',
+    thousands_separators: &['.', ' ', '\u{a0}', '\u{202f}'],
+    decimal_point: "coma",
+    digits_after_the_point: false,
+    percent: "por ciento",
+    and: "con",
+    currencies: &CURRENCIES,
+    units: &UNITS,
+    abbreviations: &ABBREVIATIONS,
+};
+
+#[test]
+fn cardinals_use_the_spanish_compounds() {
+    assert_eq!(cardinal(16), "dieciséis");
+    assert_eq!(cardinal(35), "treinta y cinco");
+    assert_eq!(cardinal(100), "cien");
+    assert_eq!(cardinal(101), "ciento uno");
+    assert_eq!(cardinal(1999), "mil novecientos noventa y nueve");
+    assert_eq!(cardinal(21_000), "veintiún mil");
+    assert_eq!(cardinal(2_000_000), "dos millones");
+    assert_eq!(cardinal(1_000_000_000), "mil millones");
+}
+
+#[test]
+fn counts_and_ordinals_agree_with_the_noun() {
+    assert_eq!(count(1, false), "un");
+    assert_eq!(count(21, false), "veintiún");
+    assert_eq!(count(21, true), "veintiuna");
+    assert_eq!(count(200, true), "doscientas");
+    assert_eq!(ordinal(3, "º"), "tercero");
+    assert_eq!(ordinal(3, ".ª"), "tercera");
+}
@@ -0,0 +1,246 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::extract::{LANGUAGE_END, LANGUAGE_START, LANGUAGE_TAG_END};
+use crate::language::Language;
+use crate::lexicon::Lexicon;
+
+/// How passages in another language are marked in the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpanMode {
+    /// Plain text, with each passage read by its own language's rules
+    #[default]
+    Plain,
+    /// SSML, with each passage in a `<lang>` element
+    Ssml,
+    /// Plain text, with a `.spans.json` file saying where each passage starts and ends so a
+    /// multilingual TTS setup can switch voices
+    Voices,
+}
+
+/// Where a passage in another language is in the output text, in characters
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub language: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A run of text in one language, with no tag for the book's own language
+#[derive(Debug, PartialEq, Eq)]
+struct Segment {
+    tag: Option<String>,
+    text: String,
+}
+
+/// Adds text to the segments, joining it to the last one if it is in the same language
+fn push_segment(segments: &mut Vec<Segment>, tag: Option<&String>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(last) if last.tag.as_ref() == tag => last.text.push_str(text),
+        _ => segments.push(Segment {
+            tag: tag.cloned(),
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Splits text at the language markers.  A passage inside another takes the inner language.
+fn segments(text: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(marker) = rest.find([LANGUAGE_START, LANGUAGE_END]) {
+        push_segment(&mut segments, tags.last(), &rest[..marker]);
+        if let Some(after) = rest[marker..].strip_prefix(LANGUAGE_START) {
+            let (tag, after) = after.split_once(LANGUAGE_TAG_END).unwrap_or((after, ""));
+            tags.push(tag.to_string());
+            rest = after;
+        } else {
+            tags.pop();
+            rest = &rest[marker + LANGUAGE_END.len_utf8()..];
+        }
+    }
+    push_segment(&mut segments, tags.last(), rest);
+    segments
+}
+
+/// Removes the language markers, leaving just the text
+///
+/// # Arguments
+/// * `text` - The text, with the language markers from `extract_text_from_html`
+pub fn strip_markers(text: &str) -> String {
+    segments(text)
+        .into_iter()
+        .map(|segment| segment.text)
+        .collect()
+}
+
+/// Escapes text for SSML content and attribute values
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Converts text that may quote passages in other languages, reading each passage with the rules
+/// for its own language.  Passages in a language without rules are read with the book's.
+///
+/// # Arguments
+/// * `text` - The text, with the language markers from `extract_text_from_html`
+/// * `language` - The language of the book
+/// * `mode` - How the passages are marked in the output
+/// * `lexicon` - Pronunciations, respelled in plain text or written as SSML `<phoneme>` elements
+/// * `convert` - Converts a run of text in a language
+/// # Returns
+/// The converted text, and where each passage in another language is in it
+pub fn read_spans(
+    text: &str,
+    language: Language,
+    mode: SpanMode,
+    lexicon: &Lexicon,
+    mut convert: impl FnMut(&str, Language) -> String,
+) -> (String, Vec<Span>) {
+    let mut result = String::new();
+    let mut spans = Vec::new();
+    for segment in segments(text) {
+        let other = segment
+            .tag
+            .filter(|tag| Language::from_tag(tag) != Some(language));
+        let segment_language = other
+            .as_deref()
+            .and_then(Language::from_tag)
+            .unwrap_or(language);
+        let converted = convert(&segment.text, segment_language);
+        let converted = if mode == SpanMode::Ssml {
+            lexicon.to_ssml(&converted, segment_language)
+        } else {
+            lexicon.respell(&converted, segment_language)
+        };
+
+        match (mode, other) {
+            (SpanMode::Ssml, Some(tag)) => {
+                result.push_str("<lang xml:lang=\"");
+                result.push_str(&escape(&tag));
+                result.push_str("\">");
+                result.push_str(&converted);
+                result.push_str("</lang>");
+            }
+            (SpanMode::Ssml, None) => result.push_str(&converted),
+            (_, Some(tag)) => {
+                let start = result.chars().count();
+                result.push_str(&converted);
+                spans.push(Span {
+                    language: tag,
+                    start,
+                    end: result.chars().count(),
+                });
+            }
+            (_, None) => result.push_str(&converted),
+        }
+    }
+
+    if mode == SpanMode::Ssml {
+        result = format!(
+            "<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"{}\">\n{result}</speak>\n",
+            language.tag()
+        );
+    }
+    (result, spans)
+}
+
+#[cfg(test)]
+fn numbers(text: &str, language: Language) -> String {
+    language.convert_numbers_to_words(text)
+}
+
+#[test]
+fn each_passage_is_read_in_its_own_language() {
+    let text = "He counted 3 \u{E003}fr\u{E004}ou 21\u{E005} and \u{E003}la\u{E004}ad 2\u{E005}.\n";
+    let (result, spans) = read_spans(
+        text,
+        Language::English,
+        SpanMode::Plain,
+        &Lexicon::default(),
+        numbers,
+    );
+    assert_eq!(result, "He counted three ou vingt et un and ad two.\n");
+    assert_eq!(spans.len(), 2);
+}
+
+#[test]
+fn passages_in_the_book_language_are_not_spans() {
+    let text = "\u{E003}en-GB\u{E004}1 cat\u{E005}\n";
+    let (result, spans) = read_spans(
+        text,
+        Language::English,
+        SpanMode::Voices,
+        &Lexicon::default(),
+        numbers,
+    );
+    assert_eq!(result, "one cat\n");
+    assert!(spans.is_empty());
+}
+
+#[test]
+fn voices_give_the_character_offsets_of_each_passage() {
+    let text = "Ça \u{E003}de\u{E004}5 Äpfel\u{E005}.";
+    let (result, spans) = read_spans(
+        text,
+        Language::French,
+        SpanMode::Voices,
+        &Lexicon::default(),
+        numbers,
+    );
+    assert_eq!(result, "Ça fünf Äpfel.");
+    assert_eq!(
+        spans,
+        vec![Span {
+            language: "de".to_string(),
+            start: 3,
+            end: 13
+        }]
+    );
+}
+
+#[test]
+fn ssml_wraps_passages_in_lang_elements() {
+    let text = "Fish & \u{E003}fr\u{E004}frites\u{E005}\n";
+    let (result, _) = read_spans(
+        text,
+        Language::English,
+        SpanMode::Ssml,
+        &Lexicon::default(),
+        numbers,
+    );
+    assert_eq!(
+        result,
+        "<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"en\">\nFish &amp; <lang xml:lang=\"fr\">frites</lang>\n</speak>\n"
+    );
+}
+
+#[test]
+fn nested_passages_take_the_inner_language() {
+    let text = "\u{E003}de\u{E004}a \u{E003}fr\u{E004}b\u{E005} c\u{E005}";
+    assert_eq!(strip_markers(text), "a b c");
+    assert_eq!(
+        segments(text),
+        vec![
+            Segment {
+                tag: Some("de".to_string()),
+                text: "a ".to_string()
+            },
+            Segment {
+                tag: Some("fr".to_string()),
+                text: "b".to_string()
+            },
+            Segment {
+                tag: Some("de".to_string()),
+                text: " c".to_string()
+            },
+        ]
+    );
+}
@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+use std::fmt;
+
+/// Where a chapter title was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleSource {
+    Toc,
+    Heading,
+    SectionTag,
+    TitleTag,
+    Idref,
+}
+
+impl fmt::Display for TitleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Toc => "TOC",
+            Self::Heading => "Heading",
+            Self::SectionTag => "Section",
+            Self::TitleTag => "Title",
+            Self::Idref => "Idref",
+        };
+        f.pad(name)
+    }
+}
+
+/// The sources that are scored, in order of preference when scores tie.
+const SCORED_SOURCES: [TitleSource; 4] = [
+    TitleSource::Toc,
+    TitleSource::Heading,
+    TitleSource::SectionTag,
+    TitleSource::TitleTag,
+];
+
+/// Sources scoring below this are never used, not even as a per chapter fallback.
+const MINIMUM_SOURCE_SCORE: f64 = 0.2;
+
+/// Titles longer than this are most likely a paragraph that was picked up by mistake.
+const MAXIMUM_TITLE_LENGTH: usize = 120;
+
+/// All the title candidates found for a single chapter
+#[derive(Debug, Clone, Default)]
+pub struct TitleCandidates {
+    pub toc: String,
+    pub title_tag: String,
+    pub section_tag: String,
+    pub heading: String,
+}
+
+impl TitleCandidates {
+    fn get(&self, source: TitleSource) -> &str {
+        match source {
+            TitleSource::Toc => &self.toc,
+            TitleSource::Heading => &self.heading,
+            TitleSource::SectionTag => &self.section_tag,
+            TitleSource::TitleTag => &self.title_tag,
+            TitleSource::Idref => "",
+        }
+    }
+}
+
+/// The title chosen for a chapter, and where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterTitle {
+    pub title: String,
+    pub source: TitleSource,
+}
+
+/// Collapses whitespace and strips surrounding quotes so candidates from different sources can be
+/// compared.  Placeholder titles such as "Cover" are treated as empty.
+///
+/// # Arguments
+/// * `title` - The raw title candidate
+/// # Returns
+/// String of the normalized title, empty if it is not usable
+pub fn normalize_title(title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title = title.trim_matches(|c: char| c == '"' || c == '\'' || c.is_whitespace());
+    if title.eq_ignore_ascii_case("cover") {
+        return String::new();
+    }
+    title.to_string()
+}
+
+/// Returns true if a title is long enough to mean something and short enough to be a title
+pub fn is_usable(title: &str) -> bool {
+    let length = title.chars().count();
+    length > 2 && length <= MAXIMUM_TITLE_LENGTH
+}
+
+/// Reduces a title to lower case letters and digits so "CHAPTER I.\nDown" and "Chapter I Down"
+/// are seen as the same title.
+fn comparison_key(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn titles_agree(a: &str, b: &str) -> bool {
+    let a = comparison_key(a);
+    let b = comparison_key(b);
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    a.contains(b.as_str()) || b.contains(a.as_str())
+}
+
+/// Scores how trustworthy a title source is across the whole book.
+///
+/// The score weighs how many chapters have a usable title (emptiness), how many of those titles
+/// are unique, whether the titles are a sensible length and how often they agree with another
+/// source for the same chapter.
+///
+/// # Arguments
+/// * `candidates` - The title candidates for every chapter in the book
+/// * `source` - The source to score
+/// # Returns
+/// A score between 0 and 1
+#[allow(clippy::cast_precision_loss, clippy::suboptimal_flops)]
+pub fn score_source(candidates: &[TitleCandidates], source: TitleSource) -> f64 {
+    let usable: Vec<(usize, &str)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| (i, candidate.get(source)))
+        .filter(|(_, title)| is_usable(title))
+        .collect();
+
+    if usable.is_empty() {
+        return 0.0;
+    }
+
+    let total = candidates.len() as f64;
+    let count = usable.len() as f64;
+
+    let coverage = count / total;
+
+    let distinct: HashSet<String> = usable
+        .iter()
+        .map(|(_, title)| comparison_key(title))
+        .collect();
+    let uniqueness = distinct.len() as f64 / count;
+
+    let length = usable
+        .iter()
+        .map(|(_, title)| {
+            if title.chars().count() <= 80 {
+                1.0
+            } else {
+                0.5
+            }
+        })
+        .sum::<f64>()
+        / count;
+
+    let agreeing = usable
+        .iter()
+        .filter(|(i, title)| {
+            SCORED_SOURCES
+                .iter()
+                .filter(|other| **other != source)
+                .any(|other| titles_agree(title, candidates[*i].get(*other)))
+        })
+        .count() as f64;
+    let agreement = agreeing / count;
+
+    // A source that gives every chapter the same title is useless regardless of coverage.
+    uniqueness * (0.45 * coverage + 0.35 * agreement + 0.2 * length)
+}
+
+/// Orders the title sources from most to least trustworthy for this book
+///
+/// # Arguments
+/// * `candidates` - The title candidates for every chapter in the book
+/// # Returns
+/// Vector of sources and their scores, best first.  Ties keep the TOC first.
+pub fn rank_sources(candidates: &[TitleCandidates]) -> Vec<(TitleSource, f64)> {
+    let mut ranked: Vec<(TitleSource, f64)> = SCORED_SOURCES
+        .iter()
+        .map(|source| (*source, score_source(candidates, *source)))
+        .collect();
+    // sort_by is stable, so equal scores stay in preference order
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+/// Picks a title for every chapter.
+///
+/// The best scoring source for the book is used wherever it has a usable title.  Chapters where it
+/// does not fall back to the next best source that has a title unique to that chapter.
+///
+/// # Arguments
+/// * `candidates` - The title candidates for every chapter in the book
+/// # Returns
+/// Vector of chosen titles, an empty title with `TitleSource::Idref` where nothing was usable
+pub fn select_titles(candidates: &[TitleCandidates]) -> Vec<ChapterTitle> {
+    let ranked: Vec<TitleSource> = rank_sources(candidates)
+        .into_iter()
+        .filter(|(_, score)| *score >= MINIMUM_SOURCE_SCORE)
+        .map(|(source, _)| source)
+        .collect();
+
+    candidates
+        .iter()
+        .map(|candidate| {
+            ranked
+                .iter()
+                .enumerate()
+                .find(|(rank, source)| {
+                    let title = candidate.get(**source);
+                    if !is_usable(title) {
+                        return false;
+                    }
+                    // Fallback sources are only trusted for titles no other chapter shares
+                    *rank == 0
+                        || candidates
+                            .iter()
+                            .filter(|other| other.get(**source) == title)
+                            .count()
+                            == 1
+                })
+                .map_or_else(
+                    || ChapterTitle {
+                        title: String::new(),
+                        source: TitleSource::Idref,
+                    },
+                    |(_, source)| ChapterTitle {
+                        title: candidate.get(*source).to_string(),
+                        source: *source,
+                    },
+                )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn candidates(toc: &str, title_tag: &str, section_tag: &str, heading: &str) -> TitleCandidates {
+    TitleCandidates {
+        toc: toc.to_string(),
+        title_tag: title_tag.to_string(),
+        section_tag: section_tag.to_string(),
+        heading: heading.to_string(),
+    }
+}
+
+#[test]
+fn normalize_title_collapses_whitespace_and_quotes() {
+    assert_eq!(
+        normalize_title("  CHAPTER III.\nA Caucus-Race "),
+        "CHAPTER III. A Caucus-Race"
+    );
+    assert_eq!(normalize_title("\"Cover\""), "");
+    assert_eq!(normalize_title(""), "");
+}
+
+#[test]
+fn repeated_titles_score_below_unique_titles() {
+    let book = vec![
+        candidates("Chapter 1", "My Book", "", "Chapter 1"),
+        candidates("Chapter 2", "My Book", "", "Chapter 2"),
+        candidates("Chapter 3", "My Book", "", "Chapter 3"),
+    ];
+    assert!(
+        score_source(&book, TitleSource::Toc) > score_source(&book, TitleSource::TitleTag) + 0.3
+    );
+    assert!(score_source(&book, TitleSource::SectionTag).abs() < f64::EPSILON);
+}
+
+#[test]
+fn ties_prefer_the_toc() {
+    let book = vec![
+        candidates("Chapter 1", "", "", "Chapter 1"),
+        candidates("Chapter 2", "", "", "Chapter 2"),
+    ];
+    assert_eq!(rank_sources(&book)[0].0, TitleSource::Toc);
+}
+
+#[test]
+fn headings_are_chosen_when_toc_is_missing() {
+    let book = vec![
+        candidates("", "Book", "", "The Beginning"),
+        candidates("", "Book", "", "The Middle"),
+        candidates("", "Book", "", "The End"),
+    ];
+    let titles = select_titles(&book);
+    assert_eq!(titles[1].title, "The Middle");
+    assert_eq!(titles[1].source, TitleSource::Heading);
+}
+
+#[test]
+fn chapters_missing_from_the_best_source_fall_back() {
+    let book = vec![
+        candidates("", "Book", "Preface", ""),
+        candidates("One", "Book", "", "One"),
+        candidates("Two", "Book", "", "Two"),
+        candidates("Three", "Book", "", "Three"),
+    ];
+    let titles = select_titles(&book);
+    assert_eq!(titles[0].title, "Preface");
+    assert_eq!(titles[0].source, TitleSource::SectionTag);
+    assert_eq!(titles[2].source, TitleSource::Toc);
+}
+
+#[test]
+fn shared_fallback_titles_are_not_used() {
+    let book = vec![
+        candidates("", "Book", "", ""),
+        candidates("One", "Book", "", ""),
+        candidates("Two", "Book", "", ""),
+    ];
+    let titles = select_titles(&book);
+    assert_eq!(titles[0].title, "");
+    assert_eq!(titles[0].source, TitleSource::Idref);
+}
+
+#[test]
+fn titles_are_measured_in_characters() {
+    assert!(!is_usable("Ü1"));
+    assert!(is_usable("Über"));
+}
@@ -0,0 +1,113 @@
+use epub::doc::NavPoint;
+
+/// A single TOC entry, flattened out of the nested navMap/nav tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    pub label: String,
+    /// Path of the document inside the epub, without the fragment
+    pub path: String,
+    /// The `#anchor` part of the link, if there was one
+    pub fragment: Option<String>,
+    /// Labels of the entries this one is nested under, outermost first
+    pub parents: Vec<String>,
+}
+
+fn flatten_into(nav_points: &[NavPoint], parents: &[String], entries: &mut Vec<TocEntry>) {
+    for nav_point in nav_points {
+        let content = nav_point.content.to_string_lossy();
+        let (path, fragment) = match content.split_once('#') {
+            Some((path, fragment)) if !fragment.is_empty() => {
+                (path.to_string(), Some(fragment.to_string()))
+            }
+            Some((path, _)) => (path.to_string(), None),
+            None => (content.to_string(), None),
+        };
+
+        entries.push(TocEntry {
+            label: nav_point.label.trim().to_string(),
+            path,
+            fragment,
+            parents: parents.to_vec(),
+        });
+
+        let mut child_parents = parents.to_vec();
+        child_parents.push(nav_point.label.trim().to_string());
+        flatten_into(&nav_point.children, &child_parents, entries);
+    }
+}
+
+/// Walks the nested TOC and returns every entry in reading order
+///
+/// # Arguments
+/// * `toc` - The top level navigation points of the epub
+/// # Returns
+/// Vector of every TOC entry, parents before their children
+pub fn flatten_toc(toc: &[NavPoint]) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    flatten_into(toc, &[], &mut entries);
+    entries
+}
+
+/// Returns the TOC entries that point into a document
+///
+/// # Arguments
+/// * `entries` - The flattened TOC
+/// * `path` - path of the document inside the epub
+pub fn entries_for_path<'a>(entries: &'a [TocEntry], path: &str) -> Vec<&'a TocEntry> {
+    entries.iter().filter(|entry| entry.path == path).collect()
+}
+
+#[cfg(test)]
+fn nav_point(label: &str, content: &str, children: Vec<NavPoint>) -> NavPoint {
+    NavPoint {
+        label: label.to_string(),
+        content: content.into(),
+        children,
+        play_order: None,
+    }
+}
+
+#[test]
+fn flatten_toc_walks_children() {
+    let toc = vec![
+        nav_point(
+            "Part One",
+            "OEBPS/part1.xhtml",
+            vec![
+                nav_point("Chapter 1: The Beginning", "OEBPS/ch1.xhtml#start", vec![]),
+                nav_point("Chapter 2", "OEBPS/ch2.xhtml", vec![]),
+            ],
+        ),
+        nav_point("Part Two", "OEBPS/part2.xhtml#", vec![]),
+    ];
+    let entries = flatten_toc(&toc);
+
+    let labels: Vec<&str> = entries.iter().map(|e| e.label.as_str()).collect();
+    assert_eq!(
+        labels,
+        vec![
+            "Part One",
+            "Chapter 1: The Beginning",
+            "Chapter 2",
+            "Part Two"
+        ]
+    );
+    assert_eq!(entries[1].path, "OEBPS/ch1.xhtml");
+    assert_eq!(entries[1].fragment, Some("start".to_string()));
+    assert_eq!(entries[1].parents, vec!["Part One"]);
+    assert_eq!(entries[3].fragment, None);
+    assert!(entries[3].parents.is_empty());
+}
+
+#[test]
+fn entries_for_path_matches_whole_path() {
+    let toc = vec![
+        nav_point("One", "OEBPS/ch1.xhtml#a", vec![]),
+        nav_point("Ten", "OEBPS/ch10.xhtml", vec![]),
+        nav_point("One again", "OEBPS/ch1.xhtml#b", vec![]),
+    ];
+    let entries = flatten_toc(&toc);
+    let found = entries_for_path(&entries, "OEBPS/ch1.xhtml");
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[1].label, "One again");
+}
@@ -0,0 +1,331 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs;
+use unicode_normalization::UnicodeNormalization;
+
+/// What dashes between words are read as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DashStyle {
+    /// A comma, eg "wait—what" -> "wait, what"
+    #[default]
+    Comma,
+    /// An ellipsis, for a longer pause, eg "wait... what"
+    Ellipsis,
+    /// Leave dashes as they are
+    Keep,
+}
+
+/// Characters mapped before NFKC, which would otherwise be left alone or turned into something
+/// the phonemizer reads badly
+const MAPPINGS: [(&str, &str); 27] = [
+    // Invisible characters that split words
+    ("\u{00AD}", ""),
+    ("\u{200B}", ""),
+    ("\u{200C}", ""),
+    ("\u{200D}", ""),
+    ("\u{2060}", ""),
+    ("\u{FEFF}", ""),
+    // Quotes and primes
+    ("\u{2018}", "'"),
+    ("\u{2019}", "'"),
+    ("\u{201A}", "'"),
+    ("\u{201B}", "'"),
+    ("\u{2039}", "'"),
+    ("\u{203A}", "'"),
+    ("\u{2032}", "'"),
+    ("\u{201C}", "\""),
+    ("\u{201D}", "\""),
+    ("\u{201E}", "\""),
+    ("\u{201F}", "\""),
+    ("\u{00AB}", "\""),
+    ("\u{00BB}", "\""),
+    ("\u{2033}", "\""),
+    // Hyphens and minus signs
+    ("\u{2010}", "-"),
+    ("\u{2011}", "-"),
+    ("\u{2212}", "-"),
+    ("\u{2044}", "/"),
+    // Punctuation
+    ("\u{2026}", "..."),
+    ("\u{2022}", ""),
+    ("\u{2023}", ""),
+];
+
+/// Characters NFKC would break up that later stages read as they are, eg "m²", "2½" and "3.ª"
+const fn is_preserved(c: char) -> bool {
+    matches!(
+        c,
+        '¹' | '²' | '³' | 'ª' | 'º' | '¼' | '½' | '¾' | '\u{2150}'..='\u{215E}'
+    )
+}
+
+/// Symbols the later stages read, so they aren't reported
+const KNOWN_SYMBOLS: [char; 12] = ['€', '£', '¥', '₹', '¢', '₩', '₽', '₺', '₪', '฿', '°', '§'];
+
+/// Replaces characters that confuse the TTS phonemizer with plain ones: NFKC (so "ﬁ" is "fi" and
+/// full width letters are normal ones), plus mappings for soft hyphens, zero width spaces, curly
+/// quotes, primes and ellipses, and dashes read as pauses
+#[derive(Debug, Clone)]
+pub struct Typography {
+    mappings: Vec<(String, String)>,
+    dashes: DashStyle,
+}
+
+/// Reads a line of a typography file, `character==replacement`.  The character can be written
+/// as a code point, eg `U+2022`, and the replacement can be empty to remove it.
+fn process_line(text: &str) -> Option<(String, String)> {
+    let text = text.trim_end_matches(['\r', '\n']);
+    if text.trim().is_empty() || text.starts_with('#') {
+        return None;
+    }
+    let Some((from, to)) = text.split_once("==") else {
+        println!("Typography File Syntax error - Ignoring line, no '==' found: {text}");
+        return None;
+    };
+    let from = from.trim();
+    let from = from
+        .strip_prefix("U+")
+        .and_then(|code| u32::from_str_radix(code, 16).ok())
+        .and_then(char::from_u32)
+        .map_or_else(|| from.to_string(), String::from);
+    Some((from, to.to_string()))
+}
+
+impl Typography {
+    /// Returns the built in mappings
+    ///
+    /// # Arguments
+    /// * `dashes` - What dashes between words are read as
+    pub fn new(dashes: DashStyle) -> Self {
+        Self {
+            mappings: MAPPINGS
+                .iter()
+                .map(|(from, to)| ((*from).to_string(), (*to).to_string()))
+                .collect(),
+            dashes,
+        }
+    }
+
+    /// Adds mappings, replacing any built in mapping for the same character
+    ///
+    /// # Arguments
+    /// * `text` - The contents of a typography file
+    pub fn extend_from_text(&mut self, text: &str) {
+        for (from, to) in text.lines().filter_map(process_line) {
+            self.mappings.retain(|(existing, _)| *existing != from);
+            self.mappings.push((from, to));
+        }
+    }
+
+    /// Loads the built in mappings, extended by a user file if it exists
+    ///
+    /// # Arguments
+    /// * `filename` - The user's typography file
+    /// * `dashes` - What dashes between words are read as
+    pub fn load(filename: &str, dashes: DashStyle) -> Self {
+        let mut typography = Self::new(dashes);
+        if let Ok(file_text) = fs::read_to_string(filename) {
+            println!("Opening user typography mappings: {filename}");
+            typography.extend_from_text(&file_text);
+        }
+        typography
+    }
+
+    /// Normalizes the typography of a text
+    ///
+    /// # Arguments
+    /// * `text` - The text to convert
+    /// # Returns
+    /// The text with plain characters
+    pub fn normalize(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (from, to) in &self.mappings {
+            text = text.replace(from.as_str(), to);
+        }
+        let text = nfkc(&text);
+        self.convert_dashes(&text)
+    }
+
+    /// Turns dashes between words into pauses, eg "wait—what" -> "wait, what".  A dash at the
+    /// start of a line (as in French and Spanish dialogue) is dropped, a dash between numbers is
+    /// left for the number stage to read as a range, and an en dash joining words is a hyphen.
+    fn convert_dashes(&self, text: &str) -> String {
+        let pause = match self.dashes {
+            DashStyle::Comma => ",",
+            DashStyle::Ellipsis => "...",
+            DashStyle::Keep => return text.to_string(),
+        };
+        let re = Regex::new(r"[ \t]*(?:—|―|⸺|⸻|--+|–)[ \t]*").unwrap();
+
+        let mut result = String::new();
+        let mut last = 0;
+        for dash in re.find_iter(text) {
+            let mut preceding = text[..dash.start()].chars().rev();
+            let before = preceding.next();
+            // An opening quote or bracket, eg "—yes" or ("—or so)
+            let opening = matches!(before, Some('"' | '\'' | '('))
+                && preceding.next().is_none_or(char::is_whitespace);
+            let after = text[dash.end()..].chars().next();
+            let spaced =
+                dash.as_str().starts_with([' ', '\t']) || dash.as_str().ends_with([' ', '\t']);
+            let replacement = match (before, after) {
+                (Some(before), Some(after))
+                    if !spaced && before.is_ascii_digit() && after.is_ascii_digit() =>
+                {
+                    dash.as_str().to_string()
+                }
+                _ if !spaced && dash.as_str() == "–" => "-".to_string(),
+                _ if opening => String::new(),
+                (None | Some('\n'), _) => String::new(),
+                (Some('.' | ',' | '!' | '?' | ';' | ':'), Some(after))
+                    if after.is_alphanumeric() =>
+                {
+                    " ".to_string()
+                }
+                (Some('.' | ',' | '!' | '?' | ';' | ':'), _)
+                | (_, Some('.' | ',' | '!' | '?' | ';' | ':')) => String::new(),
+                (_, None | Some('\n' | '"' | '\'' | ')')) => pause.to_string(),
+                _ => format!("{pause} "),
+            };
+            result.push_str(&text[last..dash.start()]);
+            result.push_str(&replacement);
+            last = dash.end();
+        }
+        result.push_str(&text[last..]);
+        result
+    }
+}
+
+/// NFKC, apart from the characters that are read as they are
+fn nfkc(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::new();
+    let mut run = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        // A non breaking space between digits groups thousands, eg "1 000"
+        let digit_group = matches!(c, '\u{a0}' | '\u{202f}')
+            && i > 0
+            && chars[i - 1].is_ascii_digit()
+            && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+        if is_preserved(c) || digit_group {
+            result.extend(run.nfkc());
+            run.clear();
+            result.push(c);
+        } else {
+            run.push(c);
+        }
+    }
+    result.extend(run.nfkc());
+    result
+}
+
+/// The characters left after normalization that the TTS engine may not read, and how often they
+/// were found
+#[derive(Debug, Default)]
+pub struct Report {
+    unmapped: BTreeMap<char, usize>,
+}
+
+impl Report {
+    /// Counts the characters in a normalized text that weren't mapped to plain ones
+    ///
+    /// # Arguments
+    /// * `text` - The normalized text
+    pub fn record(&mut self, text: &str) {
+        let unmapped = text.chars().filter(|c| {
+            !(c.is_ascii()
+                || c.is_alphanumeric()
+                || c.is_whitespace()
+                || KNOWN_SYMBOLS.contains(c)
+                || ('\u{E000}'..='\u{F8FF}').contains(c))
+        });
+        for c in unmapped {
+            *self.unmapped.entry(c).or_default() += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unmapped.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.unmapped.len()
+    }
+
+    /// Returns the report, one character per line with its code point and count, in a form that
+    /// can be copied into a typography file
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            "# Characters that were not mapped, and how often they were found.  Copy a line into"
+                .to_string(),
+            "# typography.conf as U+XXXX==replacement to map it.".to_string(),
+        ];
+        lines.extend(
+            self.unmapped
+                .iter()
+                .map(|(c, count)| format!("U+{:04X}  {c}  {count}", u32::from(*c))),
+        );
+        lines.join("\n") + "\n"
+    }
+}
+
+#[test]
+fn invisible_characters_and_ligatures_are_removed() {
+    let typography = Typography::new(DashStyle::Comma);
+    assert_eq!(
+        typography.normalize("in\u{00AD}cred\u{200B}ible ﬁne ﬂower ＡＢＣ"),
+        "incredible fine flower ABC"
+    );
+}
+
+#[test]
+fn quotes_primes_and_ellipses_are_plain() {
+    let typography = Typography::new(DashStyle::Comma);
+    assert_eq!(
+        typography.normalize("“It’s 5′ tall…” «oui»"),
+        "\"It's 5' tall...\" \"oui\""
+    );
+}
+
+#[test]
+fn superscripts_fractions_and_digit_groups_are_kept() {
+    let typography = Typography::new(DashStyle::Comma);
+    assert_eq!(
+        typography.normalize("5 m², 2½ cups, la 3.ª, 1\u{a0}000 and a\u{a0}b"),
+        "5 m², 2½ cups, la 3.ª, 1\u{a0}000 and a b"
+    );
+}
+
+#[test]
+fn dashes_become_pauses() {
+    let typography = Typography::new(DashStyle::Comma);
+    assert_eq!(
+        typography.normalize("wait—what? It was — I think — 1914–1918, Paris–London."),
+        "wait, what? It was, I think, 1914–1918, Paris-London."
+    );
+    assert_eq!(
+        typography.normalize("—Sí —dijo él—. Bien.\n\"But—\" he said \"—no.\""),
+        "Sí, dijo él. Bien.\n\"But,\" he said \"no.\""
+    );
+    let typography = Typography::new(DashStyle::Ellipsis);
+    assert_eq!(typography.normalize("wait--what"), "wait... what");
+}
+
+#[test]
+fn users_can_add_mappings() {
+    let mut typography = Typography::new(DashStyle::Keep);
+    typography.extend_from_text("# comment\nU+2605==star\n©==copyright ");
+    assert_eq!(typography.normalize("a ★ — © b"), "a star — copyright  b");
+}
+
+#[test]
+fn unmapped_characters_are_reported() {
+    let mut report = Report::default();
+    report.record("Plain café, €5, 5°, ★ and ★ and ♠");
+    assert_eq!(report.len(), 2);
+    assert_eq!(
+        report.to_text().lines().skip(2).collect::<Vec<_>>(),
+        vec!["U+2605  ★  2", "U+2660  ♠  1"]
+    );
+}
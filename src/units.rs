@@ -0,0 +1,354 @@
+use regex::Regex;
+
+/// A unit of measure and the ways it is abbreviated
+struct Unit {
+    symbols: &'static [&'static str],
+    singular: &'static str,
+    plural: &'static str,
+    /// Can be expanded without a number in front of it, only for abbreviations that are never
+    /// words in their own right
+    standalone: bool,
+    /// Can be written straight after the number, eg "5km".  Off for abbreviations that could be
+    /// the end of a word, eg the "s" of "1960s".
+    attached: bool,
+}
+
+const fn unit(
+    symbols: &'static [&'static str],
+    singular: &'static str,
+    plural: &'static str,
+) -> Unit {
+    Unit {
+        symbols,
+        singular,
+        plural,
+        standalone: false,
+        attached: true,
+    }
+}
+
+const UNITS: [Unit; 61] = [
+    // Speed
+    Unit {
+        standalone: true,
+        ..unit(
+            &["kph", "k.p.h.", "km/h"],
+            "kilometer per hour",
+            "kilometers per hour",
+        )
+    },
+    Unit {
+        standalone: true,
+        ..unit(&["mph", "m.p.h."], "mile per hour", "miles per hour")
+    },
+    unit(&["m/s"], "meter per second", "meters per second"),
+    unit(&["kn", "kts"], "knot", "knots"),
+    // Distance
+    unit(&["nm"], "nanometer", "nanometers"),
+    unit(&["mm"], "millimeter", "millimeters"),
+    unit(&["cm"], "centimeter", "centimeters"),
+    unit(&["m"], "meter", "meters"),
+    unit(&["km"], "kilometer", "kilometers"),
+    unit(&["ft", "ft."], "foot", "feet"),
+    unit(&["yd", "yds"], "yard", "yards"),
+    unit(&["mi"], "mile", "miles"),
+    unit(&["nmi"], "nautical mile", "nautical miles"),
+    // Area
+    unit(&["mm²"], "square millimeter", "square millimeters"),
+    unit(&["cm²"], "square centimeter", "square centimeters"),
+    unit(&["m²", "sq m"], "square meter", "square meters"),
+    unit(&["km²", "sq km"], "square kilometer", "square kilometers"),
+    unit(&["sq ft"], "square foot", "square feet"),
+    unit(&["sq mi"], "square mile", "square miles"),
+    unit(&["ha"], "hectare", "hectares"),
+    // Volume
+    unit(&["ml", "mL"], "milliliter", "milliliters"),
+    unit(&["cl", "cL"], "centiliter", "centiliters"),
+    unit(&["l", "L"], "liter", "liters"),
+    unit(&["cc"], "cubic centimeter", "cubic centimeters"),
+    unit(&["m³"], "cubic meter", "cubic meters"),
+    unit(&["fl oz", "fl. oz."], "fluid ounce", "fluid ounces"),
+    unit(&["tsp"], "teaspoon", "teaspoons"),
+    unit(&["tbsp"], "tablespoon", "tablespoons"),
+    unit(&["pt"], "pint", "pints"),
+    unit(&["qt"], "quart", "quarts"),
+    unit(&["gal"], "gallon", "gallons"),
+    // Mass
+    unit(&["mg"], "milligram", "milligrams"),
+    unit(&["g"], "gram", "grams"),
+    unit(&["kg"], "kilogram", "kilograms"),
+    unit(&["oz", "oz."], "ounce", "ounces"),
+    unit(&["lb", "lbs", "lb.", "lbs."], "pound", "pounds"),
+    // Temperature
+    unit(&["°C", "° C", "℃"], "degree Celsius", "degrees Celsius"),
+    unit(
+        &["°F", "° F", "℉"],
+        "degree Fahrenheit",
+        "degrees Fahrenheit",
+    ),
+    unit(&["°"], "degree", "degrees"),
+    // Time
+    unit(&["ms"], "millisecond", "milliseconds"),
+    Unit {
+        attached: false,
+        ..unit(&["s", "sec", "secs"], "second", "seconds")
+    },
+    unit(&["min", "mins"], "minute", "minutes"),
+    unit(&["hr", "hrs", "h"], "hour", "hours"),
+    // Data
+    unit(&["KB", "kB"], "kilobyte", "kilobytes"),
+    unit(&["MB"], "megabyte", "megabytes"),
+    unit(&["GB"], "gigabyte", "gigabytes"),
+    unit(&["TB"], "terabyte", "terabytes"),
+    unit(
+        &["kbps", "kb/s"],
+        "kilobit per second",
+        "kilobits per second",
+    ),
+    unit(
+        &["Mbps", "Mb/s"],
+        "megabit per second",
+        "megabits per second",
+    ),
+    unit(
+        &["Gbps", "Gb/s"],
+        "gigabit per second",
+        "gigabits per second",
+    ),
+    // Frequency, power and energy
+    unit(&["Hz"], "hertz", "hertz"),
+    unit(&["kHz"], "kilohertz", "kilohertz"),
+    unit(&["MHz"], "megahertz", "megahertz"),
+    unit(&["GHz"], "gigahertz", "gigahertz"),
+    unit(&["kW"], "kilowatt", "kilowatts"),
+    unit(&["MW"], "megawatt", "megawatts"),
+    unit(&["kWh"], "kilowatt hour", "kilowatt hours"),
+    unit(&["mAh"], "milliamp hour", "milliamp hours"),
+    unit(&["hp"], "horsepower", "horsepower"),
+    unit(&["kcal"], "kilocalorie", "kilocalories"),
+    unit(&["psi"], "pound per square inch", "pounds per square inch"),
+];
+
+fn find_unit(symbol: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.symbols.contains(&symbol))
+}
+
+/// Builds a regex alternation of every symbol, longest first so "km²" wins over "km"
+fn symbols_pattern(units: &[&Unit]) -> String {
+    let mut symbols: Vec<&str> = units
+        .iter()
+        .flat_map(|unit| unit.symbols.iter().copied())
+        .collect();
+    symbols.sort_by_key(|symbol| std::cmp::Reverse(symbol.chars().count()));
+    symbols
+        .iter()
+        .map(|symbol| regex::escape(symbol))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// A dotted abbreviation swallows the full stop at the end of a sentence, so put it back if the
+/// sentence or paragraph carries on
+fn sentence_end(symbol: &str, rest: &str) -> &'static str {
+    let next_sentence = Regex::new(r"^(\n|\s+[A-Z])").unwrap();
+    if symbol.ends_with('.') && next_sentence.is_match(rest) {
+        "."
+    } else {
+        ""
+    }
+}
+
+fn is_word_character(c: Option<char>) -> bool {
+    c.is_some_and(char::is_alphanumeric)
+}
+
+/// Expands abbreviated units of measure after a number, eg "5 km" -> "5 kilometers",
+/// "1 kg" -> "1 kilogram", "-5°C" -> "minus 5 degrees Celsius", "a 10-mm bolt" -> "a 10-millimeter
+/// bolt".  The handful of abbreviations that are never words, eg "mph", are also expanded on
+/// their own.  Only whole tokens are matched, so "triumph" stays as it is.
+///
+/// # Arguments
+/// * `text` - The text to convert
+/// # Returns
+/// The text with units in words
+pub fn convert_units_to_words(text: &str) -> String {
+    let all: Vec<&Unit> = UNITS.iter().collect();
+    let re = Regex::new(&format!(
+        r"(?<minus>[-−])?(?<number>\d{{1,3}}(?:,\d{{3}})+(?:\.\d+)?|\d+(?:\.\d+)?)(?<separator>[ \u{{a0}}-]?)(?<symbol>{})",
+        symbols_pattern(&all)
+    ))
+    .unwrap();
+
+    let mut result = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let symbol = &caps["symbol"];
+        let separator = &caps["separator"];
+        let Some(unit) = find_unit(symbol) else {
+            continue;
+        };
+
+        let before = text[..whole.start()].chars().next_back();
+        let after = text[whole.end()..].chars().next();
+        let number_start = caps.name("number").unwrap().start();
+        if is_word_character(text[..number_start].chars().next_back())
+            || is_word_character(after)
+            || (separator.is_empty() && !unit.attached)
+        {
+            continue;
+        }
+
+        // Only a minus sign at the start of a word, not a range such as 10-20°C
+        let minus = match caps.name("minus") {
+            Some(_) if !is_word_character(before) => "minus ",
+            Some(minus) => minus.as_str(),
+            None => "",
+        };
+        // A number written as an adjective is singular, eg "a 5-km run"
+        let (separator, name) = match (separator, &caps["number"]) {
+            ("-", _) => ("-", unit.singular),
+            (_, "1") => (" ", unit.singular),
+            _ => (" ", unit.plural),
+        };
+
+        result.push_str(&text[last..whole.start()]);
+        result.push_str(minus);
+        result.push_str(&caps["number"]);
+        result.push_str(separator);
+        result.push_str(name);
+        result.push_str(sentence_end(symbol, &text[whole.end()..]));
+        last = whole.end();
+    }
+    result.push_str(&text[last..]);
+
+    convert_standalone_units(&result)
+}
+
+/// Expands the units that don't need a number in front of them
+fn convert_standalone_units(text: &str) -> String {
+    let standalone: Vec<&Unit> = UNITS.iter().filter(|unit| unit.standalone).collect();
+    let re = Regex::new(&symbols_pattern(&standalone)).unwrap();
+
+    let mut result = String::new();
+    let mut last = 0;
+    for symbol in re.find_iter(text) {
+        let before = text[..symbol.start()].chars().next_back();
+        let after = text[symbol.end()..].chars().next();
+        let Some(unit) = find_unit(symbol.as_str()) else {
+            continue;
+        };
+        if is_word_character(before) || is_word_character(after) {
+            continue;
+        }
+
+        result.push_str(&text[last..symbol.start()]);
+        result.push_str(unit.plural);
+        result.push_str(sentence_end(symbol.as_str(), &text[symbol.end()..]));
+        last = symbol.end();
+    }
+    result.push_str(&text[last..]);
+    result
+}
+
+#[test]
+fn units_are_expanded_after_a_number() {
+    assert_eq!(
+        convert_units_to_words("It weighed 5 kg and was 12mm wide, 3 ft long."),
+        "It weighed 5 kilograms and was 12 millimeters wide, 3 feet long."
+    );
+    assert_eq!(
+        convert_units_to_words("1 km, 2.5 km and 1,500 km"),
+        "1 kilometer, 2.5 kilometers and 1,500 kilometers"
+    );
+}
+
+#[test]
+fn units_are_only_matched_as_whole_tokens() {
+    assert_eq!(
+        convert_units_to_words("a triumph for 5 men in 3 minutes in the 1960s"),
+        "a triumph for 5 men in 3 minutes in the 1960s"
+    );
+    assert_eq!(convert_units_to_words("5 g"), "5 grams");
+    assert_eq!(convert_units_to_words("the g string"), "the g string");
+}
+
+#[test]
+fn temperatures_are_expanded() {
+    assert_eq!(
+        convert_units_to_words("It was -5°C, or 23 °F, and the slope was 1°."),
+        "It was minus 5 degrees Celsius, or 23 degrees Fahrenheit, and the slope was 1 degree."
+    );
+}
+
+#[test]
+fn adjectives_are_singular() {
+    assert_eq!(convert_units_to_words("a 10-km run"), "a 10-kilometer run");
+}
+
+#[test]
+fn area_data_and_frequency_units_are_expanded() {
+    assert_eq!(
+        convert_units_to_words("50 m², 16 GB, 100 Mbps and 2.4 GHz"),
+        "50 square meters, 16 gigabytes, 100 megabits per second and 2.4 gigahertz"
+    );
+}
+
+#[test]
+fn dotted_units_keep_the_sentence_full_stop() {
+    assert_eq!(
+        convert_units_to_words("It weighed 5 lbs. The rest"),
+        "It weighed 5 pounds. The rest"
+    );
+    assert_eq!(
+        convert_units_to_words("5 lbs. of flour"),
+        "5 pounds of flour"
+    );
+}
+
+#[test]
+fn speed_acronyms_are_expanded() {
+    let text = "kph";
+    assert_eq!(
+        convert_units_to_words(text),
+        "kilometers per hour".to_string()
+    );
+
+    let text = "k.p.h.";
+    assert_eq!(
+        convert_units_to_words(text),
+        "kilometers per hour".to_string()
+    );
+    // Check for m.p.h. being at end of sentence, and keep period
+    let text = "k.p.h. The";
+    assert_eq!(
+        convert_units_to_words(text),
+        "kilometers per hour. The".to_string()
+    );
+
+    // Check for m.p.h. being at end of a paragraph, and keep period
+    let text = "k.p.h.\n";
+    assert_eq!(
+        convert_units_to_words(text),
+        "kilometers per hour.\n".to_string()
+    );
+
+    let text = "mph";
+    assert_eq!(convert_units_to_words(text), "miles per hour".to_string());
+
+    let text = "m.p.h.";
+    assert_eq!(convert_units_to_words(text), "miles per hour".to_string());
+    // Check for m.p.h. being at end of sentence, and keep period
+    let text = "m.p.h. The";
+    assert_eq!(
+        convert_units_to_words(text),
+        "miles per hour. The".to_string()
+    );
+
+    // Check for m.p.h. being at end of a paragraph, and keep period
+    let text = "m.p.h.\n";
+    assert_eq!(
+        convert_units_to_words(text),
+        "miles per hour.\n".to_string()
+    );
+}
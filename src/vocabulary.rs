@@ -0,0 +1,234 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+use crate::hyphenation::is_word;
+
+/// How the out of vocabulary report is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A stub lexicon, `Word==Word`, to fill in and save as lexicon.conf
+    #[default]
+    Lexicon,
+    /// A block of `\bWord\b==Word` lines to fill in and add to custom-replacements.conf
+    Replacements,
+}
+
+impl ReportFormat {
+    /// Returns the name of the report file in the output directory
+    pub const fn filename(self) -> &'static str {
+        match self {
+            Self::Lexicon => "oov-lexicon.conf",
+            Self::Replacements => "oov-replacements.conf",
+        }
+    }
+}
+
+/// The endings of contractions, eg "didn't", "she'll" and "we've"
+const CONTRACTIONS: [&str; 7] = ["s", "t", "ll", "re", "ve", "d", "m"];
+
+/// Splits a contraction into its word and ending, eg "Tharn's" -> ("Tharn", "s")
+fn split_contraction(word: &str) -> Option<(&str, &str)> {
+    word.split_once('\'')
+        .filter(|(_, ending)| CONTRACTIONS.contains(&ending.to_lowercase().as_str()))
+}
+
+/// Returns true if the word is in the word list, or is a contraction of one that is
+fn is_known(word: &str) -> bool {
+    is_word(word)
+        || split_contraction(word).is_some_and(|(stem, ending)| {
+            // "n't" belongs to the ending, eg "didn't" is "did" + "n't"
+            let stem = if ending.eq_ignore_ascii_case("t") {
+                stem.strip_suffix(['n', 'N']).unwrap_or(stem)
+            } else {
+                stem
+            };
+            is_word(stem)
+        })
+}
+
+/// A word the word list doesn't know
+#[derive(Debug)]
+struct Word {
+    /// How often each way of writing it was found, eg "Gryphon" and "GRYPHON"
+    forms: HashMap<String, usize>,
+    count: usize,
+    /// The chapter it was first found in
+    first_chapter: String,
+    /// The order it was first found in, to keep the report stable
+    order: usize,
+}
+
+impl Word {
+    /// Returns the most common way of writing the word, preferring capitals if two are as common
+    fn form(&self) -> &str {
+        self.forms
+            .iter()
+            .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+            .map_or("", |(form, _)| form.as_str())
+    }
+}
+
+/// The words in a book that are missing from the bundled English word list, so the proper nouns
+/// and invented words can be given a pronunciation before the book is read
+#[derive(Debug, Default)]
+pub struct Report {
+    words: HashMap<String, Word>,
+    /// Words in lower case that are pronounced some other way, eg by the lexicon
+    known: HashSet<String>,
+}
+
+impl Report {
+    /// Returns a report that leaves out words already given a pronunciation
+    ///
+    /// # Arguments
+    /// * `known` - Words and respellings that aren't reported, eg "Hermione" and "Her-my-oh-nee"
+    pub fn new<'a>(known: impl IntoIterator<Item = &'a str>) -> Self {
+        let re = Regex::new(r"\p{L}+").unwrap();
+        Self {
+            words: HashMap::new(),
+            known: known
+                .into_iter()
+                .flat_map(|text| re.find_iter(text).map(|m| m.as_str().to_lowercase()))
+                .collect(),
+        }
+    }
+
+    /// Counts the unknown words in a chapter's cleansed text
+    ///
+    /// # Arguments
+    /// * `text` - The cleansed text, which may contain SSML elements
+    /// * `chapter` - The name of the chapter, for the first place each word is found
+    pub fn record(&mut self, text: &str, chapter: &str) {
+        let text = Regex::new(r"<[^>]*>").unwrap().replace_all(text, " ");
+        let re = Regex::new(r"\p{L}+(?:'\p{L}+)*").unwrap();
+        for word in re.find_iter(&text).map(|m| m.as_str()) {
+            if is_known(word) {
+                continue;
+            }
+            let word = split_contraction(word).map_or(word, |(stem, _)| stem);
+            if word.chars().count() < 2 || self.known.contains(&word.to_lowercase()) {
+                continue;
+            }
+            let order = self.words.len();
+            let entry = self
+                .words
+                .entry(word.to_lowercase())
+                .or_insert_with(|| Word {
+                    forms: HashMap::new(),
+                    count: 0,
+                    first_chapter: chapter.to_string(),
+                    order,
+                });
+            entry.count += 1;
+            *entry.forms.entry(word.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns the report, most frequent words first, as stub lines that can be edited and
+    /// copied into a lexicon or custom replacements file.  Each line is left reading the word as
+    /// it is, after a comment with how often it was found and where it was found first.
+    ///
+    /// # Arguments
+    /// * `format` - Whether to write lexicon or custom replacement lines
+    pub fn to_text(&self, format: ReportFormat) -> String {
+        let destination = match format {
+            ReportFormat::Lexicon => "save the file as lexicon.conf",
+            ReportFormat::Replacements => "copy the lines into custom-replacements.conf",
+        };
+        let mut lines = vec![
+            "# Words that are not in the English word list, most frequent first.  Change the"
+                .to_string(),
+            format!(
+                "# pronunciation after '==' of each word to keep, delete the rest and {destination}."
+            ),
+        ];
+        let mut words: Vec<&Word> = self.words.values().collect();
+        words.sort_by(|a, b| b.count.cmp(&a.count).then(a.order.cmp(&b.order)));
+        for word in words {
+            let form = word.form();
+            lines.push(String::new());
+            lines.push(format!(
+                "# {} found {}, first in {}",
+                form,
+                if word.count == 1 {
+                    "once".to_string()
+                } else {
+                    format!("{} times", word.count)
+                },
+                word.first_chapter
+            ));
+            lines.push(match format {
+                ReportFormat::Lexicon => format!("{form}=={form}"),
+                ReportFormat::Replacements => format!("\\b{form}\\b=={form}"),
+            });
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+#[test]
+fn unknown_words_are_counted_with_their_first_chapter() {
+    let mut report = Report::default();
+    report.record(
+        "The Gryphon didn't see Tharn's <sub alias=\"x\">cat</sub>.",
+        "0001_One",
+    );
+    report.record("GRYPHON! said the Gryphon to Tharn in Xanadoo.", "0002_Two");
+    assert_eq!(report.len(), 3);
+    assert_eq!(
+        report
+            .to_text(ReportFormat::Lexicon)
+            .lines()
+            .skip(2)
+            .collect::<Vec<_>>(),
+        vec![
+            "",
+            "# Gryphon found 3 times, first in 0001_One",
+            "Gryphon==Gryphon",
+            "",
+            "# Tharn found 2 times, first in 0001_One",
+            "Tharn==Tharn",
+            "",
+            "# Xanadoo found once, first in 0002_Two",
+            "Xanadoo==Xanadoo",
+        ]
+    );
+    assert!(
+        report
+            .to_text(ReportFormat::Replacements)
+            .ends_with("# Xanadoo found once, first in 0002_Two\n\\bXanadoo\\b==Xanadoo\n")
+    );
+}
+
+#[test]
+fn words_with_a_pronunciation_are_not_reported() {
+    let mut report = Report::new(["Hermione", "Her-my-oh-nee", "Ron"]);
+    report.record("Hermione's wand, Ron and Her-my-oh-nee", "0003_Three");
+    report.record("Zarnithor", "0004_Four");
+    assert_eq!(report.len(), 1);
+    assert!(
+        report
+            .to_text(ReportFormat::Replacements)
+            .ends_with("# Zarnithor found once, first in 0004_Four\n\\bZarnithor\\b==Zarnithor\n")
+    );
+}
+
+#[test]
+fn contractions_of_known_words_are_not_reported() {
+    let mut report = Report::default();
+    report.record(
+        "didn't, can't, she'll, we've, I'm and Tharn's Tharn",
+        "0001_One",
+    );
+    assert_eq!(report.len(), 1);
+    assert!(
+        report
+            .to_text(ReportFormat::Lexicon)
+            .contains("# Tharn found 2 times")
+    );
+}